@@ -1,9 +1,10 @@
+use crate::value::Value;
 use serde::{Deserialize, Serialize};
 use std::io::{self, Read, Write};
 use std::net::TcpStream;
 
 #[derive(Serialize, Deserialize)]
-enum ClientCommand {
+pub enum ClientCommand {
     Connect {
         db_name: String,
         encryption_key: String,
@@ -18,16 +19,31 @@ enum ClientCommand {
     Restore {
         db_name: String,
         backup_file: String,
+        encryption_key: String,
     },
     BeginTransaction,
     CommitTransaction,
     RollbackTransaction,
-}
-
-#[derive(Serialize, Deserialize)]
-enum ServerResponse {
-    Success(String),
-    Error(String),
+    Savepoint {
+        name: String,
+    },
+    RollbackToSavepoint {
+        name: String,
+    },
+    ReleaseSavepoint {
+        name: String,
+    },
+    Prepare {
+        name: String,
+        command: String,
+    },
+    ExecutePrepared {
+        name: String,
+        parameters: Vec<Value>,
+    },
+    Deallocate {
+        name: String,
+    },
 }
 
 pub struct HexenClient {
@@ -45,8 +61,19 @@ impl HexenClient {
         self.stream.write_all(request.as_bytes()).unwrap();
 
         let mut buffer = [0; 1024];
-        let n = self.stream.read(&mut buffer).unwrap();
-        String::from_utf8_lossy(&buffer[..n]).to_string()
+        let mut response = Vec::new();
+        loop {
+            let n = self.stream.read(&mut buffer).unwrap();
+            response.extend_from_slice(&buffer[..n]);
+            let incomplete = matches!(
+                serde_json::from_slice::<serde_json::Value>(&response),
+                Err(e) if e.is_eof()
+            );
+            if n == 0 || !incomplete {
+                break;
+            }
+        }
+        String::from_utf8_lossy(&response).to_string()
     }
 }
 
@@ -60,7 +87,12 @@ pub fn run_client(mut client: HexenClient) {
         println!("5. Iniciar transação");
         println!("6. Confirmar transação");
         println!("7. Reverter transação");
-        println!("8. Sair");
+        println!("8. Preparar comando SQL");
+        println!("9. Executar comando preparado");
+        println!("10. Criar savepoint");
+        println!("11. Reverter até savepoint");
+        println!("12. Liberar savepoint");
+        println!("13. Sair");
 
         let mut choice = String::new();
         io::stdin().read_line(&mut choice).unwrap();
@@ -126,9 +158,16 @@ pub fn run_client(mut client: HexenClient) {
                 io::stdin().read_line(&mut backup_file).unwrap();
                 let backup_file = backup_file.trim();
 
+                print!("Digite a chave de criptografia: ");
+                io::stdout().flush().unwrap();
+                let mut encryption_key = String::new();
+                io::stdin().read_line(&mut encryption_key).unwrap();
+                let encryption_key = encryption_key.trim();
+
                 let command = ClientCommand::Restore {
                     db_name: db_name.to_string(),
                     backup_file: backup_file.to_string(),
+                    encryption_key: encryption_key.to_string(),
                 };
                 let response = client.send_command(command);
                 println!("Resposta do servidor: {}", response);
@@ -149,6 +188,65 @@ pub fn run_client(mut client: HexenClient) {
                 println!("Resposta do servidor: {}", response);
             }
             "8" => {
+                print!("Digite o nome do comando preparado: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+
+                print!("Digite o comando SQL (use $1, $2, ... ou ? para os parâmetros): ");
+                io::stdout().flush().unwrap();
+                let mut sql = String::new();
+                io::stdin().read_line(&mut sql).unwrap();
+
+                let command = ClientCommand::Prepare {
+                    name: name.trim().to_string(),
+                    command: sql.trim().to_string(),
+                };
+                let response = client.send_command(command);
+                println!("Resposta do servidor: {}", response);
+            }
+            "9" => {
+                print!("Digite o nome do comando preparado: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+
+                // Os valores vão como texto; o servidor os converte para os tipos declarados.
+                println!("Digite os parâmetros, um por linha (linha vazia para terminar):");
+                let mut parameters = Vec::new();
+                loop {
+                    let mut value = String::new();
+                    io::stdin().read_line(&mut value).unwrap();
+                    let value = value.trim_end_matches(['\r', '\n']);
+                    if value.is_empty() {
+                        break;
+                    }
+                    parameters.push(Value::Text(value.to_string()));
+                }
+
+                let command = ClientCommand::ExecutePrepared {
+                    name: name.trim().to_string(),
+                    parameters,
+                };
+                let response = client.send_command(command);
+                println!("Resposta do servidor: {}", response);
+            }
+            "10" | "11" | "12" => {
+                print!("Digite o nome do savepoint: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim().to_string();
+
+                let command = match choice {
+                    "10" => ClientCommand::Savepoint { name },
+                    "11" => ClientCommand::RollbackToSavepoint { name },
+                    _ => ClientCommand::ReleaseSavepoint { name },
+                };
+                let response = client.send_command(command);
+                println!("Resposta do servidor: {}", response);
+            }
+            "13" => {
                 println!("Encerrando...");
                 break;
             }
@@ -1,427 +1,3952 @@
-use crate::table::{Column, Table};
+use crate::explain;
+use crate::history::{self, History};
+use crate::lock::LockManager;
+use crate::procedure::{Procedure, Variables};
+use crate::query::{self, Context, OutputColumn, Plan, ResultSet, TableRead};
+use crate::sequence::Sequence;
+use crate::session::{PreparedStatement, Session};
+use crate::sql::{
+    self, AlterTable, AlterTableAction, AsOf, Assignment, ColumnChange, ColumnDefinition,
+    ConflictAction, ConstraintKind, CreateProcedure, CreateSequence, CreateTable, CreateTrigger,
+    CreateView, Delete, Expr, Insert, InsertSource, IsolationLevel, LockWait, OnConflict, Prepare,
+    ProcedureStatement, Query, RaiseLevel, RowLockMode, Statement, TableConstraint, TableLockMode,
+    TriggerEvent, TriggerTiming, Update,
+};
+use crate::statistics::{self, Statistics};
+use crate::table::{Column, Table, UniqueConstraint, UniqueKey, View};
+use crate::transaction::{self, PreparedTransaction, Transaction};
+use crate::trigger::{RowChange, Trigger};
+use crate::value::{DataType, Value};
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
-use base64::{decode, encode};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Instant;
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::{Read, Write},
     path::Path,
 };
 
+// Gatilhos e procedimentos que disparam ou chamam outros param neste nível, evitando
+// recursão infinita.
+const MAX_NESTING_DEPTH: usize = 16;
+
+// Versões de esquema são únicas no processo, então um plano em cache nunca é reaproveitado
+// depois de uma alteração de esquema, rollback ou restauração de backup.
+static SCHEMA_VERSION: AtomicU64 = AtomicU64::new(0);
+
+const ABORTED: &str = "A transação atual foi abortada; comandos são ignorados até o ROLLBACK.";
+
+fn next_schema_version() -> u64 {
+    SCHEMA_VERSION.fetch_add(1, AtomicOrdering::Relaxed) + 1
+}
+
+// Chave AES-256 usada para cifrar o arquivo: os primeiros 32 bytes da chave informada,
+// completados com zeros. `new`, `open`, `has_key` e a restauração passam todos por aqui.
+fn derive_key(encryption_key: &str) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    let bytes = encryption_key.as_bytes();
+    let length = bytes.len().min(32);
+    key[..length].copy_from_slice(&bytes[..length]);
+    key
+}
+
+fn aborted_error(transaction: &Transaction) -> String {
+    transaction
+        .cancelled
+        .clone()
+        .unwrap_or_else(|| ABORTED.to_string())
+}
+
+// Formato gravado no arquivo do banco e nos backups.
 #[derive(Serialize, Deserialize)]
-struct TransactionState {
-    tables: HashMap<String, Table>,
+struct StoredDatabase<'a> {
+    tables: Cow<'a, HashMap<String, Table>>,
+    #[serde(default)]
+    sequences: Cow<'a, HashMap<String, Sequence>>,
+    #[serde(default)]
+    procedures: Cow<'a, HashMap<String, Procedure>>,
+    #[serde(default)]
+    next_xid: u64,
+    #[serde(default)]
+    prepared: Cow<'a, HashMap<String, PreparedTransaction>>,
+    #[serde(default)]
+    history_retention: Option<u64>,
 }
 
 pub struct Database {
     file_path: String,
     tables: HashMap<String, Table>,
+    sequences: HashMap<String, Sequence>,
+    procedures: HashMap<String, Procedure>,
     encryption_key: [u8; 32],
-    charset: String,
     connected: bool,
-    transaction_active: bool,
-    transaction_state: Option<TransactionState>,
+    // Próximo identificador de transação e quantos commits (e quantos com alterações de
+    // esquema) já foram feitos; os snapshots das transações guardam esses contadores.
+    next_xid: u64,
+    commits: u64,
+    schema_commits: u64,
+    sequences_changed: bool,
+    nesting_depth: usize,
+    schema_version: u64,
+    locks: LockManager,
+    // statement_timeout do comando em execução, vindo da sessão.
+    statement_deadline: Option<(Instant, u64)>,
+    // Transações preparadas pelo identificador (gid) dado no PREPARE TRANSACTION.
+    prepared: HashMap<String, PreparedTransaction>,
+    history: History,
 }
 
 impl Database {
-    pub fn new(file_path: String, encryption_key: &str, charset: &str) -> Self {
-        let mut key = [0u8; 32];
-        let encryption_key_bytes = encryption_key.as_bytes();
-        key[..encryption_key_bytes.len().min(32)]
-            .copy_from_slice(&encryption_key_bytes[..encryption_key_bytes.len().min(32)]);
+    pub fn new(file_path: String, encryption_key: &str) -> Self {
         Database {
             file_path,
             tables: HashMap::new(),
-            encryption_key: key,
-            charset: charset.to_string(),
+            sequences: HashMap::new(),
+            procedures: HashMap::new(),
+            encryption_key: derive_key(encryption_key),
             connected: true,
-            transaction_active: false,
-            transaction_state: None,
+            next_xid: 1,
+            commits: 0,
+            schema_commits: 0,
+            sequences_changed: false,
+            nesting_depth: 0,
+            schema_version: next_schema_version(),
+            locks: LockManager::default(),
+            statement_deadline: None,
+            prepared: HashMap::new(),
+            history: History::new(history::DEFAULT_RETENTION, &HashMap::new()),
+        }
+    }
+
+    // Abre o banco, carregando o arquivo existente quando houver um.
+    pub fn open(file_path: String, encryption_key: &str) -> Result<Self, String> {
+        let mut db = Database::new(file_path, encryption_key);
+        if !Path::new(&db.file_path).exists() {
+            return Ok(db);
+        }
+        let encrypted = fs::read(&db.file_path)
+            .map_err(|e| format!("Erro ao ler o arquivo do banco de dados: {}", e))?;
+        let data = db
+            .decrypt_data(&encrypted)
+            .ok_or_else(|| "Chave de criptografia inválida ou arquivo corrompido.".to_string())?;
+        let stored = Self::deserialize_stored(&data)
+            .map_err(|e| format!("Erro ao desserializar dados: {}", e))?;
+        db.tables = stored.tables.into_owned();
+        db.sequences = stored.sequences.into_owned();
+        db.procedures = stored.procedures.into_owned();
+        db.next_xid = stored.next_xid.max(1);
+        db.prepared = stored.prepared.into_owned();
+        db.lock_prepared();
+        db.history = History::new(
+            stored
+                .history_retention
+                .unwrap_or(history::DEFAULT_RETENTION),
+            &db.tables,
+        );
+        Ok(db)
+    }
+
+    // As transações preparadas voltam a travar as suas tabelas depois de carregar o arquivo.
+    fn lock_prepared(&mut self) {
+        for prepared in self.prepared.values() {
+            for table in &prepared.locked_tables {
+                let _ = self
+                    .locks
+                    .lock_table(prepared.xid, table, TableLockMode::Share, true);
+            }
         }
     }
 
-    pub fn begin_transaction(&mut self) -> String {
-        if self.transaction_active {
+    // O comando bloqueado continua esperando enquanto todas as transações que o bloquearam
+    // estiverem ativas.
+    pub fn is_blocked(&self, blockers: &[u64]) -> bool {
+        !blockers.is_empty() && blockers.iter().all(|&xid| self.locks.holds_locks(xid))
+    }
+
+    // Confere a chave de quem se conecta a um banco já aberto.
+    pub fn has_key(&self, encryption_key: &str) -> bool {
+        derive_key(encryption_key) == self.encryption_key
+    }
+
+    // Sem nível informado, usa o padrão da sessão.
+    pub fn begin_transaction(
+        &mut self,
+        session: &mut Session,
+        isolation_level: Option<IsolationLevel>,
+    ) -> String {
+        if session.transaction.is_some() {
             return "Erro: Já existe uma transação ativa.".to_string();
         }
 
-        self.transaction_state = Some(TransactionState {
-            tables: self.tables.clone(),
-        });
-        self.transaction_active = true;
+        let xid = self.next_xid;
+        self.next_xid += 1;
+        session.transaction = Some(Transaction::new(
+            xid,
+            isolation_level.unwrap_or(session.isolation_level),
+            (self.commits, self.schema_commits),
+            &self.tables,
+            &self.procedures,
+        ));
         "Transação iniciada com sucesso.".to_string()
     }
 
-    pub fn commit_transaction(&mut self) -> String {
-        if !self.transaction_active {
+    pub fn commit_transaction(&mut self, session: &mut Session) -> String {
+        let Some(transaction) = session.transaction.take() else {
             return "Erro: Nenhuma transação ativa para confirmar.".to_string();
+        };
+        if transaction.aborted {
+            self.discard_transaction(&transaction);
+            return match transaction.cancelled {
+                Some(error) => format!("Erro: {}", error),
+                None => {
+                    "Erro: A transação havia sido abortada por um erro e foi revertida.".to_string()
+                }
+            };
         }
 
-        self.transaction_active = false;
-        self.transaction_state = None;
-        self.save_database();
-        "Transação confirmada com sucesso.".to_string()
+        match self.commit(&transaction) {
+            Ok(()) => "Transação confirmada com sucesso.".to_string(),
+            Err(error) => format!("Erro: {}", error),
+        }
     }
 
-    pub fn rollback_transaction(&mut self) -> String {
-        if !self.transaction_active {
-            return "Erro: Nenhuma transação ativa para reverter.".to_string();
+    fn commit(&mut self, transaction: &Transaction) -> Result<(), String> {
+        let (tables, procedures) = (self.tables.clone(), self.procedures.clone());
+        let applied = self
+            .apply_transaction(transaction)
+            .and_then(|_| self.save_database());
+        if let Err(error) = applied {
+            // Nada é confirmado sem chegar ao arquivo.
+            self.tables = tables;
+            self.procedures = procedures;
+            self.discard_transaction(transaction);
+            return Err(format!("{} A transação foi revertida.", error));
         }
+        self.commits += 1;
+        self.locks.release(transaction.xid);
+        self.history
+            .record(transaction.xid, &self.tables, transaction.changed_schema);
+        if transaction.changed_schema {
+            self.schema_commits += 1;
+            // Planos feitos por outras conexões sobre o esquema anterior deixam de valer.
+            self.schema_version = next_schema_version();
+        }
+        Ok(())
+    }
 
-        if let Some(state) = self.transaction_state.take() {
-            self.tables = state.tables;
+    fn set_transaction(
+        session: &mut Session,
+        isolation_level: IsolationLevel,
+        for_session: bool,
+    ) -> Result<String, String> {
+        if for_session {
+            session.isolation_level = isolation_level;
+            return Ok(format!(
+                "Nível de isolamento padrão da sessão alterado para {}.",
+                isolation_level.keyword()
+            ));
+        }
+        let transaction = session.transaction.as_mut().ok_or_else(|| {
+            "SET TRANSACTION só pode ser usado dentro de uma transação.".to_string()
+        })?;
+        if transaction.aborted {
+            return Err(aborted_error(transaction));
         }
-        self.transaction_active = false;
+        if transaction.started {
+            return Err(
+                "SET TRANSACTION ISOLATION LEVEL deve ser executado antes de qualquer comando da transação."
+                    .to_string(),
+            );
+        }
+        transaction.isolation_level = isolation_level;
+        Ok(format!(
+            "Nível de isolamento da transação alterado para {}.",
+            isolation_level.keyword()
+        ))
+    }
+
+    pub fn rollback_transaction(&mut self, session: &mut Session) -> String {
+        let Some(transaction) = session.transaction.take() else {
+            return "Erro: Nenhuma transação ativa para reverter.".to_string();
+        };
+
+        self.discard_transaction(&transaction);
         "Transação revertida com sucesso.".to_string()
     }
 
-    pub fn execute_command(&mut self, command: &str) -> String {
-        if !self.connected {
-            return "Erro: Você não está conectado ao banco de dados.".to_string();
+    // Chamado quando a conexão termina: a transação que ficou aberta é revertida.
+    pub fn end_session(&mut self, session: &mut Session) {
+        if session.transaction.is_some() {
+            self.rollback_transaction(session);
         }
+    }
 
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            return "Comando inválido.".to_string();
+    // Alterações de esquema exigem que nada tenha sido confirmado desde o snapshot; alterações
+    // de dados são aplicadas linha a linha sobre o estado atual, e falham se outra transação
+    // já alterou ou removeu uma das mesmas linhas.
+    fn apply_transaction(&mut self, transaction: &Transaction) -> Result<(), String> {
+        let concurrent = self.commits != transaction.snapshot_commit;
+        let changed = transaction.changed_tables();
+        if transaction.changed_schema {
+            if !self.prepared.is_empty() {
+                return Err(
+                    "Alterações de esquema não podem ser confirmadas enquanto houver transações preparadas pendentes."
+                        .to_string(),
+                );
+            }
+            if concurrent {
+                return Err(
+                    "Não foi possível serializar o acesso devido a uma alteração concorrente."
+                        .to_string(),
+                );
+            }
+            self.tables = transaction
+                .tables
+                .iter()
+                .map(|(name, table)| {
+                    let snapshot = transaction.snapshot.get(name);
+                    (
+                        name.clone(),
+                        transaction::install(snapshot, table, transaction.xid),
+                    )
+                })
+                .collect();
+            self.procedures = transaction.procedures.clone();
+            return Ok(());
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+        if self.schema_commits != transaction.snapshot_schema {
+            return Err(
+                "Não foi possível serializar o acesso devido a uma alteração concorrente no esquema."
+                    .to_string(),
+            );
         }
 
-        match parts[0].to_uppercase().as_str() {
-            "CREATE" if parts[1].to_uppercase() == "TABLE" => {
-                if self.transaction_active {
-                    self.execute_create_table(parts)
-                } else {
-                    "Erro: Operações devem ser executadas dentro de uma transação.".to_string()
-                }
+        if concurrent && transaction.isolation_level == IsolationLevel::Serializable {
+            self.check_reads(transaction)?;
+        }
+
+        let mut tables = self.tables.clone();
+        for &name in &changed {
+            let conflict = || {
+                format!(
+                    "Não foi possível serializar o acesso devido a uma alteração concorrente na tabela '{}'.",
+                    name
+                )
+            };
+            let (snapshot, work) = (&transaction.snapshot[name], &transaction.tables[name]);
+            let current = tables.get(name).ok_or_else(conflict)?;
+            let table = if Arc::ptr_eq(&current.data, &snapshot.data) {
+                transaction::install(Some(snapshot), work, transaction.xid)
+            } else {
+                transaction::merge(snapshot, work, current, transaction.xid).ok_or_else(conflict)?
+            };
+            tables.insert(name.clone(), table);
+        }
+        let previous = std::mem::replace(&mut self.tables, tables);
+        if concurrent || transaction.rebased {
+            // Restrições que cada transação verificou isoladamente podem ser violadas pela
+            // combinação das duas.
+            if let Err(error) = self.check_merged_tables(&changed) {
+                self.tables = previous;
+                return Err(error);
             }
-            "INSERT" if parts[1].to_uppercase() == "INTO" => {
-                if self.transaction_active {
-                    self.execute_insert(parts)
-                } else {
-                    "Erro: Operações devem ser executadas dentro de uma transação.".to_string()
-                }
+        }
+        Ok(())
+    }
+
+    // SERIALIZABLE: a transação só pode ser confirmada se nenhuma linha que ela leu foi
+    // alterada por transações confirmadas depois do seu snapshot; assim ela equivale a ter
+    // sido executada inteira no momento do commit.
+    fn check_reads(&self, transaction: &Transaction) -> Result<(), String> {
+        let conflict = || {
+            Err("Não foi possível serializar o acesso devido a dependências de leitura/escrita entre transações."
+                .to_string())
+        };
+        let context = Context::default();
+        for read in &transaction.reads {
+            let (before, after) = match (
+                transaction.snapshot.get(&read.table),
+                self.tables.get(&read.table),
+            ) {
+                (Some(before), Some(after)) => (before, after),
+                (None, None) => continue,
+                _ => return conflict(),
+            };
+            if Arc::ptr_eq(&before.data, &after.data) {
+                continue;
             }
-            "ALTER" if parts[1].to_uppercase() == "TABLE" => {
-                if self.transaction_active {
-                    self.execute_alter_table(parts)
-                } else {
-                    "Erro: Operações devem ser executadas dentro de uma transação.".to_string()
-                }
+            let columns = Self::output_columns(before, &read.alias);
+            // Um predicado que não pode ser avaliado conta como conflito.
+            let read_changed_row =
+                transaction::changed_rows(before, after)
+                    .into_iter()
+                    .any(|row| match &read.predicate {
+                        Some(predicate) => query::evaluate(predicate, &columns, row, &context)
+                            .map_or(true, |value| value.is_true()),
+                        None => true,
+                    });
+            if read_changed_row {
+                return conflict();
             }
-            "DROP" if parts[1].to_uppercase() == "TABLE" => {
-                if self.transaction_active {
-                    self.execute_drop_table(parts)
-                } else {
-                    "Erro: Operações devem ser executadas dentro de uma transação.".to_string()
+        }
+        Ok(())
+    }
+
+    fn check_merged_tables(&self, changed: &[&String]) -> Result<(), String> {
+        for (name, table) in &self.tables {
+            let references_changed = table.columns.iter().any(|column| {
+                column
+                    .foreign_key
+                    .as_ref()
+                    .is_some_and(|(referenced, _)| changed.contains(&referenced))
+            });
+            if changed.contains(&name) || references_changed {
+                self.check_table(name, table)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reverte na hora a transação que excedeu um tempo limite, liberando os locks dela. Ela
+    // continua na sessão, abortada, e os comandos seguintes recebem o motivo até o ROLLBACK.
+    fn cancel_transaction(&mut self, transaction: &mut Transaction, error: String) {
+        self.discard_transaction(transaction);
+        transaction.sequences.clear();
+        transaction.changed_schema = false;
+        transaction.savepoints.clear();
+        transaction.aborted = true;
+        transaction.cancelled = Some(error);
+    }
+
+    // Chamado pelo servidor quando a conexão passa do idle_in_transaction_timeout sem enviar
+    // comandos.
+    pub fn expire_idle_transaction(&mut self, session: &mut Session) {
+        let timeout = session.idle_in_transaction_timeout;
+        if let Some(transaction) = session.transaction.as_mut() {
+            let error = format!(
+                "[25P03] A transação ficou ociosa por mais de {} ms (idle_in_transaction_timeout) e foi revertida.",
+                timeout
+            );
+            self.cancel_transaction(transaction, error);
+        }
+    }
+
+    fn discard_transaction(&mut self, transaction: &Transaction) {
+        self.locks.release(transaction.xid);
+        for (name, previous) in &transaction.sequences {
+            self.restore_sequence(name, previous.as_ref());
+        }
+        if transaction.changed_schema {
+            self.schema_version = next_schema_version();
+        }
+        // Se a gravação falhar, ela é repetida no fim do próximo comando, que devolve o erro.
+        if !transaction.sequences.is_empty() && self.save_database().is_err() {
+            self.sequences_changed = true;
+        }
+    }
+
+    // Volta a definição da sequência, mantendo o valor atual.
+    fn restore_sequence(&mut self, name: &str, previous: Option<&Sequence>) {
+        match previous {
+            Some(previous) => {
+                let mut sequence = previous.clone();
+                if let Some(current) = self.sequences.get(name) {
+                    sequence.last_value = current.last_value;
+                    sequence.is_called = current.is_called;
                 }
+                self.sequences.insert(name.to_string(), sequence);
             }
-            "SELECT" if parts[1].to_uppercase() == "*" && parts[2].to_uppercase() == "FROM" => {
-                self.execute_select(parts)
+            None => {
+                self.sequences.remove(name);
             }
-            _ => "Comando SQL não suportado.".to_string(),
         }
     }
 
-    fn execute_create_table(&mut self, parts: Vec<&str>) -> String {
-        let table_name = parts[2];
-        let joined = parts[3..].join(" ");
-        let column_definitions: Vec<&str> = joined
-            .trim_matches(|c| c == '(' || c == ')' || c == ';')
-            .split(',')
-            .map(|s| s.trim())
-            .collect();
+    pub fn create_savepoint(&mut self, session: &mut Session, name: &str) -> String {
+        let Some(transaction) = session.transaction.as_mut() else {
+            return "Erro: SAVEPOINT só pode ser usado dentro de uma transação.".to_string();
+        };
+        if transaction.aborted {
+            return format!("Erro: {}", aborted_error(transaction));
+        }
+        transaction.savepoint(name, &self.sequences);
+        format!("Savepoint '{}' criado com sucesso.", name)
+    }
 
-        let mut columns = Vec::new();
-        for def in column_definitions {
-            let mut parts = def.split_whitespace();
-            let column_name = parts.next().unwrap_or("").to_string();
-            let mut is_primary_key = false;
-            let mut foreign_key = None;
-
-            for part in parts.clone() {
-                match part.to_uppercase().as_str() {
-                    "PRIMARY" if parts.clone().nth(1).unwrap_or("") == "KEY" => {
-                        is_primary_key = true;
-                    }
-                    "FOREIGN" if parts.clone().nth(1).unwrap_or("") == "KEY" => {
-                        let referenced_table = parts.clone().nth(3).unwrap_or("");
-                        let referenced_column = parts.clone().nth(5).unwrap_or("");
-                        foreign_key =
-                            Some((referenced_table.to_string(), referenced_column.to_string()));
-                    }
-                    _ => {}
-                }
+    pub fn rollback_to_savepoint(&mut self, session: &mut Session, name: &str) -> String {
+        let Some(transaction) = session.transaction.as_mut() else {
+            return "Erro: ROLLBACK TO SAVEPOINT só pode ser usado dentro de uma transação."
+                .to_string();
+        };
+        if let Some(error) = &transaction.cancelled {
+            return format!("Erro: {}", error);
+        }
+        let Some(position) = transaction.find_savepoint(name) else {
+            // Como qualquer outro erro, deixa a transação abortada.
+            transaction.aborted = true;
+            return format!("Erro: Savepoint '{}' não existe.", name);
+        };
+
+        let definitions = &transaction.savepoints[position].definitions;
+        let changed_sequences = !transaction.sequences.is_empty();
+        for name in transaction.sequences.keys() {
+            self.restore_sequence(name, definitions.get(name));
+        }
+        if transaction.changed_schema {
+            self.schema_version = next_schema_version();
+        }
+        transaction.rollback_to(position);
+        if changed_sequences {
+            if let Err(error) = self.save_database() {
+                self.sequences_changed = true;
+                return format!("Erro: {}", error);
             }
+        }
+        format!("Transação revertida até o savepoint '{}'.", name)
+    }
 
-            columns.push(Column {
-                name: column_name,
-                is_primary_key,
-                foreign_key,
-            });
+    pub fn release_savepoint(&mut self, session: &mut Session, name: &str) -> String {
+        let Some(transaction) = session.transaction.as_mut() else {
+            return "Erro: RELEASE SAVEPOINT só pode ser usado dentro de uma transação."
+                .to_string();
+        };
+        if transaction.aborted {
+            return format!("Erro: {}", aborted_error(transaction));
         }
+        let Some(position) = transaction.find_savepoint(name) else {
+            // Como qualquer outro erro, deixa a transação abortada.
+            transaction.aborted = true;
+            return format!("Erro: Savepoint '{}' não existe.", name);
+        };
+        // Os savepoints criados depois dele também deixam de existir.
+        transaction.savepoints.truncate(position);
+        format!("Savepoint '{}' liberado.", name)
+    }
 
-        self.tables.insert(
-            table_name.to_string(),
-            Table {
-                columns,
-                data: Vec::new(),
+    // Primeira fase do commit em duas fases: depois do PREPARE, a transação sai da sessão e
+    // só pode ser confirmada ou revertida pelo gid, por qualquer conexão, mesmo depois de o
+    // servidor ser reiniciado.
+    fn prepare_transaction(&mut self, session: &mut Session, gid: &str) -> Result<String, String> {
+        let transaction = session.transaction.as_mut().ok_or_else(|| {
+            "PREPARE TRANSACTION só pode ser usado dentro de uma transação.".to_string()
+        })?;
+        if transaction.aborted {
+            return Err(aborted_error(transaction));
+        }
+        if transaction.changed_schema {
+            return Err("Transações que alteram o esquema não podem ser preparadas.".to_string());
+        }
+        if self.prepared.contains_key(gid) {
+            return Err(format!(
+                "O identificador de transação '{}' já está em uso.",
+                gid
+            ));
+        }
+        let xid = transaction.xid;
+        self.locks.stop_waiting(xid);
+        let locked_tables = self.prepared_locks(transaction);
+        for table in &locked_tables {
+            self.locks
+                .lock_table(xid, table, TableLockMode::Share, false)?;
+        }
+
+        // Com as tabelas travadas, combina as alterações com o estado confirmado como no
+        // commit, sem confirmar; o resultado não muda mais até a segunda fase.
+        let committed = self.tables.clone();
+        let result = self.apply_transaction(transaction);
+        let merged = std::mem::replace(&mut self.tables, committed);
+        result?;
+        let tables = transaction
+            .changed_tables()
+            .into_iter()
+            .map(|name| {
+                let tables = (self.tables[name].clone(), merged[name].clone());
+                (name.clone(), tables)
+            })
+            .collect();
+        self.prepared.insert(
+            gid.to_string(),
+            PreparedTransaction {
+                xid,
+                prepared_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                tables,
+                locked_tables,
             },
         );
-
-        format!("Tabela '{}' criada com sucesso!", table_name)
+        // A transação só sai da sessão depois de gravada; se a gravação falhar, ela continua
+        // aberta e o erro a aborta como qualquer outro.
+        if let Err(error) = self.save_database() {
+            self.prepared.remove(gid);
+            return Err(error);
+        }
+        session.transaction = None;
+        Ok(format!("Transação preparada como '{}'.", gid))
     }
 
-    fn execute_insert(&mut self, parts: Vec<&str>) -> String {
-        let table_name = parts[2];
-        let values: Vec<String> = parts[4..]
-            .join(" ")
-            .trim_matches(|c| c == '(' || c == ')' || c == ';')
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect();
+    // Tabelas alteradas, as ligadas a elas por chaves estrangeiras e, em SERIALIZABLE, as
+    // lidas: nenhuma delas pode ser alterada por outra transação até a segunda fase.
+    fn prepared_locks(&self, transaction: &Transaction) -> Vec<String> {
+        let references = |table: &Table, referenced: &str| {
+            table.columns.iter().any(|column| {
+                column
+                    .foreign_key
+                    .as_ref()
+                    .is_some_and(|(name, _)| name == referenced)
+            })
+        };
+        let changed = transaction.changed_tables();
+        let mut tables = changed
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        for (name, table) in &self.tables {
+            let related = changed.iter().any(|changed| {
+                references(table, changed)
+                    || self
+                        .tables
+                        .get(*changed)
+                        .is_some_and(|t| references(t, name))
+            });
+            if related {
+                tables.push(name.clone());
+            }
+        }
+        if transaction.isolation_level == IsolationLevel::Serializable {
+            tables.extend(transaction.reads.iter().map(|read| read.table.clone()));
+        }
+        tables.retain(|name| self.tables.contains_key(name));
+        tables.sort();
+        tables.dedup();
+        tables
+    }
 
-        if let Some(table) = self.tables.get(table_name) {
-            for (i, column) in table.columns.iter().enumerate() {
-                if let Some((referenced_table, referenced_column)) = &column.foreign_key {
-                    if !self.is_valid_foreign_key(referenced_table, referenced_column, &values[i]) {
-                        return format!(
-                            "Erro: Valor '{}' inválido para a coluna '{}' na tabela '{}'.",
-                            values[i], column.name, table_name
-                        );
-                    }
+    pub fn commit_prepared(&mut self, session: &Session, gid: &str) -> String {
+        if session.transaction.is_some() {
+            return "Erro: COMMIT PREPARED não pode ser usado dentro de uma transação.".to_string();
+        }
+        let Some(prepared) = self.prepared.get(gid) else {
+            return format!("Erro: Transação preparada '{}' não existe.", gid);
+        };
+        let mut tables = self.tables.clone();
+        for (name, (before, after)) in &prepared.tables {
+            // Depois de reiniciar o servidor os dados não são mais compartilhados, mas as
+            // tabelas continuaram travadas e as linhas são as mesmas.
+            let table = self.tables.get(name).and_then(|current| {
+                match Arc::ptr_eq(&current.data, &before.data) {
+                    true => Some(after.clone()),
+                    false => transaction::merge(before, after, current, prepared.xid),
                 }
-            }
+            });
+            let Some(table) = table else {
+                return format!(
+                    "Erro: Não foi possível aplicar a transação preparada '{}' na tabela '{}'.",
+                    gid, name
+                );
+            };
+            tables.insert(name.clone(), table);
+        }
+        let committed = std::mem::replace(&mut self.tables, tables);
+        let prepared = self.prepared.remove(gid).unwrap();
+        if let Err(error) = self.save_database() {
+            self.tables = committed;
+            self.prepared.insert(gid.to_string(), prepared);
+            return format!("Erro: {}", error);
         }
+        self.commits += 1;
+        self.locks.release(prepared.xid);
+        self.history.record(prepared.xid, &self.tables, false);
+        format!("Transação preparada '{}' confirmada com sucesso.", gid)
+    }
 
-        if let Some(table) = self.tables.get_mut(table_name) {
-            table.data.push(values);
-            return format!("Dados inseridos na tabela '{}'.", table_name);
+    pub fn rollback_prepared(&mut self, session: &Session, gid: &str) -> String {
+        if session.transaction.is_some() {
+            return "Erro: ROLLBACK PREPARED não pode ser usado dentro de uma transação."
+                .to_string();
         }
+        let Some(prepared) = self.prepared.remove(gid) else {
+            return format!("Erro: Transação preparada '{}' não existe.", gid);
+        };
+        if let Err(error) = self.save_database() {
+            self.prepared.insert(gid.to_string(), prepared);
+            return format!("Erro: {}", error);
+        }
+        self.locks.release(prepared.xid);
+        format!("Transação preparada '{}' revertida com sucesso.", gid)
+    }
 
-        format!("Erro: Tabela '{}' não existe.", table_name)
+    // Transações preparadas ainda sem decisão, para o coordenador resolver.
+    fn show_prepared_transactions(&self) -> ResultSet {
+        let mut prepared = self.prepared.iter().collect::<Vec<_>>();
+        prepared.sort_by_key(|(_, prepared)| prepared.xid);
+        ResultSet {
+            columns: vec![
+                Self::output_column("gid", DataType::Text),
+                Self::output_column("transaction", DataType::Integer),
+                Self::output_column("prepared", DataType::Text),
+            ],
+            rows: prepared
+                .into_iter()
+                .map(|(gid, prepared)| {
+                    vec![
+                        Value::Text(gid.clone()),
+                        Value::Integer(prepared.xid as i64),
+                        Value::Text(prepared.prepared_at.clone()),
+                    ]
+                })
+                .collect(),
+        }
     }
 
-    fn is_valid_foreign_key(
+    // Commits ainda no histórico, para escolher o estado lido com AS OF.
+    fn show_history(&self) -> ResultSet {
+        ResultSet {
+            columns: vec![
+                Self::output_column("transaction", DataType::Integer),
+                Self::output_column("committed", DataType::Timestamp),
+                Self::output_column("tables", DataType::Text),
+            ],
+            rows: self
+                .history
+                .versions()
+                .map(|version| {
+                    vec![
+                        version
+                            .xid
+                            .map_or(Value::Null, |xid| Value::Integer(xid as i64)),
+                        Value::Timestamp(version.committed_at),
+                        Value::Text(version.changed.join(", ")),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    // Catálogo usado pela consulta: as referências com AS OF entram com o estado confirmado
+    // da tabela no instante ou na transação pedidos, com o texto da referência como nome.
+    fn tables_as_of(
         &self,
-        referenced_table: &str,
-        referenced_column: &str,
-        value: &str,
-    ) -> bool {
-        if let Some(table) = self.tables.get(referenced_table) {
-            if let Some(column_index) = table
-                .columns
-                .iter()
-                .position(|c| c.name == referenced_column)
-            {
-                return table.data.iter().any(|row| row[column_index] == value);
+        query: &Query,
+        context: &Context,
+    ) -> Result<Cow<'_, HashMap<String, Table>>, String> {
+        let references = query.as_of_references();
+        if references.is_empty() {
+            return Ok(Cow::Borrowed(&self.tables));
+        }
+        let mut tables = self.tables.clone();
+        for reference in references {
+            let Some(as_of) = &reference.as_of else {
+                continue;
+            };
+            let past = match as_of {
+                AsOf::Timestamp(expr) => {
+                    match query::evaluate(expr, &[], &[], context)?.cast(DataType::Timestamp)? {
+                        Value::Timestamp(timestamp) => self.history.at_timestamp(timestamp)?,
+                        _ => return Err("AS OF TIMESTAMP exige um instante.".to_string()),
+                    }
+                }
+                AsOf::Transaction(expr) => match query::evaluate(expr, &[], &[], context)? {
+                    Value::Integer(xid) if xid > 0 => self.history.at_transaction(xid as u64)?,
+                    _ => {
+                        return Err("AS OF TRANSACTION exige o número de uma transação.".to_string())
+                    }
+                },
+            };
+            let table = past.get(&reference.name).ok_or_else(|| {
+                format!(
+                    "Tabela '{}' não existia no estado pedido ({}).",
+                    reference.name, as_of
+                )
+            })?;
+            if table.view.as_ref().is_some_and(|view| !view.materialized) {
+                return Err("AS OF não pode ser usado com views.".to_string());
             }
+            tables.insert(reference.key(), table.clone());
         }
-        false
+        Ok(Cow::Owned(tables))
+    }
+
+    // Executa `f` com as tabelas e os procedimentos da transação no lugar dos confirmados.
+    fn in_workspace<T>(
+        &mut self,
+        transaction: Option<&mut Transaction>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let Some(transaction) = transaction else {
+            return f(self);
+        };
+        std::mem::swap(&mut self.tables, &mut transaction.tables);
+        std::mem::swap(&mut self.procedures, &mut transaction.procedures);
+        let result = f(self);
+        std::mem::swap(&mut self.tables, &mut transaction.tables);
+        std::mem::swap(&mut self.procedures, &mut transaction.procedures);
+        result
     }
 
-    fn execute_select(&self, parts: Vec<&str>) -> String {
-        let table_name = parts[3];
+    pub fn execute_command(&mut self, command: &str, session: &mut Session) -> String {
+        if !self.connected {
+            return "Erro: Você não está conectado ao banco de dados.".to_string();
+        }
 
-        if let Some(table) = self.tables.get(table_name) {
-            let mut condition_column = None;
-            let mut condition_value = None;
+        if command.trim().is_empty() {
+            return "Comando inválido.".to_string();
+        }
 
-            if parts.len() > 4 && parts[4].to_uppercase() == "WHERE" {
-                if parts.len() < 6 {
-                    return "Sintaxe inválida. Use: SELECT * FROM <table_name> WHERE <column> = <value>".to_string();
+        let statement = match sql::parse(command) {
+            Ok(statement) => statement,
+            Err(error) => {
+                if let Some(transaction) = session.transaction.as_mut() {
+                    transaction.aborted = true;
                 }
-                condition_column = Some(parts[5]);
-                condition_value = Some(parts[7].trim_matches(|c| c == '\'' || c == '"'));
+                if error.starts_with("Comando SQL") {
+                    return error;
+                }
+                return format!("Erro: {}", error);
             }
+        };
 
-            let header: Vec<String> = table.columns.iter().map(|c| c.name.clone()).collect();
-            let mut result = format!(
-                "+{}\n",
-                "-".repeat(header.iter().map(|h| h.len() + 2).sum::<usize>() + header.len() - 1)
-            );
-            result += &format!("| {}\n", header.join(" | "));
-            result += &format!(
-                "+{}\n",
-                "-".repeat(header.iter().map(|h| h.len() + 2).sum::<usize>() + header.len() - 1)
-            );
-
-            for row in &table.data {
-                if let Some(column) = condition_column {
-                    if let Some(value) = condition_value {
-                        if let Some(index) = table.columns.iter().position(|c| c.name == column) {
-                            if row[index] != value {
-                                continue;
-                            }
-                        } else {
-                            return format!("Erro: Coluna '{}' não encontrada.", column);
-                        }
-                    }
+        match statement {
+            Statement::Prepare(prepare) => self
+                .prepare_statement(session, prepare)
+                .unwrap_or_else(|error| format!("Erro: {}", error)),
+            Statement::Execute { name, arguments } => {
+                let context = Context::default();
+                match arguments
+                    .iter()
+                    .map(|argument| query::evaluate(argument, &[], &[], &context))
+                    .collect()
+                {
+                    Ok(parameters) => self.execute_prepared(session, &name, parameters),
+                    Err(error) => format!("Erro: {}", error),
                 }
-
-                result += &format!("| {}\n", row.join(" | "));
             }
-
-            result += &format!(
-                "+{}\n",
-                "-".repeat(header.iter().map(|h| h.len() + 2).sum::<usize>() + header.len() - 1)
-            );
-            return result;
+            Statement::Deallocate(name) => session
+                .deallocate(name.as_deref())
+                .unwrap_or_else(|error| format!("Erro: {}", error)),
+            Statement::Begin(isolation_level) => self.begin_transaction(session, isolation_level),
+            Statement::Commit => self.commit_transaction(session),
+            Statement::Rollback => self.rollback_transaction(session),
+            Statement::Savepoint(name) => self.create_savepoint(session, &name),
+            Statement::RollbackToSavepoint(name) => self.rollback_to_savepoint(session, &name),
+            Statement::ReleaseSavepoint(name) => self.release_savepoint(session, &name),
+            Statement::SetTransaction {
+                isolation_level,
+                session: for_session,
+            } => Self::set_transaction(session, isolation_level, for_session)
+                .unwrap_or_else(|error| format!("Erro: {}", error)),
+            Statement::LockTable { .. } if session.transaction.is_none() => {
+                "Erro: LOCK TABLE só pode ser usado dentro de uma transação.".to_string()
+            }
+            Statement::SetTimeout {
+                timeout,
+                milliseconds,
+            } => {
+                session.set_timeout(timeout, milliseconds);
+                match milliseconds {
+                    0 => format!("{} desativado.", timeout.name()),
+                    _ => format!("{} alterado para {} ms.", timeout.name(), milliseconds),
+                }
+            }
+            Statement::PrepareTransaction(gid) => {
+                let result = self.prepare_transaction(session, &gid);
+                self.finish_statement(session, false, result)
+            }
+            Statement::CommitPrepared(gid) => self.commit_prepared(session, &gid),
+            Statement::RollbackPrepared(gid) => self.rollback_prepared(session, &gid),
+            Statement::ShowPreparedTransactions => self.show_prepared_transactions().render(),
+            Statement::ShowHistory => self.show_history().render(),
+            Statement::SetHistoryRetention(milliseconds) => {
+                let milliseconds = milliseconds.unwrap_or(history::DEFAULT_RETENTION);
+                let previous = self.history.retention;
+                self.history.set_retention(milliseconds);
+                if let Err(error) = self.save_database() {
+                    self.history.set_retention(previous);
+                    return format!("Erro: {}", error);
+                }
+                match milliseconds {
+                    0 => "history_retention desativado.".to_string(),
+                    _ => format!("history_retention alterado para {} ms.", milliseconds),
+                }
+            }
+            Statement::SetAutocommit(enabled) => {
+                session.autocommit = enabled;
+                match enabled {
+                    true => "Autocommit ativado.".to_string(),
+                    false => "Autocommit desativado.".to_string(),
+                }
+            }
+            statement => {
+                self.statement_deadline = session.statement_deadline();
+                let implicit = self.begin_implicit(session, &statement);
+                let result = self.execute_statement(
+                    statement,
+                    command,
+                    session.transaction.as_mut(),
+                    Vec::new(),
+                    None,
+                );
+                self.finish_statement(session, implicit, result)
+            }
         }
-
-        format!("Erro: Tabela '{}' não existe.", table_name)
     }
 
-    fn execute_alter_table(&mut self, parts: Vec<&str>) -> String {
-        let table_name = parts[2];
-        let action = parts[3].to_uppercase();
+    // Com autocommit, um comando que altera o banco fora de uma transação explícita roda em
+    // uma transação própria.
+    fn begin_implicit(&mut self, session: &mut Session, statement: &Statement) -> bool {
+        if !session.autocommit || session.transaction.is_some() || statement.is_read_only() {
+            return false;
+        }
+        self.begin_transaction(session, None);
+        true
+    }
 
-        if let Some(table) = self.tables.get_mut(table_name) {
-            match action.as_str() {
-                "ADD" => {
-                    if parts.len() < 5 || parts[4].to_uppercase() != "COLUMN" {
-                        return "Sintaxe inválida. Use: ALTER TABLE <nome> ADD COLUMN <coluna>"
-                            .to_string();
-                    }
-                    let column_name = parts[5];
-                    table.columns.push(Column {
-                        name: column_name.to_string(),
-                        is_primary_key: false,
-                        foreign_key: None,
-                    });
-                    format!(
-                        "Coluna '{}' adicionada à tabela '{}'.",
-                        column_name, table_name
-                    )
-                }
-                "DROP" => {
-                    if parts.len() < 5 || parts[4].to_uppercase() != "COLUMN" {
-                        return "Sintaxe inválida. Use: ALTER TABLE <nome> DROP COLUMN <coluna>"
-                            .to_string();
+    // A transação implícita é confirmada se o comando deu certo e revertida se falhou.
+    fn finish_statement(
+        &mut self,
+        session: &mut Session,
+        implicit: bool,
+        result: Result<String, String>,
+    ) -> String {
+        let mut result = result;
+        // statement_timeout é verificado durante a execução (e o erro pode vir de dentro de um
+        // procedimento ou gatilho); os demais limites, aqui.
+        let mut timed_out = matches!(&result, Err(error) if error.contains("[57014]"));
+        // Bloqueado por um lock: o comando foi desfeito e será repetido pelo servidor quando
+        // o lock for liberado. A transação implícita recomeça do zero na nova tentativa.
+        if let Some(xid) = session.transaction.as_ref().map(|t| t.xid) {
+            if self.locks.is_waiting(xid) {
+                match session.wait_expired() {
+                    Some(error) => {
+                        self.locks.stop_waiting(xid);
+                        result = Err(error);
+                        timed_out = true;
                     }
-                    let column_name = parts[5];
-                    if let Some(index) = table.columns.iter().position(|c| c.name == column_name) {
-                        table.columns.remove(index);
-                        for row in &mut table.data {
-                            row.remove(index);
+                    None => {
+                        session.waiting = Some(self.locks.blockers(xid));
+                        if implicit {
+                            let transaction = session.transaction.take().unwrap();
+                            self.discard_transaction(&transaction);
                         }
-                        return format!(
-                            "Coluna '{}' removida da tabela '{}'.",
-                            column_name, table_name
-                        );
+                        return result.unwrap_or_else(|error| error);
                     }
-                    format!(
-                        "Erro: Coluna '{}' não encontrada na tabela '{}'.",
-                        column_name, table_name
-                    )
                 }
-                _ => "Ação inválida. Use ADD COLUMN ou DROP COLUMN.".to_string(),
             }
-        } else {
-            format!("Erro: Tabela '{}' não existe.", table_name)
         }
+        session.end_statement();
+        let result = match implicit.then(|| session.transaction.take()).flatten() {
+            Some(transaction) => match result {
+                Ok(output) => self.commit(&transaction).map(|_| output),
+                Err(error) => {
+                    self.discard_transaction(&transaction);
+                    Err(error)
+                }
+            },
+            None => match (result, session.transaction.as_mut()) {
+                // Tempo limite excedido: a transação explícita é revertida imediatamente.
+                (Err(error), Some(transaction)) if timed_out && transaction.cancelled.is_none() => {
+                    let error = format!("{} A transação foi revertida.", error);
+                    self.cancel_transaction(transaction, error.clone());
+                    Err(error)
+                }
+                // Qualquer outro erro em uma transação explícita a deixa abortada.
+                (Err(error), Some(transaction)) => {
+                    transaction.aborted = true;
+                    Err(error)
+                }
+                (result, _) => result,
+            },
+        };
+        result.unwrap_or_else(|error| format!("Erro: {}", error))
     }
 
-    fn execute_drop_table(&mut self, parts: Vec<&str>) -> String {
-        let table_name = parts[2];
-        if self.tables.remove(table_name).is_some() {
-            format!("Tabela '{}' excluída com sucesso.", table_name)
-        } else {
-            format!("Erro: Tabela '{}' não existe.", table_name)
+    // Prepara um comando recebido pelo protocolo, sem passar por PREPARE.
+    pub fn prepare_command(&mut self, session: &mut Session, name: &str, command: &str) -> String {
+        if !self.connected {
+            return "Erro: Você não está conectado ao banco de dados.".to_string();
         }
+        sql::parse_prepared(command)
+            .and_then(|(statement, parameter_count)| {
+                let prepare = Prepare {
+                    name: name.to_string(),
+                    parameter_types: Vec::new(),
+                    statement: Box::new(statement),
+                    parameter_count,
+                };
+                self.prepare_statement(session, prepare)
+            })
+            .unwrap_or_else(|error| format!("Erro: {}", error))
     }
 
-    pub fn create_backup(
-        db_name: &str,
-        databases: &Arc<Mutex<HashMap<String, Database>>>,
+    fn prepare_statement(
+        &mut self,
+        session: &mut Session,
+        prepare: Prepare,
     ) -> Result<String, String> {
-        let databases = databases.lock().unwrap();
-        let db = databases
-            .get(db_name)
-            .ok_or("Banco de dados não encontrado.".to_string())?;
+        let mut used = vec![false; prepare.parameter_count];
+        prepare.statement.transform(&mut |e| {
+            if let Some(used) = match e {
+                Expr::Parameter(n) => n.checked_sub(1).and_then(|i| used.get_mut(i)),
+                _ => None,
+            } {
+                *used = true;
+            }
+            None
+        });
+        if let Some(unused) = used.iter().position(|used| !used) {
+            return Err(format!(
+                "O parâmetro ${} não é usado; os parâmetros devem ser numerados a partir de $1, sem lacunas.",
+                unused + 1
+            ));
+        }
+        let plan = match prepare.statement.as_ref() {
+            // O estado lido com AS OF depende dos parâmetros: a consulta é planejada a cada
+            // execução.
+            Statement::Select(query) if !query.as_of_references().is_empty() => None,
+            Statement::Select(query) => Some((
+                self.schema_version,
+                self.in_workspace(session.transaction.as_mut(), |db| {
+                    query::plan_query(&db.tables, query)
+                })?
+                .0,
+            )),
+            Statement::Insert(_)
+            | Statement::Update(_)
+            | Statement::Delete(_)
+            | Statement::Call { .. } => None,
+            _ => {
+                return Err(
+                    "Apenas SELECT, INSERT, UPDATE, DELETE e CALL podem ser preparados."
+                        .to_string(),
+                )
+            }
+        };
+        let inferred_types = self.in_workspace(session.transaction.as_mut(), |db| {
+            query::infer_parameter_types(&db.tables, &prepare.statement, prepare.parameter_count)
+        });
+        session.prepare(
+            &prepare.name,
+            PreparedStatement {
+                statement: *prepare.statement,
+                parameter_types: prepare.parameter_types,
+                inferred_types,
+                parameter_count: prepare.parameter_count,
+                plan,
+            },
+        )?;
+        Ok(format!("Comando '{}' preparado com sucesso!", prepare.name))
+    }
 
-        let backup_dir = "backups";
-        if !Path::new(backup_dir).exists() {
-            fs::create_dir(backup_dir)
-                .map_err(|e| format!("Erro ao criar diretório de backups: {}", e))?;
+    pub fn execute_prepared(
+        &mut self,
+        session: &mut Session,
+        name: &str,
+        parameters: Vec<Value>,
+    ) -> String {
+        if !self.connected {
+            return "Erro: Você não está conectado ao banco de dados.".to_string();
         }
+        let statement = match session.prepared_with_transaction(name) {
+            Ok((prepared, _)) => prepared.statement.clone(),
+            Err(error) => return format!("Erro: {}", error),
+        };
+        self.statement_deadline = session.statement_deadline();
+        let implicit = self.begin_implicit(session, &statement);
+        let result = self.run_prepared(session, name, parameters);
+        self.finish_statement(session, implicit, result)
+    }
 
-        let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
-        let backup_file = format!("{}/{}_{}.hxn.gz", backup_dir, db_name, timestamp);
+    fn run_prepared(
+        &mut self,
+        session: &mut Session,
+        name: &str,
+        parameters: Vec<Value>,
+    ) -> Result<String, String> {
+        let (prepared, mut transaction) = session.prepared_with_transaction(name)?;
+        let parameters = prepared.bind(name, parameters)?;
+        // O plano em cache é refeito quando o esquema mudou desde que foi gerado.
+        if let Statement::Select(query) = &prepared.statement {
+            if query.as_of_references().is_empty()
+                && prepared
+                    .plan
+                    .as_ref()
+                    .is_none_or(|(version, _)| *version != self.schema_version)
+            {
+                let planned = self.in_workspace(transaction.as_deref_mut(), |db| {
+                    query::plan_query(&db.tables, query)
+                });
+                prepared.plan = Some((self.schema_version, planned?.0));
+            }
+        }
+        let plan = prepared.plan.as_ref().map(|(_, plan)| plan);
+        self.execute_statement(
+            prepared.statement.clone(),
+            "",
+            transaction,
+            parameters,
+            plan,
+        )
+    }
 
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        let serialized_data = serde_json::to_string(&db.tables)
-            .map_err(|e| format!("Erro ao serializar dados: {}", e))?;
-        encoder
-            .write_all(serialized_data.as_bytes())
-            .map_err(|e| format!("Erro ao escrever backup: {}", e))?;
-        let compressed_data = encoder
-            .finish()
-            .map_err(|e| format!("Erro ao compactar backup: {}", e))?;
+    fn execute_statement(
+        &mut self,
+        statement: Statement,
+        command: &str,
+        mut transaction: Option<&mut Transaction>,
+        parameters: Vec<Value>,
+        plan: Option<&Plan>,
+    ) -> Result<String, String> {
+        if !statement.is_read_only() && transaction.is_none() {
+            return Err("Operações devem ser executadas dentro de uma transação.".to_string());
+        }
+        let changes_schema = !matches!(
+            statement,
+            Statement::Select(_)
+                | Statement::Explain { .. }
+                | Statement::Insert(_)
+                | Statement::Update(_)
+                | Statement::Delete(_)
+                | Statement::Call { .. }
+                | Statement::LockTable { .. }
+        );
 
-        let mut file = File::create(&backup_file)
-            .map_err(|e| format!("Erro ao criar arquivo de backup: {}", e))?;
-        file.write_all(&compressed_data)
-            .map_err(|e| format!("Erro ao salvar backup: {}", e))?;
+        if let Some(transaction) = transaction.as_deref_mut() {
+            if transaction.aborted {
+                return Err(aborted_error(transaction));
+            }
+            transaction.started = true;
+            if transaction.isolation_level == IsolationLevel::ReadCommitted {
+                let commits = (self.commits, self.schema_commits);
+                transaction.refresh(commits, &self.tables, &self.procedures)?;
+            }
+        }
 
-        Ok(format!("Backup criado com sucesso: {}", backup_file))
+        let sequences = transaction.as_ref().map(|_| self.sequences.clone());
+        let mut reads = transaction
+            .as_deref_mut()
+            .filter(|t| t.isolation_level == IsolationLevel::Serializable)
+            .map(|t| std::mem::take(&mut t.reads));
+        let xid = transaction.as_ref().map(|t| t.xid);
+        let result = self.in_workspace(transaction.as_deref_mut(), |db| {
+            db.dispatch_statement(statement, command, parameters, plan, xid, reads.as_mut())
+        });
+        if let (Some(transaction), Some(sequences)) = (transaction, sequences) {
+            if let Some(reads) = reads {
+                transaction.reads = reads;
+            }
+            if result.is_ok() {
+                transaction.record_sequences(&sequences, &self.sequences);
+                transaction.changed_schema |= changes_schema;
+            } else if self.locks.is_waiting(transaction.xid) {
+                // O comando bloqueado será repetido do início: os valores que ele consumiu das
+                // sequências voltam, para que as tentativas não gastem valores.
+                self.sequences = sequences;
+                self.sequences_changed = false;
+            } else {
+                // Sequências criadas, removidas ou alteradas pelo comando que falhou.
+                let changed = self
+                    .sequences
+                    .keys()
+                    .chain(sequences.keys())
+                    .filter(
+                        |name| match (sequences.get(*name), self.sequences.get(*name)) {
+                            (Some(old), Some(new)) => !old.same_definition(new),
+                            _ => true,
+                        },
+                    )
+                    .cloned()
+                    .collect::<HashSet<_>>();
+                for name in changed {
+                    self.restore_sequence(&name, sequences.get(&name));
+                }
+            }
+        }
+        if changes_schema {
+            self.schema_version = next_schema_version();
+        }
+        // O estado das sequências não participa da transação: é gravado imediatamente.
+        if std::mem::take(&mut self.sequences_changed) {
+            if let Err(error) = self.save_database() {
+                self.sequences_changed = true;
+                return Err(error);
+            }
+        }
+        result
     }
 
-    pub fn restore_backup(
-        db_name: &str,
-        backup_file: &str,
-        databases: &Arc<Mutex<HashMap<String, Database>>>,
+    fn dispatch_statement(
+        &mut self,
+        statement: Statement,
+        command: &str,
+        parameters: Vec<Value>,
+        plan: Option<&Plan>,
+        xid: Option<u64>,
+        mut reads: Option<&mut Vec<TableRead>>,
     ) -> Result<String, String> {
-        let mut databases = databases.lock().unwrap();
-        if !Path::new(backup_file).exists() {
-            return Err("Arquivo de backup não encontrado.".to_string());
+        // Cada comando é atômico: se falhar no meio (em uma das linhas, em um gatilho ou em
+        // um procedimento), tudo o que ele já tinha alterado é desfeito. Os dados são
+        // compartilhados com o snapshot, então a cópia é barata.
+        let snapshot =
+            (!statement.is_read_only()).then(|| (self.tables.clone(), self.procedures.clone()));
+        let mut context = Context::new(std::mem::take(&mut self.sequences))
+            .with_parameters(parameters)
+            .with_deadline(self.statement_deadline.take());
+        if let Some(reads) = reads.as_mut() {
+            context = context.with_reads(std::mem::take(*reads));
         }
+        if let Some(xid) = xid {
+            self.locks.stop_waiting(xid);
+            context = context.with_locks(xid, std::mem::take(&mut self.locks));
+        }
+        let result = match statement {
+            Statement::Select(query) => match plan {
+                Some(plan) => query::execute_plan(&self.tables, plan, &context),
+                None => self
+                    .tables_as_of(&query, &context)
+                    .and_then(|tables| query::execute_query(&tables, &query, &context)),
+            }
+            .map(|result_set| result_set.render()),
+            Statement::Explain { analyze, query } => self
+                .tables_as_of(&query, &context)
+                .and_then(|tables| explain::explain(&tables, &query, analyze, &context))
+                .map(|result_set| result_set.render()),
+            Statement::Analyze(table) => self.execute_analyze(table.as_deref()),
+            Statement::CreateTable(create) => self.execute_create_table(create, &context),
+            Statement::Insert(insert) => self.execute_insert(insert, &context),
+            Statement::Update(update) => self.execute_update(update, &context),
+            Statement::Delete(delete) => self.execute_delete(delete, &context),
+            Statement::AlterTable(alter) => self.execute_alter_table(alter, &context),
+            Statement::DropTable(table_name) => self.execute_drop_table(&table_name, &context),
+            Statement::CreateSequence(create) => Self::execute_create_sequence(create, &context),
+            Statement::DropSequence(name) => self.execute_drop_sequence(&name, &context),
+            Statement::CreateView(create) => self.execute_create_view(create, &context),
+            Statement::RefreshMaterializedView(name) => {
+                self.execute_refresh_materialized_view(&name, &context)
+            }
+            Statement::DropView { name, materialized } => {
+                self.execute_drop_view(&name, materialized)
+            }
+            Statement::CreateTrigger(create) => self.execute_create_trigger(create, command),
+            Statement::DropTrigger { name, table } => self.execute_drop_trigger(&name, &table),
+            Statement::CreateProcedure(create) => self.execute_create_procedure(create, command),
+            Statement::DropProcedure(name) => self.execute_drop_procedure(&name),
+            Statement::LockTable {
+                table,
+                mode,
+                nowait,
+            } => self.execute_lock_table(&table, mode, nowait, &context),
+            Statement::Call { name, arguments } => self
+                .call_procedure(&name, &arguments, &context)
+                .map(|mut output| {
+                    output.push(format!("Procedimento '{}' executado com sucesso!", name));
+                    output.join("\n")
+                }),
+            Statement::Prepare(_) | Statement::Execute { .. } | Statement::Deallocate(_) => {
+                Err("Comandos preparados não podem ser aninhados.".to_string())
+            }
+            Statement::Begin(_)
+            | Statement::Commit
+            | Statement::Rollback
+            | Statement::Savepoint(_)
+            | Statement::RollbackToSavepoint(_)
+            | Statement::ReleaseSavepoint(_)
+            | Statement::SetTransaction { .. }
+            | Statement::SetAutocommit(_)
+            | Statement::SetTimeout { .. }
+            | Statement::PrepareTransaction(_)
+            | Statement::CommitPrepared(_)
+            | Statement::RollbackPrepared(_)
+            | Statement::ShowPreparedTransactions
+            | Statement::ShowHistory
+            | Statement::SetHistoryRetention(_) => {
+                Err("Comandos de controle de transação não podem ser usados aqui.".to_string())
+            }
+        };
 
-        let mut file = File::open(backup_file)
-            .map_err(|e| format!("Erro ao abrir arquivo de backup: {}", e))?;
-        let mut compressed_data = Vec::new();
-        file.read_to_end(&mut compressed_data)
+        if let Some(locks) = context.take_locks() {
+            self.locks = locks;
+        }
+        // Um comando que ficou esperando um lock é desfeito para ser repetido depois, mesmo
+        // que o erro tenha sido tratado dentro de um procedimento.
+        let result = match xid {
+            Some(xid) if self.locks.is_waiting(xid) => {
+                result.and_then(|_| Err("Comando aguardando a liberação de um lock.".to_string()))
+            }
+            _ => result,
+        };
+        if let (Err(_), Some((tables, procedures))) = (&result, snapshot) {
+            self.tables = tables;
+            self.procedures = procedures;
+        }
+        if let Some(reads) = reads {
+            *reads = context.take_reads();
+        }
+        let (sequences, changed) = context.finish();
+        self.sequences = sequences;
+        self.sequences_changed |= changed;
+        result
+    }
+
+    fn execute_lock_table(
+        &self,
+        table_name: &str,
+        mode: TableLockMode,
+        nowait: bool,
+        context: &Context,
+    ) -> Result<String, String> {
+        self.base_table(table_name)?;
+        context.lock_table(table_name, mode, nowait)?;
+        Ok(format!(
+            "Tabela '{}' travada em modo {}.",
+            table_name,
+            mode.keyword()
+        ))
+    }
+
+    fn execute_create_table(
+        &mut self,
+        create: CreateTable,
+        context: &Context,
+    ) -> Result<String, String> {
+        let table_name = &create.name;
+        if self.tables.contains_key(table_name) {
+            return Err(format!("Tabela '{}' já existe.", table_name));
+        }
+        for definition in &create.columns {
+            Self::check_column_definition(definition)?;
+        }
+
+        let mut constraints: Vec<UniqueConstraint> = create
+            .columns
+            .iter()
+            .filter(|c| c.is_unique)
+            .map(|c| UniqueConstraint {
+                name: format!("{}_{}_key", table_name, c.name),
+                columns: vec![c.name.clone()],
+            })
+            .collect();
+        let serial_columns: Vec<String> = create
+            .columns
+            .iter()
+            .filter(|c| c.is_serial)
+            .map(|c| c.name.clone())
+            .collect();
+        let mut columns: Vec<Column> = create.columns.into_iter().map(Column::from).collect();
+
+        for constraint in create.constraints {
+            let (ConstraintKind::PrimaryKey(names) | ConstraintKind::Unique(names)) =
+                &constraint.kind;
+            for name in names {
+                if !columns.iter().any(|c| &c.name == name) {
+                    return Err(format!(
+                        "Coluna '{}' da restrição não existe na tabela '{}'.",
+                        name, table_name
+                    ));
+                }
+            }
+            match constraint.kind {
+                ConstraintKind::PrimaryKey(names) => {
+                    for column in columns.iter_mut().filter(|c| names.contains(&c.name)) {
+                        column.is_primary_key = true;
+                    }
+                }
+                ConstraintKind::Unique(names) => constraints.push(UniqueConstraint {
+                    name: constraint
+                        .name
+                        .unwrap_or_else(|| format!("{}_{}_key", table_name, names.join("_"))),
+                    columns: names,
+                }),
+            }
+        }
+
+        // Cada coluna SERIAL ganha uma sequência própria usada como valor padrão.
+        let mut sequences = context.sequences();
+        for column_name in &serial_columns {
+            let sequence_name = format!("{}_{}_seq", table_name, column_name);
+            if sequences.contains_key(&sequence_name) {
+                return Err(format!("Sequência '{}' já existe.", sequence_name));
+            }
+        }
+        for column_name in serial_columns {
+            let sequence_name = format!("{}_{}_seq", table_name, column_name);
+            let mut sequence = Sequence::new(&CreateSequence::default())?;
+            sequence.owned_by = Some((table_name.clone(), column_name.clone()));
+            sequences.insert(sequence_name.clone(), sequence);
+            if let Some(column) = columns.iter_mut().find(|c| c.name == column_name) {
+                column.default = Some(format!("NEXTVAL('{}')", sequence_name));
+            }
+        }
+
+        self.tables.insert(
+            table_name.clone(),
+            Table {
+                columns,
+                data: Arc::default(),
+                constraints,
+                view: None,
+                triggers: Vec::new(),
+                statistics: Statistics::default(),
+                row_xmin: Arc::default(),
+            },
+        );
+
+        Ok(format!("Tabela '{}' criada com sucesso!", table_name))
+    }
+
+    // Todas as linhas são montadas e validadas antes de qualquer alteração na tabela,
+    // então um erro em qualquer linha descarta o comando inteiro.
+    fn execute_insert(&mut self, insert: Insert, context: &Context) -> Result<String, String> {
+        let table_name = &insert.table;
+        let table = self.base_table(table_name)?;
+        let columns = &table.columns;
+        context.lock_table(table_name, TableLockMode::RowExclusive, false)?;
+
+        let targets: Vec<usize> = match &insert.columns {
+            None => (0..columns.len()).collect(),
+            Some(names) => {
+                let mut targets = Vec::with_capacity(names.len());
+                for name in names {
+                    let index = table.column_index(name).ok_or_else(|| {
+                        format!(
+                            "Coluna '{}' não encontrada na tabela '{}'.",
+                            name, table_name
+                        )
+                    })?;
+                    if targets.contains(&index) {
+                        return Err(format!("Coluna '{}' informada mais de uma vez.", name));
+                    }
+                    targets.push(index);
+                }
+                targets
+            }
+        };
+
+        let keys = table.unique_keys(table_name);
+        let conflict_key = match &insert.on_conflict {
+            Some(OnConflict {
+                target: Some(target),
+                ..
+            }) => Some(Self::conflict_key(table, &keys, target)?),
+            _ => None,
+        };
+
+        // Colunas visíveis no DO UPDATE: a linha existente e a pseudo-linha `excluded`.
+        let target_columns =
+            Self::output_columns(table, insert.alias.as_deref().unwrap_or(table_name));
+        let conflict_columns: Vec<OutputColumn> = target_columns
+            .iter()
+            .cloned()
+            .chain(Self::output_columns(table, "excluded"))
+            .collect();
+        let mut assignments = Vec::new();
+        if let Some(OnConflict {
+            action:
+                ConflictAction::DoUpdate {
+                    assignments: items,
+                    selection,
+                },
+            ..
+        }) = &insert.on_conflict
+        {
+            assignments = Self::resolve_assignments(table, table_name, items, &conflict_columns)?;
+            if let Some(selection) = selection {
+                query::check_condition(selection, &conflict_columns, "WHERE")?;
+            }
+        }
+
+        let source_rows = match &insert.source {
+            InsertSource::Values(rows) => rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|expr| query::evaluate(expr, &[], &[], context))
+                        .collect::<Result<Vec<_>, String>>()
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+            InsertSource::Query(query) => {
+                let tables = self.tables_as_of(query, context)?;
+                query::execute_query(&tables, query, context)?.rows
+            }
+        };
+
+        let defaults = columns
+            .iter()
+            .map(|c| c.default.as_deref().map(sql::parse_expression).transpose())
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // Índice de cada chave única: valor da chave -> posição da linha. Posições a partir
+        // de `existing` referem-se às linhas novas deste comando.
+        let existing = table.data.len();
+        let mut indexes: Vec<HashMap<String, usize>> = keys
+            .iter()
+            .map(|key| {
+                table
+                    .data
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, row)| key.value_of(row).map(|value| (value, i)))
+                    .collect()
+            })
+            .collect();
+        let mut rows: Vec<Vec<Value>> = Vec::with_capacity(source_rows.len());
+        let mut updates: HashMap<usize, Vec<Value>> = HashMap::new();
+        let mut affected = Vec::new();
+
+        for source in source_rows {
+            if source.len() != targets.len() {
+                return Err(match &insert.columns {
+                    None => format!(
+                        "A tabela '{}' tem {} colunas, mas foram informados {} valores.",
+                        table_name,
+                        columns.len(),
+                        source.len()
+                    ),
+                    Some(_) => format!(
+                        "Foram informadas {} colunas, mas {} valores.",
+                        targets.len(),
+                        source.len()
+                    ),
+                });
+            }
+
+            let mut values: Vec<Option<Value>> = vec![None; columns.len()];
+            for (&index, value) in targets.iter().zip(source) {
+                values[index] = Some(value);
+            }
+
+            let mut row = Vec::with_capacity(columns.len());
+            for ((value, column), default) in values.into_iter().zip(columns).zip(&defaults) {
+                let value = match (value, default) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => query::evaluate(default, &[], &[], context)?,
+                    (None, None) => Value::Null,
+                };
+                row.push(Self::cast_to_column(value, column, table_name)?);
+            }
+            Self::check_primary_key(&keys, columns, &row)?;
+
+            // Com uma chave de conflito explícita, ela é verificada antes das demais.
+            let conflict = conflict_key
+                .iter()
+                .copied()
+                .chain(0..keys.len())
+                .find_map(|k| {
+                    keys[k]
+                        .value_of(&row)
+                        .and_then(|value| indexes[k].get(&value))
+                        .map(|&position| (k, position))
+                });
+
+            let Some((k, position)) = conflict else {
+                for (key, index) in keys.iter().zip(&mut indexes) {
+                    if let Some(value) = key.value_of(&row) {
+                        index.insert(value, existing + rows.len());
+                    }
+                }
+                affected.push(existing + rows.len());
+                rows.push(row);
+                continue;
+            };
+
+            let action = match &insert.on_conflict {
+                Some(on_conflict) if conflict_key.is_none_or(|c| c == k) => &on_conflict.action,
+                _ => {
+                    return Err(format!(
+                        "Chave duplicada {} viola a restrição '{}' da tabela '{}'.",
+                        keys[k].describe(columns, &row),
+                        keys[k].name,
+                        table_name
+                    ))
+                }
+            };
+            let ConflictAction::DoUpdate { selection, .. } = action else {
+                continue;
+            };
+            if position >= existing || updates.contains_key(&position) {
+                return Err(
+                    "O comando ON CONFLICT DO UPDATE não pode alterar a mesma linha duas vezes."
+                        .to_string(),
+                );
+            }
+
+            let current = &table.data[position];
+            context.lock_row(table_name, current, RowLockMode::Update, LockWait::Wait)?;
+            let conflict_row: Vec<Value> = current.iter().chain(&row).cloned().collect();
+            if let Some(selection) = selection {
+                if !query::evaluate(selection, &conflict_columns, &conflict_row, context)?.is_true()
+                {
+                    continue;
+                }
+            }
+            let mut updated = current.clone();
+            for (index, expr) in &assignments {
+                let value = query::evaluate(expr, &conflict_columns, &conflict_row, context)?;
+                updated[*index] = Self::cast_to_column(value, &columns[*index], table_name)?;
+            }
+            Self::check_primary_key(&keys, columns, &updated)?;
+
+            for (key, index) in keys.iter().zip(&mut indexes) {
+                let old = key.value_of(current);
+                let new = key.value_of(&updated);
+                if old == new {
+                    continue;
+                }
+                if let Some(new) = &new {
+                    if index.contains_key(new) {
+                        return Err(format!(
+                            "Chave duplicada {} viola a restrição '{}' da tabela '{}'.",
+                            key.describe(columns, &updated),
+                            key.name,
+                            table_name
+                        ));
+                    }
+                }
+                if let Some(old) = old {
+                    index.remove(&old);
+                }
+                if let Some(new) = new {
+                    index.insert(new, position);
+                }
+            }
+            affected.push(position);
+            updates.insert(position, updated);
+        }
+
+        let written: Vec<&Vec<Value>> = rows.iter().chain(updates.values()).collect();
+        self.check_foreign_keys(table_name, table, &written)?;
+
+        let returned = if insert.returning.is_empty() {
+            None
+        } else {
+            let affected_rows: Vec<&Vec<Value>> = affected
+                .iter()
+                .map(|&position| match position.checked_sub(existing) {
+                    Some(new) => &rows[new],
+                    None => &updates[&position],
+                })
+                .collect();
+            Some(query::returning(
+                &insert.returning,
+                &target_columns,
+                &affected_rows,
+                context,
+            )?)
+        };
+
+        let changes: Vec<RowChange> = if table.triggers.is_empty() {
+            Vec::new()
+        } else {
+            affected
+                .iter()
+                .map(|&position| match position.checked_sub(existing) {
+                    Some(new) => RowChange::inserted(rows[new].clone()),
+                    None => {
+                        RowChange::updated(table.data[position].clone(), updates[&position].clone())
+                    }
+                })
+                .collect()
+        };
+        let (inserted, updated) = (rows.len(), updates.len());
+        self.apply_changes(table_name, &changes, inserted + updated, context, |table| {
+            let data = Arc::make_mut(&mut table.data);
+            for (position, row) in updates {
+                data[position] = row;
+            }
+            data.extend(rows);
+        })?;
+        if let Some(result_set) = returned {
+            return Ok(result_set.render());
+        }
+        match (inserted, updated) {
+            (1, 0) => Ok(format!("Dados inseridos na tabela '{}'.", table_name)),
+            (_, 0) => Ok(format!(
+                "{} linhas inseridas na tabela '{}'.",
+                inserted, table_name
+            )),
+            _ => Ok(format!(
+                "{} linha(s) inserida(s) e {} atualizada(s) na tabela '{}'.",
+                inserted, updated, table_name
+            )),
+        }
+    }
+
+    fn execute_update(&mut self, update: Update, context: &Context) -> Result<String, String> {
+        let table_name = &update.table;
+        let table = self.base_table(table_name)?;
+        let columns = Self::output_columns(table, update.alias.as_deref().unwrap_or(table_name));
+        let assignments =
+            Self::resolve_assignments(table, table_name, &update.assignments, &columns)?;
+        if let Some(selection) = &update.selection {
+            query::check_condition(selection, &columns, "WHERE")?;
+        }
+        let alias = update.alias.as_deref().unwrap_or(table_name);
+        context.record_read(table_name, alias, update.selection.as_ref());
+        context.lock_table(table_name, TableLockMode::RowExclusive, false)?;
+
+        let keys = table.unique_keys(table_name);
+        let mut data = table.data.to_vec();
+        let mut changed = Vec::new();
+        for (position, row) in table.data.iter().enumerate() {
+            if let Some(selection) = &update.selection {
+                if !query::evaluate(selection, &columns, row, context)?.is_true() {
+                    continue;
+                }
+            }
+            context.lock_row(table_name, row, RowLockMode::Update, LockWait::Wait)?;
+            let mut updated = row.clone();
+            for (index, expr) in &assignments {
+                let value = query::evaluate(expr, &columns, row, context)?;
+                updated[*index] = Self::cast_to_column(value, &table.columns[*index], table_name)?;
+            }
+            Self::check_primary_key(&keys, &table.columns, &updated)?;
+            data[position] = updated;
+            changed.push(position);
+        }
+
+        Self::check_unique_keys(&keys, &table.columns, table_name, &data, &changed)?;
+        let changed_rows: Vec<&Vec<Value>> = changed.iter().map(|&i| &data[i]).collect();
+        self.check_foreign_keys(table_name, table, &changed_rows)?;
+        self.check_referencing_rows(table_name, table, &data)?;
+
+        let returned = if update.returning.is_empty() {
+            None
+        } else {
+            Some(query::returning(
+                &update.returning,
+                &columns,
+                &changed_rows,
+                context,
+            )?)
+        };
+        let changes: Vec<RowChange> = if table.triggers.is_empty() {
+            Vec::new()
+        } else {
+            changed
+                .iter()
+                .map(|&i| RowChange::updated(table.data[i].clone(), data[i].clone()))
+                .collect()
+        };
+        let count = changed.len();
+        self.apply_changes(table_name, &changes, count, context, |table| {
+            table.data = Arc::new(data)
+        })?;
+        match returned {
+            Some(result_set) => Ok(result_set.render()),
+            None => Ok(format!(
+                "Linhas atualizadas na tabela '{}': {}.",
+                table_name, count
+            )),
+        }
+    }
+
+    fn execute_delete(&mut self, delete: Delete, context: &Context) -> Result<String, String> {
+        let table_name = &delete.table;
+        let table = self.base_table(table_name)?;
+        let columns = Self::output_columns(table, delete.alias.as_deref().unwrap_or(table_name));
+        if let Some(selection) = &delete.selection {
+            query::check_condition(selection, &columns, "WHERE")?;
+        }
+        let alias = delete.alias.as_deref().unwrap_or(table_name);
+        context.record_read(table_name, alias, delete.selection.as_ref());
+        context.lock_table(table_name, TableLockMode::RowExclusive, false)?;
+
+        let mut data = Vec::with_capacity(table.data.len());
+        let mut deleted = Vec::new();
+        for row in table.data.iter() {
+            let matches = match &delete.selection {
+                Some(selection) => query::evaluate(selection, &columns, row, context)?.is_true(),
+                None => true,
+            };
+            if matches {
+                context.lock_row(table_name, row, RowLockMode::Update, LockWait::Wait)?;
+                deleted.push(row);
+            } else {
+                data.push(row.clone());
+            }
+        }
+        self.check_referencing_rows(table_name, table, &data)?;
+
+        let returned = if delete.returning.is_empty() {
+            None
+        } else {
+            Some(query::returning(
+                &delete.returning,
+                &columns,
+                &deleted,
+                context,
+            )?)
+        };
+        let changes: Vec<RowChange> = if table.triggers.is_empty() {
+            Vec::new()
+        } else {
+            deleted
+                .iter()
+                .map(|row| RowChange::deleted(row.to_vec()))
+                .collect()
+        };
+        let count = deleted.len();
+        self.apply_changes(table_name, &changes, count, context, |table| {
+            table.data = Arc::new(data)
+        })?;
+        match returned {
+            Some(result_set) => Ok(result_set.render()),
+            None => Ok(format!(
+                "Linhas removidas da tabela '{}': {}.",
+                table_name, count
+            )),
+        }
+    }
+
+    // Dispara os gatilhos BEFORE, aplica a alteração e dispara os gatilhos AFTER. `apply`
+    // trabalha com as posições lidas pelo comando; se os gatilhos BEFORE alteraram a própria
+    // tabela, as mudanças são refeitas sobre a tabela relida.
+    fn apply_changes(
+        &mut self,
+        table_name: &str,
+        changes: &[RowChange],
+        count: usize,
+        context: &Context,
+        apply: impl FnOnce(&mut Table),
+    ) -> Result<(), String> {
+        // Sem gatilhos não há mudanças registradas, e nada pode alterar a tabela no meio.
+        let read = match changes.is_empty() {
+            true => None,
+            false => self.tables.get(table_name).map(|t| Arc::clone(&t.data)),
+        };
+        self.fire_triggers(table_name, TriggerTiming::Before, changes, context)?;
+        let rewritten = read.is_some_and(|read| {
+            self.tables
+                .get(table_name)
+                .is_some_and(|t| !Arc::ptr_eq(&read, &t.data))
+        });
+        if rewritten {
+            self.reapply_changes(table_name, changes)?;
+        } else if let Some(table) = self.tables.get_mut(table_name) {
+            apply(table);
+        }
+        if let Some(table) = self.tables.get_mut(table_name) {
+            if statistics::record_changes(table, count) {
+                self.schema_version = next_schema_version();
+            }
+        }
+        self.fire_triggers(table_name, TriggerTiming::After, changes, context)
+    }
+
+    // Refaz as mudanças do comando sobre a tabela alterada pelos gatilhos BEFORE, encontrando
+    // cada linha pelos valores que o comando leu. Como no PostgreSQL, é um erro se o gatilho
+    // alterou ou removeu uma das linhas que o comando ia alterar.
+    fn reapply_changes(&mut self, table_name: &str, changes: &[RowChange]) -> Result<(), String> {
+        let table = &self.tables[table_name];
+        let mut data = table.data.to_vec();
+        let mut touched = vec![false; data.len()];
+        let mut removed = vec![false; data.len()];
+        for change in changes {
+            let Some(old) = &change.old else {
+                if let Some(new) = &change.new {
+                    data.push(new.clone());
+                    touched.push(true);
+                    removed.push(false);
+                }
+                continue;
+            };
+            let position = (0..data.len())
+                .find(|&i| !touched[i] && data[i] == *old)
+                .ok_or_else(|| {
+                    format!(
+                        "Uma linha a ser alterada na tabela '{}' já foi modificada por um gatilho disparado pelo mesmo comando.",
+                        table_name
+                    )
+                })?;
+            touched[position] = true;
+            match &change.new {
+                Some(new) => data[position] = new.clone(),
+                None => removed[position] = true,
+            }
+        }
+        let mut removed = removed.into_iter();
+        data.retain(|_| !removed.next().unwrap_or(false));
+
+        let mut altered = table.clone();
+        altered.data = Arc::new(data);
+        self.check_table(table_name, &altered)?;
+        self.check_referencing_rows(table_name, table, &altered.data)?;
+        self.tables.insert(table_name.to_string(), altered);
+        Ok(())
+    }
+
+    fn fire_triggers(
+        &mut self,
+        table_name: &str,
+        timing: TriggerTiming,
+        changes: &[RowChange],
+        context: &Context,
+    ) -> Result<(), String> {
+        let Some(table) = self.tables.get(table_name) else {
+            return Ok(());
+        };
+        let triggers: Vec<Trigger> = table
+            .triggers
+            .iter()
+            .filter(|t| t.timing == timing)
+            .cloned()
+            .collect();
+        if triggers.is_empty() || changes.is_empty() {
+            return Ok(());
+        }
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Limite de {} gatilhos aninhados excedido.",
+                MAX_NESTING_DEPTH
+            ));
+        }
+        let columns = table.columns.clone();
+        let mut definitions = Vec::with_capacity(triggers.len());
+        for trigger in &triggers {
+            let Statement::CreateTrigger(definition) = sql::parse(&trigger.definition)? else {
+                return Err(format!("Gatilho '{}' inválido.", trigger.name));
+            };
+            definitions.push(definition);
+        }
+
+        self.nesting_depth += 1;
+        let result = self.run_triggers(&triggers, &definitions, &columns, changes, context);
+        self.nesting_depth -= 1;
+        result
+    }
+
+    fn run_triggers(
+        &mut self,
+        triggers: &[Trigger],
+        definitions: &[CreateTrigger],
+        columns: &[Column],
+        changes: &[RowChange],
+        context: &Context,
+    ) -> Result<(), String> {
+        for change in changes {
+            for (trigger, definition) in triggers.iter().zip(definitions) {
+                if !trigger.events.contains(&change.event) {
+                    continue;
+                }
+                let fire = || -> Result<Vec<Statement>, String> {
+                    if let Some(condition) = &definition.condition {
+                        let condition = change.bind_expr(condition, columns)?;
+                        if !query::evaluate(&condition, &[], &[], context)?.is_true() {
+                            return Ok(Vec::new());
+                        }
+                    }
+                    definition
+                        .body
+                        .iter()
+                        .map(|statement| change.bind_statement(statement, columns))
+                        .collect()
+                };
+                let result = fire().and_then(|statements| {
+                    for statement in statements {
+                        match statement {
+                            Statement::Insert(insert) => self.execute_insert(insert, context),
+                            Statement::Update(update) => self.execute_update(update, context),
+                            Statement::Delete(delete) => self.execute_delete(delete, context),
+                            Statement::Call { name, arguments } => self
+                                .call_procedure(&name, &arguments, context)
+                                .map(|_| String::new()),
+                            _ => continue,
+                        }?;
+                    }
+                    Ok(())
+                });
+                // Erros de gatilhos aninhados já chegam identificados.
+                result.map_err(|e| {
+                    if e.starts_with("Gatilho '") {
+                        e
+                    } else {
+                        format!("Gatilho '{}': {}", trigger.name, e)
+                    }
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Coluna dos resultados gerados pelo próprio banco, como SHOW HISTORY.
+    fn output_column(name: &str, data_type: DataType) -> OutputColumn {
+        OutputColumn {
+            table: None,
+            name: name.to_string(),
+            data_type: Some(data_type),
+        }
+    }
+
+    fn output_columns(table: &Table, alias: &str) -> Vec<OutputColumn> {
+        table
+            .columns
+            .iter()
+            .map(|c| OutputColumn {
+                table: Some(alias.to_string()),
+                name: c.name.clone(),
+                data_type: Some(c.data_type),
+            })
+            .collect()
+    }
+
+    fn resolve_assignments<'a>(
+        table: &Table,
+        table_name: &str,
+        assignments: &'a [Assignment],
+        columns: &[OutputColumn],
+    ) -> Result<Vec<(usize, &'a Expr)>, String> {
+        let mut resolved: Vec<(usize, &Expr)> = Vec::with_capacity(assignments.len());
+        for assignment in assignments {
+            let index = table.column_index(&assignment.column).ok_or_else(|| {
+                format!(
+                    "Coluna '{}' não encontrada na tabela '{}'.",
+                    assignment.column, table_name
+                )
+            })?;
+            if resolved.iter().any(|(i, _)| *i == index) {
+                return Err(format!(
+                    "Coluna '{}' informada mais de uma vez.",
+                    assignment.column
+                ));
+            }
+            if assignment.value.contains_window_function() {
+                return Err("Funções de janela não são permitidas em SET.".to_string());
+            }
+            query::infer_type(&assignment.value, columns)?;
+            resolved.push((index, &assignment.value));
+        }
+        Ok(resolved)
+    }
+
+    // Verifica se os valores gravados nas colunas com chave estrangeira existem na tabela
+    // referenciada; em autorreferências, as próprias linhas gravadas também contam.
+    fn check_foreign_keys(
+        &self,
+        table_name: &str,
+        table: &Table,
+        written: &[&Vec<Value>],
+    ) -> Result<(), String> {
+        for (index, column) in table.columns.iter().enumerate() {
+            let Some((referenced_table, referenced_column)) = &column.foreign_key else {
+                continue;
+            };
+            for row in written.iter().filter(|row| !row[index].is_null()) {
+                let value = &row[index];
+                let written_here = referenced_table == table_name
+                    && table.column_index(referenced_column).is_some_and(|i| {
+                        written
+                            .iter()
+                            .any(|r| r[i].compare(value) == Some(Ordering::Equal))
+                    });
+                if !written_here
+                    && !self.is_valid_foreign_key(referenced_table, referenced_column, value)
+                {
+                    return Err(format!(
+                        "Valor '{}' inválido para a coluna '{}' na tabela '{}'.",
+                        value, column.name, table_name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Impede que UPDATE ou DELETE removam valores ainda referenciados por chaves estrangeiras.
+    fn check_referencing_rows(
+        &self,
+        table_name: &str,
+        table: &Table,
+        new_data: &[Vec<Value>],
+    ) -> Result<(), String> {
+        for (referencing_name, referencing) in &self.tables {
+            for (index, column) in referencing.columns.iter().enumerate() {
+                let Some((referenced_table, referenced_column)) = &column.foreign_key else {
+                    continue;
+                };
+                if referenced_table != table_name {
+                    continue;
+                }
+                let Some(referenced_index) = table.column_index(referenced_column) else {
+                    continue;
+                };
+                let values_in = |rows: &[Vec<Value>]| -> HashSet<String> {
+                    rows.iter()
+                        .filter_map(|row| row.get(referenced_index))
+                        .filter(|v| !v.is_null())
+                        .map(|v| v.to_string())
+                        .collect()
+                };
+                let remaining = values_in(new_data);
+                let removed: HashSet<String> = values_in(&table.data)
+                    .difference(&remaining)
+                    .cloned()
+                    .collect();
+                if removed.is_empty() {
+                    continue;
+                }
+                let referencing_rows = if referencing_name == table_name {
+                    new_data
+                } else {
+                    &referencing.data
+                };
+                if let Some(value) = referencing_rows
+                    .iter()
+                    .filter_map(|row| row.get(index))
+                    .find(|v| !v.is_null() && removed.contains(&v.to_string()))
+                {
+                    return Err(format!(
+                        "O valor '{}' da coluna '{}' ainda é referenciado pela tabela '{}'.",
+                        value, referenced_column, referencing_name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_unique_keys(
+        keys: &[UniqueKey],
+        columns: &[Column],
+        table_name: &str,
+        data: &[Vec<Value>],
+        changed: &[usize],
+    ) -> Result<(), String> {
+        let changed: HashSet<usize> = changed.iter().copied().collect();
+        for key in keys {
+            let mut index: HashSet<String> = data
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !changed.contains(i))
+                .filter_map(|(_, row)| key.value_of(row))
+                .collect();
+            for &position in &changed {
+                if let Some(value) = key.value_of(&data[position]) {
+                    if !index.insert(value) {
+                        return Err(format!(
+                            "Chave duplicada {} viola a restrição '{}' da tabela '{}'.",
+                            key.describe(columns, &data[position]),
+                            key.name,
+                            table_name
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn conflict_key(table: &Table, keys: &[UniqueKey], target: &[String]) -> Result<usize, String> {
+        let mut columns = Vec::with_capacity(target.len());
+        for name in target {
+            columns.push(
+                table
+                    .column_index(name)
+                    .ok_or_else(|| format!("Coluna '{}' não encontrada.", name))?,
+            );
+        }
+        columns.sort_unstable();
+        keys.iter()
+            .position(|key| {
+                let mut key_columns = key.columns.clone();
+                key_columns.sort_unstable();
+                key_columns == columns
+            })
+            .ok_or_else(|| {
+                "Não existe restrição UNIQUE ou PRIMARY KEY correspondente à especificação do ON CONFLICT."
+                    .to_string()
+            })
+    }
+
+    fn check_column_definition(definition: &ColumnDefinition) -> Result<(), String> {
+        if definition.is_serial && definition.default.is_some() {
+            return Err(format!(
+                "A coluna '{}' é auto-incrementada e não aceita DEFAULT.",
+                definition.name
+            ));
+        }
+        if let Some(default) = &definition.default {
+            Self::check_default(&definition.name, default)?;
+        }
+        Ok(())
+    }
+
+    fn check_default(column_name: &str, default: &Expr) -> Result<(), String> {
+        query::infer_type(default, &[])
+            .map(|_| ())
+            .map_err(|e| format!("DEFAULT inválido para a coluna '{}': {}", column_name, e))
+    }
+
+    fn cast_to_column(value: Value, column: &Column, table_name: &str) -> Result<Value, String> {
+        if value.is_null() && column.is_not_null {
+            return Err(format!(
+                "A coluna '{}' da tabela '{}' não aceita valores nulos.",
+                column.name, table_name
+            ));
+        }
+        value.cast(column.data_type).map_err(|_| {
+            format!(
+                "Valor '{}' inválido para a coluna '{}' na tabela '{}'.",
+                value, column.name, table_name
+            )
+        })
+    }
+
+    fn check_primary_key(
+        keys: &[UniqueKey],
+        columns: &[Column],
+        row: &[Value],
+    ) -> Result<(), String> {
+        for key in keys.iter().filter(|k| k.is_primary_key) {
+            if let Some(&index) = key.columns.iter().find(|&&i| row[i].is_null()) {
+                return Err(format!(
+                    "A coluna '{}' faz parte da chave primária e não pode ser nula.",
+                    columns[index].name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_valid_foreign_key(
+        &self,
+        referenced_table: &str,
+        referenced_column: &str,
+        value: &Value,
+    ) -> bool {
+        if let Some(table) = self.tables.get(referenced_table) {
+            if let Some(column_index) = table
+                .columns
+                .iter()
+                .position(|c| c.name == referenced_column)
+            {
+                return table
+                    .data
+                    .iter()
+                    .any(|row| row[column_index].compare(value) == Some(Ordering::Equal));
+            }
+        }
+        false
+    }
+
+    fn execute_alter_table(
+        &mut self,
+        alter: AlterTable,
+        context: &Context,
+    ) -> Result<String, String> {
+        let table_name = &alter.table;
+        self.base_table(table_name)?;
+
+        match alter.action {
+            AlterTableAction::AddColumn(definition) => {
+                self.add_column(table_name, definition, context)
+            }
+            AlterTableAction::DropColumn(column_name) => {
+                self.drop_column(table_name, &column_name, context)
+            }
+            AlterTableAction::RenameTable(new_name) => {
+                self.rename_table(table_name, &new_name, context)
+            }
+            AlterTableAction::RenameColumn { old_name, new_name } => {
+                self.rename_column(table_name, &old_name, &new_name, context)
+            }
+            AlterTableAction::AlterColumn { column, change } => {
+                self.alter_column(table_name, &column, change)
+            }
+            AlterTableAction::AddConstraint(constraint) => {
+                self.add_constraint(table_name, constraint)
+            }
+            AlterTableAction::DropConstraint(name) => self.drop_constraint(table_name, &name),
+            AlterTableAction::SetAutoanalyzeFraction(fraction) => {
+                if let Some(table) = self.tables.get_mut(table_name) {
+                    table.statistics.autoanalyze_fraction = Some(fraction);
+                }
+                Ok(format!(
+                    "autoanalyze_fraction da tabela '{}' definido como {}.",
+                    table_name, fraction
+                ))
+            }
+        }
+    }
+
+    fn find_column(table: &Table, table_name: &str, column_name: &str) -> Result<usize, String> {
+        table.column_index(column_name).ok_or_else(|| {
+            format!(
+                "Coluna '{}' não encontrada na tabela '{}'.",
+                column_name, table_name
+            )
+        })
+    }
+
+    // Valida chaves primárias, restrições UNIQUE e chaves estrangeiras de todas as linhas.
+    fn check_table(&self, table_name: &str, table: &Table) -> Result<(), String> {
+        let keys = table.unique_keys(table_name);
+        for row in table.data.iter() {
+            Self::check_primary_key(&keys, &table.columns, row)?;
+        }
+        let all: Vec<usize> = (0..table.data.len()).collect();
+        Self::check_unique_keys(&keys, &table.columns, table_name, &table.data, &all)?;
+        let rows: Vec<&Vec<Value>> = table.data.iter().collect();
+        self.check_foreign_keys(table_name, table, &rows)
+    }
+
+    // As linhas existentes recebem o valor padrão da nova coluna, ou NULL.
+    fn add_column(
+        &mut self,
+        table_name: &str,
+        definition: ColumnDefinition,
+        context: &Context,
+    ) -> Result<String, String> {
+        let table = &self.tables[table_name];
+        let column_name = definition.name.clone();
+        if table.column_index(&column_name).is_some() {
+            return Err(format!(
+                "Coluna '{}' já existe na tabela '{}'.",
+                column_name, table_name
+            ));
+        }
+        if definition.is_serial {
+            return Err(format!(
+                "Não é possível adicionar a coluna auto-incrementada '{}' a uma tabela existente.",
+                column_name
+            ));
+        }
+        if definition.is_primary_key && table.columns.iter().any(|c| c.is_primary_key) {
+            return Err(format!(
+                "A tabela '{}' já possui uma chave primária.",
+                table_name
+            ));
+        }
+        Self::check_column_definition(&definition)?;
+        // Views criadas antes de o `*` ser expandido na definição mudariam de forma.
+        if let Some(view) = self.dependent_view(table_name, Some(&column_name)) {
+            return Err(format!(
+                "Não é possível adicionar a coluna '{}' à tabela '{}': a view '{}' depende das colunas dela.",
+                column_name, table_name, view
+            ));
+        }
+
+        let mut altered = table.clone();
+        if definition.is_unique {
+            altered.constraints.push(UniqueConstraint {
+                name: format!("{}_{}_key", table_name, column_name),
+                columns: vec![column_name.clone()],
+            });
+        }
+        let column = Column::from(definition);
+        let default = column
+            .default
+            .as_deref()
+            .map(sql::parse_expression)
+            .transpose()?;
+        for row in Arc::make_mut(&mut altered.data) {
+            row.resize(table.columns.len(), Value::Null);
+            let value = match &default {
+                Some(default) => query::evaluate(default, &[], &[], context)?,
+                None => Value::Null,
+            };
+            row.push(Self::cast_to_column(value, &column, table_name)?);
+        }
+        altered.columns.push(column);
+        self.check_table(table_name, &altered)?;
+
+        self.tables.insert(table_name.to_string(), altered);
+        Ok(format!(
+            "Coluna '{}' adicionada à tabela '{}'.",
+            column_name, table_name
+        ))
+    }
+
+    fn drop_column(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        context: &Context,
+    ) -> Result<String, String> {
+        let index = Self::find_column(&self.tables[table_name], table_name, column_name)?;
+        if let Some(view) = self.dependent_view(table_name, Some(column_name)) {
+            return Err(format!(
+                "Não é possível excluir a coluna '{}' da tabela '{}': a view '{}' depende dela.",
+                column_name, table_name, view
+            ));
+        }
+        if let Some((owner, trigger)) = self
+            .dependent_triggers(table_name, Some(column_name))?
+            .first()
+        {
+            return Err(format!(
+                "Não é possível excluir a coluna '{}' da tabela '{}': o gatilho '{}' da tabela '{}' depende dela.",
+                column_name, table_name, trigger, owner
+            ));
+        }
+        for (referencing_name, referencing) in &self.tables {
+            for column in &referencing.columns {
+                let Some((referenced_table, referenced_column)) = &column.foreign_key else {
+                    continue;
+                };
+                let is_self = referencing_name == table_name && column.name == column_name;
+                if referenced_table == table_name && referenced_column == column_name && !is_self {
+                    return Err(format!(
+                        "A coluna '{}' é referenciada pela chave estrangeira da coluna '{}' da tabela '{}'.",
+                        column_name, column.name, referencing_name
+                    ));
+                }
+            }
+        }
+
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Tabela '{}' não existe.", table_name))?;
+        table.columns.remove(index);
+        table
+            .constraints
+            .retain(|constraint| !constraint.columns.iter().any(|c| c == column_name));
+        for row in Arc::make_mut(&mut table.data) {
+            if index < row.len() {
+                row.remove(index);
+            }
+        }
+        table.statistics.forget_column(column_name);
+        let owner = Some((table_name.to_string(), column_name.to_string()));
+        context
+            .sequences()
+            .retain(|_, sequence| sequence.owned_by != owner);
+        Ok(format!(
+            "Coluna '{}' removida da tabela '{}'.",
+            column_name, table_name
+        ))
+    }
+
+    // Chaves estrangeiras e sequências que apontam para a tabela acompanham o novo nome.
+    fn rename_table(
+        &mut self,
+        table_name: &str,
+        new_name: &str,
+        context: &Context,
+    ) -> Result<String, String> {
+        if self.tables.contains_key(new_name) {
+            return Err(format!("Tabela '{}' já existe.", new_name));
+        }
+        if let Some(view) = self.dependent_view(table_name, None) {
+            return Err(format!(
+                "Não é possível renomear a tabela '{}': a view '{}' depende dela.",
+                table_name, view
+            ));
+        }
+        if let Some((owner, trigger)) = self.dependent_triggers(table_name, None)?.first() {
+            return Err(format!(
+                "Não é possível renomear a tabela '{}': o gatilho '{}' da tabela '{}' depende dela.",
+                table_name, trigger, owner
+            ));
+        }
+        if let Some(table) = self.tables.remove(table_name) {
+            self.tables.insert(new_name.to_string(), table);
+        }
+        for table in self.tables.values_mut() {
+            for column in &mut table.columns {
+                if let Some((referenced_table, _)) = &mut column.foreign_key {
+                    if referenced_table == table_name {
+                        *referenced_table = new_name.to_string();
+                    }
+                }
+            }
+        }
+        for sequence in context.sequences().values_mut() {
+            if let Some((owner, _)) = &mut sequence.owned_by {
+                if owner == table_name {
+                    *owner = new_name.to_string();
+                }
+            }
+        }
+        Ok(format!(
+            "Tabela '{}' renomeada para '{}'.",
+            table_name, new_name
+        ))
+    }
+
+    fn rename_column(
+        &mut self,
+        table_name: &str,
+        old_name: &str,
+        new_name: &str,
+        context: &Context,
+    ) -> Result<String, String> {
+        let table = &self.tables[table_name];
+        let index = Self::find_column(table, table_name, old_name)?;
+        if table.column_index(new_name).is_some() {
+            return Err(format!(
+                "Coluna '{}' já existe na tabela '{}'.",
+                new_name, table_name
+            ));
+        }
+        if let Some(view) = self.dependent_view(table_name, Some(old_name)) {
+            return Err(format!(
+                "Não é possível renomear a coluna '{}' da tabela '{}': a view '{}' depende dela.",
+                old_name, table_name, view
+            ));
+        }
+        if let Some((owner, trigger)) = self.dependent_triggers(table_name, Some(old_name))?.first()
+        {
+            return Err(format!(
+                "Não é possível renomear a coluna '{}' da tabela '{}': o gatilho '{}' da tabela '{}' depende dela.",
+                old_name, table_name, trigger, owner
+            ));
+        }
+
+        if let Some(table) = self.tables.get_mut(table_name) {
+            table.columns[index].name = new_name.to_string();
+            table.statistics.rename_column(old_name, new_name);
+            for constraint in &mut table.constraints {
+                for column in &mut constraint.columns {
+                    if column == old_name {
+                        *column = new_name.to_string();
+                    }
+                }
+            }
+        }
+        for table in self.tables.values_mut() {
+            for column in &mut table.columns {
+                if let Some((referenced_table, referenced_column)) = &mut column.foreign_key {
+                    if referenced_table == table_name && referenced_column == old_name {
+                        *referenced_column = new_name.to_string();
+                    }
+                }
+            }
+        }
+        let owner = Some((table_name.to_string(), old_name.to_string()));
+        for sequence in context.sequences().values_mut() {
+            if sequence.owned_by == owner {
+                sequence.owned_by = Some((table_name.to_string(), new_name.to_string()));
+            }
+        }
+        Ok(format!(
+            "Coluna '{}' renomeada para '{}' na tabela '{}'.",
+            old_name, new_name, table_name
+        ))
+    }
+
+    fn alter_column(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        change: ColumnChange,
+    ) -> Result<String, String> {
+        let table = &self.tables[table_name];
+        let index = Self::find_column(table, table_name, column_name)?;
+        if let ColumnChange::SetType(_) = change {
+            if let Some(view) = self.dependent_view(table_name, Some(column_name)) {
+                return Err(format!(
+                    "Não é possível alterar o tipo da coluna '{}' da tabela '{}': a view '{}' depende dela.",
+                    column_name, table_name, view
+                ));
+            }
+        }
+        let mut altered = table.clone();
+        let column = &mut altered.columns[index];
+        match change {
+            ColumnChange::SetType(data_type) => {
+                for row in Arc::make_mut(&mut altered.data) {
+                    if let Some(value) = row.get_mut(index) {
+                        *value = value.cast(data_type).map_err(|_| {
+                            format!(
+                                "Não foi possível converter o valor '{}' da coluna '{}' para {}.",
+                                value, column_name, data_type
+                            )
+                        })?;
+                    }
+                }
+                column.data_type = data_type;
+                altered.statistics.forget_column(column_name);
+            }
+            ColumnChange::SetDefault(default) => {
+                Self::check_default(column_name, &default)?;
+                column.default = Some(default.to_string());
+            }
+            ColumnChange::DropDefault => column.default = None,
+            ColumnChange::SetNotNull => {
+                if altered
+                    .data
+                    .iter()
+                    .any(|row| row.get(index).is_none_or(Value::is_null))
+                {
+                    return Err(format!(
+                        "A coluna '{}' da tabela '{}' contém valores nulos.",
+                        column_name, table_name
+                    ));
+                }
+                column.is_not_null = true;
+            }
+            ColumnChange::DropNotNull => {
+                if column.is_primary_key {
+                    return Err(format!(
+                        "A coluna '{}' faz parte da chave primária e não pode ser nula.",
+                        column_name
+                    ));
+                }
+                column.is_not_null = false;
+            }
+        }
+        self.check_table(table_name, &altered)?;
+
+        self.tables.insert(table_name.to_string(), altered);
+        Ok(format!(
+            "Coluna '{}' da tabela '{}' alterada.",
+            column_name, table_name
+        ))
+    }
+
+    fn add_constraint(
+        &mut self,
+        table_name: &str,
+        constraint: TableConstraint,
+    ) -> Result<String, String> {
+        let table = &self.tables[table_name];
+        let (ConstraintKind::PrimaryKey(names) | ConstraintKind::Unique(names)) = &constraint.kind;
+        for name in names {
+            if table.column_index(name).is_none() {
+                return Err(format!(
+                    "Coluna '{}' da restrição não existe na tabela '{}'.",
+                    name, table_name
+                ));
+            }
+        }
+
+        let mut altered = table.clone();
+        let name = match constraint.kind {
+            ConstraintKind::PrimaryKey(names) => {
+                if table.columns.iter().any(|c| c.is_primary_key) {
+                    return Err(format!(
+                        "A tabela '{}' já possui uma chave primária.",
+                        table_name
+                    ));
+                }
+                for column in altered
+                    .columns
+                    .iter_mut()
+                    .filter(|c| names.contains(&c.name))
+                {
+                    column.is_primary_key = true;
+                }
+                format!("{}_pkey", table_name)
+            }
+            ConstraintKind::Unique(names) => {
+                let name = constraint
+                    .name
+                    .unwrap_or_else(|| format!("{}_{}_key", table_name, names.join("_")));
+                if table.unique_keys(table_name).iter().any(|k| k.name == name) {
+                    return Err(format!(
+                        "Restrição '{}' já existe na tabela '{}'.",
+                        name, table_name
+                    ));
+                }
+                altered.constraints.push(UniqueConstraint {
+                    name: name.clone(),
+                    columns: names,
+                });
+                name
+            }
+        };
+        self.check_table(table_name, &altered)?;
+
+        self.tables.insert(table_name.to_string(), altered);
+        Ok(format!(
+            "Restrição '{}' adicionada à tabela '{}'.",
+            name, table_name
+        ))
+    }
+
+    fn drop_constraint(&mut self, table_name: &str, name: &str) -> Result<String, String> {
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Tabela '{}' não existe.", table_name))?;
+        let has_primary_key = table.columns.iter().any(|c| c.is_primary_key);
+        if has_primary_key && name == format!("{}_pkey", table_name) {
+            for column in &mut table.columns {
+                column.is_primary_key = false;
+            }
+        } else if let Some(position) = table.constraints.iter().position(|c| c.name == name) {
+            table.constraints.remove(position);
+        } else {
+            return Err(format!(
+                "Restrição '{}' não existe na tabela '{}'.",
+                name, table_name
+            ));
+        }
+        Ok(format!(
+            "Restrição '{}' removida da tabela '{}'.",
+            name, table_name
+        ))
+    }
+
+    fn execute_drop_table(
+        &mut self,
+        table_name: &str,
+        context: &Context,
+    ) -> Result<String, String> {
+        self.base_table(table_name)?;
+        if let Some(view) = self.dependent_view(table_name, None) {
+            return Err(format!(
+                "Não é possível excluir a tabela '{}': a view '{}' depende dela.",
+                table_name, view
+            ));
+        }
+        // Os gatilhos da própria tabela são excluídos junto com ela.
+        let triggers = self.dependent_triggers(table_name, None)?;
+        if let Some((owner, trigger)) = triggers.iter().find(|(owner, _)| *owner != table_name) {
+            return Err(format!(
+                "Não é possível excluir a tabela '{}': o gatilho '{}' da tabela '{}' depende dela.",
+                table_name, trigger, owner
+            ));
+        }
+        self.tables.remove(table_name);
+        context.sequences().retain(
+            |_, sequence| !matches!(&sequence.owned_by, Some((table, _)) if table == table_name),
+        );
+        Ok(format!("Tabela '{}' excluída com sucesso.", table_name))
+    }
+
+    fn execute_create_trigger(
+        &mut self,
+        create: CreateTrigger,
+        command: &str,
+    ) -> Result<String, String> {
+        let table_name = &create.table;
+        let table = self.base_table(table_name)?;
+        if table.triggers.iter().any(|t| t.name == create.name) {
+            return Err(format!(
+                "Gatilho '{}' já existe na tabela '{}'.",
+                create.name, table_name
+            ));
+        }
+
+        // Valida as referências a NEW e OLD com uma linha vazia de cada evento.
+        let empty = vec![Value::Null; table.columns.len()];
+        for event in &create.events {
+            let change = match event {
+                TriggerEvent::Insert => RowChange::inserted(empty.clone()),
+                TriggerEvent::Update => RowChange::updated(empty.clone(), empty.clone()),
+                TriggerEvent::Delete => RowChange::deleted(empty.clone()),
+            };
+            if let Some(condition) = &create.condition {
+                let condition = change.bind_expr(condition, &table.columns)?;
+                query::check_condition(&condition, &[], "WHEN")?;
+            }
+            for statement in &create.body {
+                change.bind_statement(statement, &table.columns)?;
+            }
+        }
+
+        let trigger = Trigger {
+            name: create.name.clone(),
+            timing: create.timing,
+            events: create.events,
+            definition: command.trim().to_string(),
+        };
+        if let Some(table) = self.tables.get_mut(table_name) {
+            table.triggers.push(trigger);
+        }
+        Ok(format!(
+            "Gatilho '{}' criado na tabela '{}'.",
+            create.name, table_name
+        ))
+    }
+
+    fn execute_drop_trigger(&mut self, name: &str, table_name: &str) -> Result<String, String> {
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Tabela '{}' não existe.", table_name))?;
+        let position = table
+            .triggers
+            .iter()
+            .position(|t| t.name == name)
+            .ok_or_else(|| format!("Gatilho '{}' não existe na tabela '{}'.", name, table_name))?;
+        table.triggers.remove(position);
+        Ok(format!(
+            "Gatilho '{}' removido da tabela '{}'.",
+            name, table_name
+        ))
+    }
+
+    fn execute_create_procedure(
+        &mut self,
+        create: CreateProcedure,
+        command: &str,
+    ) -> Result<String, String> {
+        if self.procedures.contains_key(&create.name) {
+            return Err(format!("Procedimento '{}' já existe.", create.name));
+        }
+        let mut names = HashSet::new();
+        if let Some((name, _)) = create.parameters.iter().find(|(n, _)| !names.insert(n)) {
+            return Err(format!("Parâmetro '{}' duplicado.", name));
+        }
+        self.procedures.insert(
+            create.name.clone(),
+            Procedure {
+                name: create.name.clone(),
+                definition: command.trim().to_string(),
+            },
+        );
+        Ok(format!(
+            "Procedimento '{}' criado com sucesso!",
+            create.name
+        ))
+    }
+
+    fn execute_drop_procedure(&mut self, name: &str) -> Result<String, String> {
+        if self.procedures.remove(name).is_none() {
+            return Err(format!("Procedimento '{}' não existe.", name));
+        }
+        Ok(format!("Procedimento '{}' excluído com sucesso.", name))
+    }
+
+    // Executa o procedimento e devolve as mensagens produzidas por RAISE NOTICE e SELECT.
+    fn call_procedure(
+        &mut self,
+        name: &str,
+        arguments: &[Expr],
+        context: &Context,
+    ) -> Result<Vec<String>, String> {
+        let procedure = self
+            .procedures
+            .get(name)
+            .ok_or_else(|| format!("Procedimento '{}' não existe.", name))?;
+        let Statement::CreateProcedure(definition) = sql::parse(&procedure.definition)? else {
+            return Err(format!("Procedimento '{}' inválido.", name));
+        };
+        if arguments.len() != definition.parameters.len() {
+            return Err(format!(
+                "Procedimento '{}' espera {} argumento(s), mas recebeu {}.",
+                name,
+                definition.parameters.len(),
+                arguments.len()
+            ));
+        }
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Limite de {} chamadas de procedimentos aninhadas excedido.",
+                MAX_NESTING_DEPTH
+            ));
+        }
+
+        let mut variables = Variables::default();
+        for ((parameter, data_type), argument) in definition.parameters.iter().zip(arguments) {
+            let value = query::evaluate(argument, &[], &[], context)?;
+            variables.declare(parameter, *data_type, value)?;
+        }
+        let mut output = Vec::new();
+        self.nesting_depth += 1;
+        let result = self.run_block(&definition.body, &mut variables, &mut output, context);
+        self.nesting_depth -= 1;
+        // Erros de procedimentos aninhados já chegam identificados.
+        result.map_err(|e| {
+            if e.starts_with("Procedimento '") {
+                e
+            } else {
+                format!("Procedimento '{}': {}", name, e)
+            }
+        })?;
+        Ok(output)
+    }
+
+    // Devolve false quando um RETURN encerra o procedimento.
+    fn run_block(
+        &mut self,
+        body: &[ProcedureStatement],
+        variables: &mut Variables,
+        output: &mut Vec<String>,
+        context: &Context,
+    ) -> Result<bool, String> {
+        context.check_deadline()?;
+        for statement in body {
+            let completed = match statement {
+                ProcedureStatement::Declare {
+                    name,
+                    data_type,
+                    default,
+                } => {
+                    let value = match default {
+                        Some(default) => variables.evaluate(default, context)?,
+                        None => Value::Null,
+                    };
+                    variables.declare(name, *data_type, value)?;
+                    true
+                }
+                ProcedureStatement::Assign { name, value } => {
+                    let value = variables.evaluate(value, context)?;
+                    variables.assign(name, value)?;
+                    true
+                }
+                ProcedureStatement::If {
+                    branches,
+                    else_branch,
+                } => {
+                    let mut chosen = else_branch;
+                    for (condition, branch) in branches {
+                        if variables.condition(condition, "IF", context)? {
+                            chosen = branch;
+                            break;
+                        }
+                    }
+                    self.run_block(chosen, variables, output, context)?
+                }
+                ProcedureStatement::While { condition, body } => {
+                    let mut completed = true;
+                    while completed && variables.condition(condition, "WHILE", context)? {
+                        completed = self.run_block(body, variables, output, context)?;
+                    }
+                    completed
+                }
+                ProcedureStatement::For {
+                    record,
+                    query,
+                    body,
+                } => {
+                    let query = variables.bind_query(query)?;
+                    let tables = self.tables_as_of(&query, context)?;
+                    let result = query::execute_query(&tables, &query, context)?;
+                    let columns: Vec<String> =
+                        result.columns.iter().map(|c| c.name.clone()).collect();
+                    let mut completed = true;
+                    for row in result.rows {
+                        variables.set_record(record, columns.clone(), row);
+                        completed = self.run_block(body, variables, output, context)?;
+                        if !completed {
+                            break;
+                        }
+                    }
+                    completed
+                }
+                ProcedureStatement::Raise {
+                    level,
+                    message,
+                    arguments,
+                } => {
+                    let message = variables.format_message(message, arguments, context)?;
+                    if *level == RaiseLevel::Exception {
+                        return Err(message);
+                    }
+                    output.push(format!("AVISO: {}", message));
+                    true
+                }
+                ProcedureStatement::Return => false,
+                ProcedureStatement::Execute { statement, into } => {
+                    self.run_statement(
+                        variables.bind_statement(statement)?,
+                        into,
+                        variables,
+                        output,
+                        context,
+                    )?;
+                    true
+                }
+            };
+            if !completed {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn run_statement(
+        &mut self,
+        statement: Statement,
+        into: &[String],
+        variables: &mut Variables,
+        output: &mut Vec<String>,
+        context: &Context,
+    ) -> Result<(), String> {
+        match statement {
+            Statement::Select(query) => {
+                let tables = self.tables_as_of(&query, context)?;
+                let result = query::execute_query(&tables, &query, context)?;
+                if into.is_empty() {
+                    output.push(result.render());
+                    return Ok(());
+                }
+                if into.len() != result.columns.len() {
+                    return Err(format!(
+                        "SELECT INTO espera {} coluna(s), mas a consulta retornou {}.",
+                        into.len(),
+                        result.columns.len()
+                    ));
+                }
+                // Sem linhas, as variáveis recebem NULL.
+                let row = result
+                    .rows
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| vec![Value::Null; into.len()]);
+                for (name, value) in into.iter().zip(row) {
+                    variables.assign(name, value)?;
+                }
+            }
+            Statement::Insert(insert) => {
+                self.execute_insert(insert, context)?;
+            }
+            Statement::Update(update) => {
+                self.execute_update(update, context)?;
+            }
+            Statement::Delete(delete) => {
+                self.execute_delete(delete, context)?;
+            }
+            Statement::Call { name, arguments } => {
+                output.extend(self.call_procedure(&name, &arguments, context)?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Views não podem ser alteradas por INSERT, UPDATE, DELETE ou ALTER TABLE.
+    fn base_table(&self, table_name: &str) -> Result<&Table, String> {
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| format!("Tabela '{}' não existe.", table_name))?;
+        if table.view.is_some() {
+            return Err(format!("'{}' é uma view, não uma tabela.", table_name));
+        }
+        Ok(table)
+    }
+
+    // Gatilhos (tabela dona e nome) que usam a tabela ou, com `column`, essa coluna dela.
+    fn dependent_triggers(
+        &self,
+        table_name: &str,
+        column: Option<&str>,
+    ) -> Result<Vec<(&String, &String)>, String> {
+        let mut dependents = Vec::new();
+        for (owner, table) in &self.tables {
+            for trigger in &table.triggers {
+                if trigger.references(owner, table_name, column)? {
+                    dependents.push((owner, &trigger.name));
+                }
+            }
+        }
+        Ok(dependents)
+    }
+
+    // View que usa a tabela ou, com `column`, essa coluna dela.
+    fn dependent_view(&self, name: &str, column: Option<&str>) -> Option<&String> {
+        self.tables.iter().find_map(|(view_name, table)| {
+            let view = table.view.as_ref()?;
+            if !view.dependencies.iter().any(|d| d == name) {
+                return None;
+            }
+            let Some(column) = column else {
+                return Some(view_name);
+            };
+            match sql::parse(&view.query) {
+                Ok(Statement::Select(query)) if !query.uses_column(name, column) => None,
+                _ => Some(view_name),
+            }
+        })
+    }
+
+    fn execute_create_view(
+        &mut self,
+        create: CreateView,
+        context: &Context,
+    ) -> Result<String, String> {
+        let name = &create.name;
+        if self.tables.contains_key(name) {
+            return Err(format!("Tabela '{}' já existe.", name));
+        }
+        if let Some(locking) = &create.query.locking {
+            return Err(format!(
+                "{} não pode ser usado na consulta de uma view.",
+                locking.mode.keyword()
+            ));
+        }
+        if !create.query.as_of_references().is_empty() {
+            return Err("AS OF não pode ser usado na consulta de uma view.".to_string());
+        }
+        let (plan, planned_columns) = query::plan_query(&self.tables, &create.query)?;
+        let names: Vec<String> = match create.columns {
+            Some(names) if names.len() != planned_columns.len() => {
+                return Err(format!(
+                    "A consulta da view devolve {} colunas, mas foram informados {} nomes.",
+                    planned_columns.len(),
+                    names.len()
+                ))
+            }
+            Some(names) => names,
+            None => planned_columns.iter().map(|c| c.name.clone()).collect(),
+        };
+        for (i, column_name) in names.iter().enumerate() {
+            if names[..i].contains(column_name) {
+                return Err(format!(
+                    "A coluna '{}' aparece mais de uma vez na view '{}'.",
+                    column_name, name
+                ));
+            }
+        }
+        let columns = names
+            .into_iter()
+            .zip(&planned_columns)
+            .map(|(column_name, planned)| Column {
+                name: column_name,
+                data_type: planned.data_type.unwrap_or_default(),
+                is_primary_key: false,
+                foreign_key: None,
+                default: None,
+                is_not_null: false,
+            })
+            .collect();
+        let data = if create.materialized {
+            query::execute_plan(&self.tables, &plan, context)?.rows
+        } else {
+            Vec::new()
+        };
+
+        self.tables.insert(
+            name.clone(),
+            Table {
+                columns,
+                data: Arc::new(data),
+                constraints: Vec::new(),
+                triggers: Vec::new(),
+                statistics: Statistics::default(),
+                row_xmin: Arc::default(),
+                view: Some(View {
+                    query: query::expand_wildcards(&self.tables, &create.query).to_string(),
+                    materialized: create.materialized,
+                    dependencies: create.query.referenced_tables(),
+                }),
+            },
+        );
+        if create.materialized {
+            Ok(format!("View materializada '{}' criada com sucesso!", name))
+        } else {
+            Ok(format!("View '{}' criada com sucesso!", name))
+        }
+    }
+
+    fn execute_analyze(&mut self, table_name: Option<&str>) -> Result<String, String> {
+        let mut names: Vec<String> = match table_name {
+            Some(name) => {
+                let table = self
+                    .tables
+                    .get(name)
+                    .ok_or_else(|| format!("Tabela '{}' não existe.", name))?;
+                if table.view.as_ref().is_some_and(|view| !view.materialized) {
+                    return Err(format!(
+                        "'{}' é uma view; apenas tabelas e views materializadas podem ser analisadas.",
+                        name
+                    ));
+                }
+                vec![name.to_string()]
+            }
+            None => self
+                .tables
+                .iter()
+                .filter(|(_, table)| table.view.as_ref().is_none_or(|view| view.materialized))
+                .map(|(name, _)| name.clone())
+                .collect(),
+        };
+        if names.is_empty() {
+            return Ok("Nenhuma tabela para analisar.".to_string());
+        }
+        names.sort();
+        let mut output = Vec::new();
+        for name in names {
+            if let Some(table) = self.tables.get_mut(&name) {
+                statistics::analyze(table);
+                output.push(format!(
+                    "Tabela '{}' analisada: {} linhas.",
+                    name,
+                    table.data.len()
+                ));
+            }
+        }
+        Ok(output.join("\n"))
+    }
+
+    fn execute_refresh_materialized_view(
+        &mut self,
+        name: &str,
+        context: &Context,
+    ) -> Result<String, String> {
+        let query = match self.tables.get(name).and_then(|t| t.view.as_ref()) {
+            Some(view) if view.materialized => view.query.clone(),
+            _ => return Err(format!("View materializada '{}' não existe.", name)),
+        };
+        let Statement::Select(query) = sql::parse(&query)? else {
+            return Err(format!("View materializada '{}' inválida.", name));
+        };
+        let rows = query::execute_query(&self.tables, &query, context)?.rows;
+        let count = rows.len();
+        if let Some(table) = self.tables.get_mut(name) {
+            table.data = Arc::new(rows);
+            statistics::record_changes(table, count);
+        }
+        Ok(format!(
+            "View materializada '{}' atualizada: {} linhas.",
+            name, count
+        ))
+    }
+
+    fn execute_drop_view(&mut self, name: &str, materialized: bool) -> Result<String, String> {
+        let kind = if materialized {
+            "View materializada"
+        } else {
+            "View"
+        };
+        match self.tables.get(name).and_then(|t| t.view.as_ref()) {
+            Some(view) if view.materialized == materialized => {}
+            Some(view) if view.materialized => {
+                return Err(format!(
+                    "'{}' é uma view materializada; use DROP MATERIALIZED VIEW.",
+                    name
+                ))
+            }
+            Some(_) => return Err(format!("'{}' não é uma view materializada.", name)),
+            None => return Err(format!("{} '{}' não existe.", kind, name)),
+        }
+        if let Some(view) = self.dependent_view(name, None) {
+            return Err(format!(
+                "Não é possível excluir '{}': a view '{}' depende dela.",
+                name, view
+            ));
+        }
+        self.tables.remove(name);
+        Ok(format!("{} '{}' excluída com sucesso.", kind, name))
+    }
+
+    fn execute_create_sequence(
+        create: CreateSequence,
+        context: &Context,
+    ) -> Result<String, String> {
+        let sequence = Sequence::new(&create)?;
+        let mut sequences = context.sequences();
+        if sequences.contains_key(&create.name) {
+            return Err(format!("Sequência '{}' já existe.", create.name));
+        }
+        sequences.insert(create.name.clone(), sequence);
+        Ok(format!("Sequência '{}' criada com sucesso!", create.name))
+    }
+
+    fn execute_drop_sequence(&self, name: &str, context: &Context) -> Result<String, String> {
+        let mut sequences = context.sequences();
+        let sequence = sequences
+            .get(name)
+            .ok_or_else(|| format!("Sequência '{}' não existe.", name))?;
+        if let Some((table, column)) = &sequence.owned_by {
+            return Err(format!(
+                "A sequência '{}' é usada pela coluna '{}' da tabela '{}'.",
+                name, column, table
+            ));
+        }
+        sequences.remove(name);
+        Ok(format!("Sequência '{}' excluída com sucesso.", name))
+    }
+
+    // Aceita também o formato antigo, que gravava apenas o mapa de tabelas.
+    fn deserialize_stored(data: &[u8]) -> Result<StoredDatabase<'static>, serde_json::Error> {
+        serde_json::from_slice(data).or_else(|error| {
+            serde_json::from_slice(data)
+                .map(|tables| StoredDatabase {
+                    tables: Cow::Owned(tables),
+                    sequences: Cow::Owned(HashMap::new()),
+                    procedures: Cow::Owned(HashMap::new()),
+                    next_xid: 0,
+                    prepared: Cow::Owned(HashMap::new()),
+                    history_retention: None,
+                })
+                .map_err(|_| error)
+        })
+    }
+
+    pub fn create_backup(&self, db_name: &str) -> Result<String, String> {
+        let backup_dir = "backups";
+        if !Path::new(backup_dir).exists() {
+            fs::create_dir(backup_dir)
+                .map_err(|e| format!("Erro ao criar diretório de backups: {}", e))?;
+        }
+
+        let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
+        let backup_file = format!("{}/{}_{}.hxn.gz", backup_dir, db_name, timestamp);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let stored = StoredDatabase {
+            tables: Cow::Borrowed(&self.tables),
+            sequences: Cow::Borrowed(&self.sequences),
+            procedures: Cow::Borrowed(&self.procedures),
+            next_xid: self.next_xid,
+            prepared: Cow::Borrowed(&self.prepared),
+            history_retention: Some(self.history.retention),
+        };
+        let serialized_data = serde_json::to_string(&stored)
+            .map_err(|e| format!("Erro ao serializar dados: {}", e))?;
+        encoder
+            .write_all(serialized_data.as_bytes())
+            .map_err(|e| format!("Erro ao escrever backup: {}", e))?;
+        let compressed_data = encoder
+            .finish()
+            .map_err(|e| format!("Erro ao compactar backup: {}", e))?;
+
+        let mut file = File::create(&backup_file)
+            .map_err(|e| format!("Erro ao criar arquivo de backup: {}", e))?;
+        file.write_all(&compressed_data)
+            .map_err(|e| format!("Erro ao salvar backup: {}", e))?;
+
+        Ok(format!("Backup criado com sucesso: {}", backup_file))
+    }
+
+    // O banco restaurado continua cifrado com a chave do dono, que precisa abrir o arquivo
+    // atual do banco; quem chama confere também a chave de um banco já aberto.
+    pub fn restore_backup(
+        db_name: &str,
+        backup_file: &str,
+        encryption_key: &str,
+    ) -> Result<Database, String> {
+        let mut db = Database::new(format!("{}.hxn", db_name), encryption_key);
+        // O arquivo que seria substituído precisa abrir com a chave, mesmo com o banco fechado.
+        if Path::new(&db.file_path).exists() {
+            let encrypted = fs::read(&db.file_path)
+                .map_err(|e| format!("Erro ao ler o arquivo do banco de dados: {}", e))?;
+            if db.decrypt_data(&encrypted).is_none() {
+                return Err("Chave de criptografia inválida ou arquivo corrompido.".to_string());
+            }
+        }
+        if !Path::new(backup_file).exists() {
+            return Err("Arquivo de backup não encontrado.".to_string());
+        }
+
+        let mut file = File::open(backup_file)
+            .map_err(|e| format!("Erro ao abrir arquivo de backup: {}", e))?;
+        let mut compressed_data = Vec::new();
+        file.read_to_end(&mut compressed_data)
             .map_err(|e| format!("Erro ao ler arquivo de backup: {}", e))?;
 
         let mut decoder = flate2::read::GzDecoder::new(&compressed_data[..]);
-        let mut decompressed_data = String::new();
+        let mut decompressed_data = Vec::new();
         decoder
-            .read_to_string(&mut decompressed_data)
+            .read_to_end(&mut decompressed_data)
             .map_err(|e| format!("Erro ao descompactar backup: {}", e))?;
 
-        let tables: HashMap<String, Table> = serde_json::from_str(&decompressed_data)
+        let stored = Self::deserialize_stored(&decompressed_data)
             .map_err(|e| format!("Erro ao desserializar dados: {}", e))?;
-        databases.insert(
-            db_name.to_string(),
-            Database {
-                file_path: format!("{}.hxn", db_name),
-                tables,
-                encryption_key: [0u8; 32],
-                charset: "UTF-8".to_string(),
-                connected: true,
-                transaction_active: false,
-                transaction_state: None,
-            },
+        db.tables = stored.tables.into_owned();
+        db.sequences = stored.sequences.into_owned();
+        db.procedures = stored.procedures.into_owned();
+        db.next_xid = stored.next_xid.max(1);
+        db.prepared = stored.prepared.into_owned();
+        db.history = History::new(
+            stored
+                .history_retention
+                .unwrap_or(history::DEFAULT_RETENTION),
+            &db.tables,
         );
-
-        Ok(format!("Backup restaurado com sucesso para '{}'.", db_name))
+        db.lock_prepared();
+        Ok(db)
     }
 
-    fn save_database(&self) {
-        let serialized_data = serde_json::to_string(&self.tables).unwrap();
+    // Grava o estado confirmado e as transações preparadas; as transações abertas ficam
+    // apenas nas sessões. O arquivo novo é escrito ao lado do atual e renomeado sobre ele,
+    // então uma falha no meio da gravação deixa o arquivo anterior intacto.
+    fn save_database(&self) -> Result<(), String> {
+        let stored = StoredDatabase {
+            tables: Cow::Borrowed(&self.tables),
+            sequences: Cow::Borrowed(&self.sequences),
+            procedures: Cow::Borrowed(&self.procedures),
+            next_xid: self.next_xid,
+            prepared: Cow::Borrowed(&self.prepared),
+            history_retention: Some(self.history.retention),
+        };
+        let serialized_data = serde_json::to_string(&stored)
+            .map_err(|e| format!("Erro ao serializar dados: {}", e))?;
         let encrypted_data = self.encrypt_data(serialized_data.as_bytes());
-        fs::write(&self.file_path, encrypted_data).unwrap();
+
+        let path = Path::new(&self.file_path);
+        let temporary = path.with_extension("hxn.tmp");
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&temporary)?;
+            file.write_all(&encrypted_data)?;
+            file.sync_all()?;
+            fs::rename(&temporary, path)?;
+            // A renomeação só sobrevive a uma queda de energia depois do fsync do diretório.
+            #[cfg(unix)]
+            {
+                let directory = path.parent().filter(|p| !p.as_os_str().is_empty());
+                File::open(directory.unwrap_or(Path::new(".")))?.sync_all()?;
+            }
+            Ok(())
+        };
+        write().map_err(|e| {
+            let _ = fs::remove_file(&temporary);
+            format!("Erro ao gravar o arquivo do banco de dados: {}", e)
+        })
     }
 
     fn encrypt_data(&self, data: &[u8]) -> Vec<u8> {
         let cipher = Aes256Gcm::new_from_slice(&self.encryption_key).unwrap();
-        let nonce = Nonce::from_slice(b"unique_nonce_123");
-        cipher.encrypt(nonce, data).unwrap()
+        let nonce_bytes: [u8; 12] = rand::thread_rng().gen();
+        let nonce = Nonce::from_slice(&nonce_bytes);
+        let mut encrypted = nonce_bytes.to_vec();
+        encrypted.extend(cipher.encrypt(nonce, data).unwrap());
+        encrypted
+    }
+
+    fn decrypt_data(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < 12 {
+            return None;
+        }
+        let cipher = Aes256Gcm::new_from_slice(&self.encryption_key).unwrap();
+        let (nonce_bytes, ciphertext) = data.split_at(12);
+        cipher
+            .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+            .ok()
+    }
+}
+
+// Apoio aos testes dos módulos que precisam de um banco de verdade para executar SQL.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    // Banco em um arquivo temporário próprio do teste, removido ao final, com uma sessão
+    // padrão para os testes que não precisam de várias conexões.
+    pub struct TestDatabase {
+        pub db: Database,
+        pub session: Session,
+    }
+
+    impl TestDatabase {
+        pub fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "hexen_test_{}_{}.hxn",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_file(&path);
+            TestDatabase {
+                db: Database::new(path.to_string_lossy().to_string(), "chave"),
+                session: Session::default(),
+            }
+        }
+
+        pub fn reopen(&mut self) {
+            let path = self.db.file_path.clone();
+            self.db = Database::open(path, "chave").unwrap();
+            self.session = Session::default();
+        }
+
+        pub fn run(&mut self, session: &mut Session, command: &str) -> String {
+            self.db.execute_command(command, session)
+        }
+
+        // Executa na sessão padrão; o comando precisa dar certo.
+        pub fn exec(&mut self, command: &str) -> String {
+            ok(self.db.execute_command(command, &mut self.session))
+        }
+
+        // Executa na sessão padrão e devolve a mensagem de erro.
+        pub fn fail(&mut self, command: &str) -> String {
+            let output = self.db.execute_command(command, &mut self.session);
+            assert!(output.starts_with("Erro"), "{}: {}", command, output);
+            output
+        }
+
+        // Linhas do resultado de uma consulta na sessão padrão.
+        pub fn rows(&mut self, query: &str) -> Vec<String> {
+            rows(self.exec(query))
+        }
+    }
+
+    impl Drop for TestDatabase {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.db.file_path);
+        }
+    }
+
+    pub fn ok(output: String) -> String {
+        assert!(!output.starts_with("Erro"), "{}", output);
+        output
+    }
+
+    // Linhas de um resultado renderizado, sem o cabeçalho, com os valores separados por " | ".
+    pub fn rows(output: String) -> Vec<String> {
+        ok(output)
+            .lines()
+            .filter_map(|line| line.strip_prefix("| "))
+            .skip(1)
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn row_count(output: String) -> usize {
+        rows(output).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+
+    #[test]
+    fn failing_statement_changes_nothing() {
+        let mut test = TestDatabase::new("atomicity");
+        let mut session = Session::default();
+        ok(test.run(&mut session, "CREATE TABLE t (id INTEGER PRIMARY KEY)"));
+
+        let output = test.run(&mut session, "INSERT INTO t VALUES (1), (2), (1)");
+        assert!(output.starts_with("Erro"), "{}", output);
+        assert_eq!(row_count(test.run(&mut session, "SELECT * FROM t")), 0);
+
+        // Em uma transação explícita o erro desfaz o comando e aborta a transação.
+        ok(test.run(&mut session, "BEGIN"));
+        ok(test.run(&mut session, "INSERT INTO t VALUES (1)"));
+        assert!(test
+            .run(&mut session, "INSERT INTO t VALUES (2), (1)")
+            .starts_with("Erro"));
+        assert!(test
+            .run(&mut session, "SELECT * FROM t")
+            .contains("foi abortada"));
+        ok(test.run(&mut session, "ROLLBACK"));
+        assert_eq!(row_count(test.run(&mut session, "SELECT * FROM t")), 0);
+    }
+
+    #[test]
+    fn serializable_rejects_write_skew() {
+        let mut test = TestDatabase::new("write_skew");
+        let (mut a, mut b) = (Session::default(), Session::default());
+        ok(test.run(
+            &mut a,
+            "CREATE TABLE plantao (medico TEXT PRIMARY KEY, ativo BOOLEAN)",
+        ));
+        ok(test.run(
+            &mut a,
+            "INSERT INTO plantao VALUES ('ana', TRUE), ('bia', TRUE)",
+        ));
+
+        // Cada transação confere que há outro médico de plantão e sai do seu.
+        for (session, medico) in [(&mut a, "ana"), (&mut b, "bia")] {
+            ok(test.run(session, "BEGIN TRANSACTION ISOLATION LEVEL SERIALIZABLE"));
+            assert_eq!(
+                row_count(test.run(session, "SELECT * FROM plantao WHERE ativo = TRUE")),
+                2
+            );
+            ok(test.run(
+                session,
+                &format!(
+                    "UPDATE plantao SET ativo = FALSE WHERE medico = '{}'",
+                    medico
+                ),
+            ));
+        }
+        ok(test.run(&mut a, "COMMIT"));
+        let output = test.run(&mut b, "COMMIT");
+        assert!(
+            output.contains("dependências de leitura/escrita"),
+            "{}",
+            output
+        );
+        assert_eq!(
+            row_count(test.run(&mut a, "SELECT * FROM plantao WHERE ativo = TRUE")),
+            1
+        );
+    }
+
+    #[test]
+    fn deadlock_aborts_the_transaction_that_closes_the_cycle() {
+        let mut test = TestDatabase::new("deadlock");
+        let (mut a, mut b) = (Session::default(), Session::default());
+        ok(test.run(&mut a, "CREATE TABLE t (id INTEGER PRIMARY KEY, v INTEGER)"));
+        ok(test.run(&mut a, "INSERT INTO t VALUES (1, 0), (2, 0)"));
+        ok(test.run(&mut a, "BEGIN"));
+        ok(test.run(&mut b, "BEGIN"));
+        ok(test.run(&mut a, "UPDATE t SET v = 1 WHERE id = 1"));
+        ok(test.run(&mut b, "UPDATE t SET v = 2 WHERE id = 2"));
+
+        test.run(&mut a, "UPDATE t SET v = 1 WHERE id = 2");
+        let blocker = b.transaction.as_ref().unwrap().xid;
+        assert_eq!(a.waiting.take(), Some(vec![blocker]));
+
+        let output = test.run(&mut b, "UPDATE t SET v = 2 WHERE id = 1");
+        assert!(output.contains("[40P01]"), "{}", output);
+        assert!(b.waiting.is_none());
+        assert!(test.run(&mut b, "SELECT 1").contains("foi abortada"));
+
+        // Com a vítima revertida, a outra transação consegue o lock.
+        ok(test.run(&mut b, "ROLLBACK"));
+        assert!(!test.db.is_blocked(&[blocker]));
+        ok(test.run(&mut a, "UPDATE t SET v = 1 WHERE id = 2"));
+        assert!(a.waiting.is_none());
+        ok(test.run(&mut a, "COMMIT"));
+    }
+
+    #[test]
+    fn blocked_statement_does_not_consume_sequence_values() {
+        let mut test = TestDatabase::new("blocked_sequence");
+        let (mut a, mut b) = (Session::default(), Session::default());
+        ok(test.run(&mut a, "CREATE TABLE t (id INTEGER PRIMARY KEY, v INTEGER)"));
+        ok(test.run(&mut a, "CREATE SEQUENCE s"));
+        ok(test.run(&mut a, "INSERT INTO t VALUES (1, 0)"));
+        ok(test.run(&mut a, "BEGIN"));
+        ok(test.run(&mut a, "UPDATE t SET v = 1 WHERE id = 1"));
+
+        for _ in 0..3 {
+            test.run(&mut b, "UPDATE t SET v = NEXTVAL('s') WHERE id = 1");
+            assert!(b.waiting.take().is_some());
+        }
+        ok(test.run(&mut a, "COMMIT"));
+        ok(test.run(&mut b, "UPDATE t SET v = NEXTVAL('s') WHERE id = 1"));
+        assert!(ok(test.run(&mut b, "SELECT v FROM t")).contains("| 1"));
+    }
+
+    #[test]
+    fn prepared_transaction_survives_reopening_the_database() {
+        let mut test = TestDatabase::new("two_phase");
+        let mut session = Session::default();
+        ok(test.run(&mut session, "CREATE TABLE t (id INTEGER PRIMARY KEY)"));
+        ok(test.run(&mut session, "BEGIN"));
+        ok(test.run(&mut session, "INSERT INTO t VALUES (1)"));
+        ok(test.run(&mut session, "PREPARE TRANSACTION 'g1'"));
+        assert!(session.transaction.is_none());
+
+        test.reopen();
+        let mut session = Session::default();
+        assert!(ok(test.run(&mut session, "SHOW PREPARED TRANSACTIONS")).contains("g1"));
+        assert_eq!(row_count(test.run(&mut session, "SELECT * FROM t")), 0);
+
+        // As tabelas da transação preparada continuam travadas depois de reabrir.
+        ok(test.run(&mut session, "BEGIN"));
+        test.run(&mut session, "INSERT INTO t VALUES (2)");
+        assert!(session.waiting.take().is_some());
+        ok(test.run(&mut session, "ROLLBACK"));
+
+        ok(test.run(&mut session, "COMMIT PREPARED 'g1'"));
+        assert_eq!(row_count(test.run(&mut session, "SELECT * FROM t")), 1);
+        test.reopen();
+        assert_eq!(row_count(test.run(&mut session, "SELECT * FROM t")), 1);
+        assert!(!ok(test.run(&mut session, "SHOW PREPARED TRANSACTIONS")).contains("g1"));
+    }
+
+    #[test]
+    fn view_keeps_its_columns_when_the_table_gains_one() {
+        let mut test = TestDatabase::new("view_wildcard");
+        test.exec("CREATE TABLE t (a INTEGER, b INTEGER)");
+        test.exec("INSERT INTO t VALUES (1, 2)");
+        test.exec("CREATE VIEW v AS SELECT * FROM t");
+        test.exec("ALTER TABLE t ADD COLUMN c INTEGER");
+        assert_eq!(test.rows("SELECT * FROM v"), ["1 | 2"]);
+    }
+
+    #[test]
+    fn only_columns_used_by_a_view_are_protected() {
+        let mut test = TestDatabase::new("view_columns");
+        test.exec("CREATE TABLE t (a INTEGER, b INTEGER)");
+        test.exec("INSERT INTO t VALUES (1, 2)");
+        test.exec("CREATE VIEW v AS SELECT a FROM t WHERE a > 0");
+        test.fail("ALTER TABLE t ALTER COLUMN a TYPE TEXT");
+        test.fail("ALTER TABLE t RENAME COLUMN a TO x");
+        test.fail("ALTER TABLE t DROP COLUMN a");
+        test.exec("ALTER TABLE t ALTER COLUMN b TYPE TEXT");
+        test.exec("ALTER TABLE t RENAME COLUMN b TO c");
+        test.exec("ALTER TABLE t DROP COLUMN c");
+        assert_eq!(test.rows("SELECT * FROM v"), ["1"]);
+    }
+
+    #[test]
+    fn commit_that_cannot_be_written_is_rolled_back() {
+        let directory = std::env::temp_dir().join(format!("hexen_missing_{}", std::process::id()));
+        let file_path = directory.join("db.hxn").to_string_lossy().into_owned();
+        let mut db = Database::new(file_path, "chave");
+        let mut session = Session::default();
+        assert!(db
+            .execute_command("CREATE TABLE t (x INTEGER)", &mut session)
+            .starts_with("Erro"));
+        assert!(db
+            .execute_command("SELECT * FROM t", &mut session)
+            .starts_with("Erro"));
+    }
+
+    #[test]
+    fn saving_replaces_the_file_without_leaving_a_temporary_one() {
+        let mut test = TestDatabase::new("atomic_save");
+        test.exec("CREATE TABLE t (x INTEGER)");
+        test.exec("INSERT INTO t VALUES (1)");
+        assert!(!Path::new(&test.db.file_path)
+            .with_extension("hxn.tmp")
+            .exists());
+        test.reopen();
+        assert_eq!(test.rows("SELECT x FROM t"), ["1"]);
+    }
+
+    #[test]
+    fn prepared_parameters_are_numbered_and_converted_on_both_paths() {
+        let mut test = TestDatabase::new("prepared_parameters");
+        test.exec("CREATE TABLE t (id INTEGER PRIMARY KEY, nome TEXT)");
+        test.exec("INSERT INTO t VALUES (1, 'a')");
+        let error = test.fail("PREPARE q AS SELECT nome FROM t WHERE id = $2");
+        assert!(error.contains("$1 não é usado"), "{}", error);
+
+        test.exec("PREPARE q AS SELECT nome FROM t WHERE id = $1");
+        assert_eq!(test.rows("EXECUTE q ('1')"), ["a"]);
+        let error = test.fail("EXECUTE q ('x')");
+        assert!(error.contains("Parâmetro $1"), "{}", error);
+
+        let TestDatabase { db, session } = &mut test;
+        ok(db.prepare_command(session, "p", "SELECT nome FROM t WHERE id = $1"));
+        let output = db.execute_prepared(session, "p", vec![Value::Text("x".to_string())]);
+        assert_eq!(output, error);
+        let output = db.execute_prepared(session, "p", vec![Value::Text("1".to_string())]);
+        assert_eq!(rows(output), ["a"]);
+    }
+
+    #[test]
+    fn unknown_savepoint_aborts_the_transaction() {
+        let mut test = TestDatabase::new("unknown_savepoint");
+        test.exec("CREATE TABLE t (x INTEGER)");
+        test.exec("BEGIN");
+        test.exec("SAVEPOINT a");
+        test.exec("INSERT INTO t VALUES (1)");
+        test.fail("ROLLBACK TO nada");
+        assert!(test.fail("SELECT * FROM t").contains("foi abortada"));
+        // Um savepoint existente ainda desfaz o erro.
+        test.exec("ROLLBACK TO a");
+        test.fail("RELEASE nada");
+        assert!(test
+            .fail("INSERT INTO t VALUES (2)")
+            .contains("foi abortada"));
+        test.exec("ROLLBACK TO a");
+        test.exec("INSERT INTO t VALUES (3)");
+        test.exec("COMMIT");
+        assert_eq!(test.rows("SELECT x FROM t"), ["3"]);
+    }
+
+    #[test]
+    fn transactions_read_committed_by_default() {
+        let mut test = TestDatabase::new("default_isolation");
+        let (mut a, mut b) = (Session::default(), Session::default());
+        ok(test.run(&mut a, "CREATE TABLE t (x INTEGER)"));
+        ok(test.run(&mut a, "BEGIN"));
+        assert_eq!(row_count(test.run(&mut a, "SELECT * FROM t")), 0);
+        ok(test.run(&mut b, "INSERT INTO t VALUES (1)"));
+        assert_eq!(row_count(test.run(&mut a, "SELECT * FROM t")), 1);
+        ok(test.run(&mut a, "COMMIT"));
+
+        ok(test.run(&mut a, "BEGIN ISOLATION LEVEL REPEATABLE READ"));
+        assert_eq!(row_count(test.run(&mut a, "SELECT * FROM t")), 1);
+        ok(test.run(&mut b, "INSERT INTO t VALUES (2)"));
+        assert_eq!(row_count(test.run(&mut a, "SELECT * FROM t")), 1);
+        ok(test.run(&mut a, "COMMIT"));
+    }
+
+    #[test]
+    fn insert_with_column_lists_defaults_and_select() {
+        let mut test = TestDatabase::new("insert_forms");
+        test.exec(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, nome TEXT DEFAULT 'anon', nota INTEGER)",
+        );
+        test.exec("INSERT INTO t VALUES (1, 'ana', 7), (2, 'bia', NULL)");
+        test.exec("INSERT INTO t (nota, id) VALUES (5, 3)");
+        test.exec("INSERT INTO t (id) VALUES (4)");
+        assert_eq!(
+            test.rows("SELECT id, nome, nota FROM t ORDER BY id"),
+            [
+                "1 | ana | 7",
+                "2 | bia | NULL",
+                "3 | anon | 5",
+                "4 | anon | NULL"
+            ]
+        );
+        test.fail("INSERT INTO t (id, nome) VALUES (5)");
+        test.fail("INSERT INTO t (id, nada) VALUES (5, 1)");
+
+        test.exec("CREATE TABLE copia (id INTEGER, nome TEXT)");
+        test.exec("INSERT INTO copia SELECT id, nome FROM t WHERE nota IS NOT NULL");
+        assert_eq!(
+            test.rows("SELECT id, nome FROM copia ORDER BY id"),
+            ["1 | ana", "3 | anon"]
+        );
+        // Uma linha inválida desfaz o comando inteiro.
+        test.fail("INSERT INTO t VALUES (6, 'c', 1), (1, 'd', 2)");
+        assert_eq!(row_count(test.exec("SELECT * FROM t")), 4);
+    }
+
+    #[test]
+    fn insert_on_conflict() {
+        let mut test = TestDatabase::new("on_conflict");
+        test.exec("CREATE TABLE estoque (sku TEXT PRIMARY KEY, qtd INTEGER, UNIQUE (qtd))");
+        test.exec("INSERT INTO estoque VALUES ('A1', 5)");
+        test.exec("INSERT INTO estoque VALUES ('A1', 1), ('B2', 3) ON CONFLICT DO NOTHING");
+        test.exec(
+            "INSERT INTO estoque VALUES ('A1', 2), ('C3', 4) \
+             ON CONFLICT (sku) DO UPDATE SET qtd = estoque.qtd + excluded.qtd",
+        );
+        assert_eq!(
+            test.rows("SELECT sku, qtd FROM estoque ORDER BY sku"),
+            ["A1 | 7", "B2 | 3", "C3 | 4"]
+        );
+        test.exec(
+            "INSERT INTO estoque VALUES ('B2', 10) \
+             ON CONFLICT (sku) DO UPDATE SET qtd = excluded.qtd WHERE estoque.qtd > 5",
+        );
+        assert_eq!(test.rows("SELECT qtd FROM estoque WHERE sku = 'B2'"), ["3"]);
+        test.fail("INSERT INTO estoque VALUES ('A1', 1) ON CONFLICT (qtd, sku) DO NOTHING");
+        // O conflito com outra restrição ainda é um erro.
+        test.fail("INSERT INTO estoque VALUES ('D4', 7) ON CONFLICT (sku) DO NOTHING");
+    }
+
+    #[test]
+    fn returning_the_affected_rows() {
+        let mut test = TestDatabase::new("returning");
+        test.exec("CREATE TABLE t (id INTEGER PRIMARY KEY, v INTEGER)");
+        assert_eq!(
+            test.rows("INSERT INTO t VALUES (1, 10), (2, 20) RETURNING *"),
+            ["1 | 10", "2 | 20"]
+        );
+        assert_eq!(
+            test.rows("UPDATE t SET v = v + 1 WHERE id = 2 RETURNING id, v * 2 AS dobro"),
+            ["2 | 42"]
+        );
+        assert_eq!(
+            test.rows(
+                "INSERT INTO t VALUES (2, 0), (3, 30) \
+                 ON CONFLICT (id) DO UPDATE SET v = 0 RETURNING id, v"
+            ),
+            ["2 | 0", "3 | 30"]
+        );
+        assert_eq!(
+            test.rows("DELETE FROM t WHERE v < 20 RETURNING v"),
+            ["10", "0"]
+        );
+        assert_eq!(test.rows("SELECT id FROM t"), ["3"]);
+        test.fail("DELETE FROM t RETURNING nada");
+        assert_eq!(test.rows("SELECT id FROM t"), ["3"]);
+    }
+
+    #[test]
+    fn alter_table_checks_the_existing_rows() {
+        let mut test = TestDatabase::new("alter_table");
+        test.exec("CREATE TABLE t (id INTEGER, codigo TEXT)");
+        test.exec("INSERT INTO t VALUES (1, '10'), (2, NULL)");
+        test.exec("ALTER TABLE t ALTER COLUMN codigo TYPE INTEGER");
+        assert_eq!(test.rows("SELECT codigo + 1 FROM t WHERE id = 1"), ["11"]);
+        test.exec("ALTER TABLE t ALTER COLUMN codigo TYPE TEXT");
+        test.exec("INSERT INTO t VALUES (3, 'abc')");
+        test.fail("ALTER TABLE t ALTER COLUMN codigo TYPE INTEGER");
+
+        test.fail("ALTER TABLE t ALTER COLUMN codigo SET NOT NULL");
+        test.exec("ALTER TABLE t ALTER COLUMN id SET NOT NULL");
+        test.fail("INSERT INTO t (codigo) VALUES ('z')");
+        test.exec("ALTER TABLE t ALTER COLUMN codigo SET DEFAULT 'novo'");
+        test.exec("INSERT INTO t (id) VALUES (4)");
+        test.exec("ALTER TABLE t ALTER COLUMN codigo DROP DEFAULT");
+        test.exec("INSERT INTO t (id) VALUES (5)");
+        assert_eq!(
+            test.rows("SELECT id, codigo FROM t WHERE id > 3 ORDER BY id"),
+            ["4 | novo", "5 | NULL"]
+        );
+
+        test.exec("INSERT INTO t VALUES (5, 'dup')");
+        test.fail("ALTER TABLE t ADD PRIMARY KEY (id)");
+        test.exec("DELETE FROM t WHERE codigo = 'dup'");
+        test.exec("ALTER TABLE t ADD PRIMARY KEY (id)");
+        test.fail("INSERT INTO t VALUES (5, 'dup')");
+        test.exec("ALTER TABLE t DROP CONSTRAINT t_pkey");
+        test.exec("INSERT INTO t VALUES (5, 'dup')");
+        test.exec("ALTER TABLE t ADD CONSTRAINT codigo_unico UNIQUE (codigo)");
+        test.fail("INSERT INTO t VALUES (6, 'abc')");
+
+        test.exec("ALTER TABLE t RENAME COLUMN codigo TO cod");
+        test.exec("ALTER TABLE t RENAME TO u");
+        test.fail("SELECT * FROM t");
+        assert_eq!(test.rows("SELECT cod FROM u WHERE id = 3"), ["abc"]);
+        test.fail("INSERT INTO u VALUES (7, 'abc')");
+    }
+
+    #[test]
+    fn renamed_tables_and_columns_keep_foreign_keys() {
+        let mut test = TestDatabase::new("alter_foreign_keys");
+        test.exec("CREATE TABLE pai (id INTEGER PRIMARY KEY)");
+        test.exec("CREATE TABLE filho (id INTEGER, pai_id INTEGER FOREIGN KEY REFERENCES pai(id))");
+        test.exec("INSERT INTO pai VALUES (1)");
+        test.exec("INSERT INTO filho VALUES (1, 1)");
+        test.exec("ALTER TABLE pai RENAME COLUMN id TO codigo");
+        test.exec("ALTER TABLE pai RENAME TO mae");
+        test.fail("INSERT INTO filho VALUES (2, 2)");
+        test.fail("DELETE FROM mae WHERE codigo = 1");
+        test.fail("ALTER TABLE mae DROP COLUMN codigo");
+    }
+
+    #[test]
+    fn views_and_materialized_views() {
+        let mut test = TestDatabase::new("views");
+        test.exec("CREATE TABLE vendas (id INTEGER, regiao TEXT, valor INTEGER)");
+        test.exec("INSERT INTO vendas VALUES (1, 'sul', 10), (2, 'norte', 20)");
+        test.exec(
+            "CREATE VIEW sul (codigo, total) AS SELECT id, valor FROM vendas WHERE regiao = 'sul'",
+        );
+        test.exec("CREATE MATERIALIZED VIEW todas AS SELECT id FROM vendas");
+        test.exec("CREATE VIEW grandes AS SELECT codigo FROM sul WHERE total > 5");
+        test.exec("INSERT INTO vendas VALUES (3, 'sul', 30)");
+
+        assert_eq!(
+            test.rows("SELECT codigo, total FROM sul ORDER BY codigo"),
+            ["1 | 10", "3 | 30"]
+        );
+        assert_eq!(test.rows("SELECT codigo FROM grandes"), ["1", "3"]);
+        assert_eq!(test.rows("SELECT id FROM todas ORDER BY id"), ["1", "2"]);
+        test.exec("REFRESH MATERIALIZED VIEW todas");
+        assert_eq!(
+            test.rows("SELECT id FROM todas ORDER BY id"),
+            ["1", "2", "3"]
+        );
+
+        test.fail("INSERT INTO sul VALUES (4, 40)");
+        test.fail("DELETE FROM todas");
+        test.fail("DROP TABLE vendas");
+        test.fail("ALTER TABLE vendas RENAME TO v2");
+        test.fail("DROP VIEW sul");
+        test.exec("DROP VIEW grandes");
+        test.exec("DROP VIEW sul");
+        test.exec("DROP MATERIALIZED VIEW todas");
+        test.exec("DROP TABLE vendas");
+    }
+
+    #[test]
+    fn prepared_statements_belong_to_the_session() {
+        let mut test = TestDatabase::new("prepared_statements");
+        let mut other = Session::default();
+        test.exec("CREATE TABLE t (id INTEGER, nome TEXT)");
+        test.exec("PREPARE novo (INTEGER, TEXT) AS INSERT INTO t VALUES ($1, $2)");
+        test.exec("EXECUTE novo ('1', 'x''); DROP TABLE t; --')");
+        assert_eq!(test.rows("SELECT nome FROM t"), ["x'); DROP TABLE t; --"]);
+        test.exec("PREPARE busca AS SELECT id FROM t WHERE nome = ? OR id = ?");
+        assert_eq!(test.rows("EXECUTE busca ('x', 1)"), ["1"]);
+        test.fail("EXECUTE busca ('x')");
+        test.fail("PREPARE busca AS SELECT 1");
+        assert!(test
+            .run(&mut other, "EXECUTE busca ('x', 1)")
+            .starts_with("Erro"));
+
+        // O plano guardado é refeito depois de uma mudança no esquema.
+        test.exec("ALTER TABLE t RENAME COLUMN id TO codigo");
+        test.fail("EXECUTE busca ('x', 1)");
+        test.exec("ALTER TABLE t RENAME COLUMN codigo TO id");
+        assert_eq!(test.rows("EXECUTE busca ('x', 1)"), ["1"]);
+
+        test.exec("DEALLOCATE busca");
+        test.fail("EXECUTE busca ('x', 1)");
+        test.exec("DEALLOCATE ALL");
+        test.fail("EXECUTE novo (2, 'b')");
+        test.fail("PREPARE d AS DROP TABLE t");
     }
 }
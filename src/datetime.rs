@@ -0,0 +1,651 @@
+use crate::sql::BinaryOperator;
+use crate::value::{DataType, Value};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+// Valores de INTERVAL que não cabem nos campos (meses e dias em 32 bits, microssegundos em 64).
+pub const INTERVAL_OUT_OF_RANGE: &str = "Valor de INTERVAL fora do intervalo suportado.";
+
+// Campos acumulados durante a leitura do texto, convertidos no final; assim um valor grande
+// demais é detectado em vez de truncado.
+#[derive(Default)]
+struct Parts {
+    months: i128,
+    days: i128,
+    micros: i128,
+}
+
+impl Parts {
+    fn add(self, other: Parts) -> Parts {
+        Parts {
+            months: self.months.saturating_add(other.months),
+            days: self.days.saturating_add(other.days),
+            micros: self.micros.saturating_add(other.micros),
+        }
+    }
+
+    fn into_interval(self) -> Result<Interval, String> {
+        let out_of_range = |_| INTERVAL_OUT_OF_RANGE.to_string();
+        Ok(Interval {
+            months: i32::try_from(self.months).map_err(out_of_range)?,
+            days: i32::try_from(self.days).map_err(out_of_range)?,
+            micros: i64::try_from(self.micros).map_err(out_of_range)?,
+        })
+    }
+}
+
+impl Interval {
+    // Aceita o formato ISO-8601 (P1Y2M3DT4H5M6S) e o formato "1 day 02:00:00". `Ok(None)`
+    // quando o texto não é um intervalo.
+    pub fn parse(text: &str) -> Result<Option<Interval>, String> {
+        let text = text.trim();
+        let parts = match text.strip_prefix('P').or_else(|| text.strip_prefix('p')) {
+            Some(iso) => Self::parse_iso(iso),
+            None => Self::parse_words(text),
+        };
+        parts.map(Parts::into_interval).transpose()
+    }
+
+    fn parse_words(text: &str) -> Option<Parts> {
+        let mut parts = Parts::default();
+        let mut words = text.split_whitespace();
+        let mut parsed_any = false;
+        while let Some(word) = words.next() {
+            parsed_any = true;
+            if word.contains(':') {
+                parts.micros = parts.micros.saturating_add(parse_clock(word)?);
+                continue;
+            }
+            let (number, unit) = match word.find(|c: char| c.is_alphabetic()) {
+                Some(index) if index > 0 => (&word[..index], word[index..].to_string()),
+                _ => (word, words.next()?.to_string()),
+            };
+            let amount: f64 = number.parse().ok()?;
+            parts = parts.add(Self::from_unit(amount, &unit)?);
+        }
+        parsed_any.then_some(parts)
+    }
+
+    fn parse_iso(text: &str) -> Option<Parts> {
+        let mut parts = Parts::default();
+        let mut in_time = false;
+        let mut number = String::new();
+        for c in text.chars() {
+            match c {
+                'T' | 't' => in_time = true,
+                '0'..='9' | '.' | '-' => number.push(c),
+                _ => {
+                    let amount: f64 = number.parse().ok()?;
+                    number.clear();
+                    let unit = match (c.to_ascii_uppercase(), in_time) {
+                        ('Y', false) => "year",
+                        ('M', false) => "month",
+                        ('W', false) => "week",
+                        ('D', false) => "day",
+                        ('H', true) => "hour",
+                        ('M', true) => "minute",
+                        ('S', true) => "second",
+                        _ => return None,
+                    };
+                    parts = parts.add(Self::from_unit(amount, unit)?);
+                }
+            }
+        }
+        number.is_empty().then_some(parts)
+    }
+
+    // Conversões de f64 para i128 saturam, e o valor saturado é rejeitado no final.
+    fn from_unit(amount: f64, unit: &str) -> Option<Parts> {
+        let unit = unit.to_lowercase();
+        let micros = |factor: f64| Parts {
+            micros: (amount * factor) as i128,
+            ..Default::default()
+        };
+        Some(match unit.as_str() {
+            "year" | "years" | "yr" | "yrs" | "y" | "ano" | "anos" => Parts {
+                months: (amount * 12.0) as i128,
+                ..Default::default()
+            },
+            "month" | "months" | "mon" | "mons" | "mes" | "mês" | "meses" => Parts {
+                months: amount as i128,
+                ..Default::default()
+            },
+            "week" | "weeks" | "w" | "semana" | "semanas" => Parts {
+                days: (amount * 7.0) as i128,
+                ..Default::default()
+            },
+            "day" | "days" | "d" | "dia" | "dias" => Parts {
+                days: amount as i128,
+                ..Default::default()
+            },
+            "hour" | "hours" | "hr" | "hrs" | "h" | "hora" | "horas" => {
+                micros(3600.0 * MICROS_PER_SECOND as f64)
+            }
+            "minute" | "minutes" | "min" | "mins" | "m" | "minuto" | "minutos" => {
+                micros(60.0 * MICROS_PER_SECOND as f64)
+            }
+            "second" | "seconds" | "sec" | "secs" | "s" | "segundo" | "segundos" => {
+                micros(MICROS_PER_SECOND as f64)
+            }
+            "millisecond" | "milliseconds" | "ms" => micros(1000.0),
+            "microsecond" | "microseconds" | "us" => micros(1.0),
+            _ => return None,
+        })
+    }
+
+    pub fn add(&self, other: &Interval) -> Result<Interval, String> {
+        let out_of_range = || INTERVAL_OUT_OF_RANGE.to_string();
+        Ok(Interval {
+            months: self
+                .months
+                .checked_add(other.months)
+                .ok_or_else(out_of_range)?,
+            days: self.days.checked_add(other.days).ok_or_else(out_of_range)?,
+            micros: self
+                .micros
+                .checked_add(other.micros)
+                .ok_or_else(out_of_range)?,
+        })
+    }
+
+    pub fn negate(&self) -> Result<Interval, String> {
+        let out_of_range = || INTERVAL_OUT_OF_RANGE.to_string();
+        Ok(Interval {
+            months: self.months.checked_neg().ok_or_else(out_of_range)?,
+            days: self.days.checked_neg().ok_or_else(out_of_range)?,
+            micros: self.micros.checked_neg().ok_or_else(out_of_range)?,
+        })
+    }
+
+    // None quando algum campo do resultado não cabe no seu tipo.
+    pub fn multiply(&self, factor: f64) -> Option<Interval> {
+        let months = self.months as f64 * factor;
+        let days = self.days as f64 * factor + months.fract() * 30.0;
+        let micros = self.micros as f64 * factor + days.fract() * MICROS_PER_DAY as f64;
+        let fits =
+            |value: f64, min: f64, max: f64| value.is_finite() && value >= min && value <= max;
+        if !fits(months, i32::MIN as f64, i32::MAX as f64)
+            || !fits(days, i32::MIN as f64, i32::MAX as f64)
+            || !fits(micros, i64::MIN as f64, i64::MAX as f64)
+        {
+            return None;
+        }
+        Some(Interval {
+            months: months.trunc() as i32,
+            days: days.trunc() as i32,
+            micros: micros as i64,
+        })
+    }
+
+    // Comparação aproximada, considerando meses de 30 dias.
+    fn approximate_micros(&self) -> i128 {
+        (self.months as i128 * 30 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+
+    pub fn compare(&self, other: &Interval) -> Ordering {
+        self.approximate_micros().cmp(&other.approximate_micros())
+    }
+
+    pub fn to_seconds(self) -> f64 {
+        self.approximate_micros() as f64 / MICROS_PER_SECOND as f64
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: i32, singular: &str, plural: &str| {
+            format!("{} {}", n, if n.abs() == 1 { singular } else { plural })
+        };
+        let mut parts = Vec::new();
+        let (years, months) = (self.months / 12, self.months % 12);
+        if years != 0 {
+            parts.push(plural(years, "year", "years"));
+        }
+        if months != 0 {
+            parts.push(plural(months, "mon", "mons"));
+        }
+        if self.days != 0 {
+            parts.push(plural(self.days, "day", "days"));
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!(
+                "{}{}",
+                sign,
+                format_clock(self.micros.unsigned_abs())
+            ));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+fn format_clock(micros: u64) -> String {
+    let seconds = micros / MICROS_PER_SECOND as u64;
+    let fraction = micros % MICROS_PER_SECOND as u64;
+    let clock = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match fraction {
+        0 => clock,
+        _ => format!(
+            "{}.{}",
+            clock,
+            format!("{:06}", fraction).trim_end_matches('0')
+        ),
+    }
+}
+
+fn parse_clock(text: &str) -> Option<i128> {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text),
+    };
+    let mut parts = text.split(':');
+    let hours: i128 = parts.next()?.parse().ok()?;
+    let minutes: i128 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next().unwrap_or("0").parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let micros = hours
+        .checked_mul(3600)?
+        .checked_add(minutes.checked_mul(60)?)?
+        .checked_mul(MICROS_PER_SECOND as i128)?
+        .checked_add((seconds * MICROS_PER_SECOND as f64).round() as i128)?;
+    Some(sign * micros)
+}
+
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .or_else(|| parse_timestamp(text).map(|ts| ts.date()))
+}
+
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    let text = text.trim();
+    ["%H:%M:%S%.f", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(text, format).ok())
+}
+
+// Timestamps ISO-8601 com fuso horário são convertidos para UTC.
+pub fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(text) {
+        return Some(ts.naive_utc());
+    }
+    let formats = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ];
+    formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+pub fn format_time(time: &NaiveTime) -> String {
+    let micros = time.num_seconds_from_midnight() as u64 * MICROS_PER_SECOND as u64
+        + (time.nanosecond() / 1000) as u64;
+    format_clock(micros)
+}
+
+pub fn format_timestamp(ts: &NaiveDateTime) -> String {
+    format!(
+        "{} {}",
+        ts.date().format("%Y-%m-%d"),
+        format_time(&ts.time())
+    )
+}
+
+pub fn add_interval(ts: NaiveDateTime, interval: &Interval) -> Result<NaiveDateTime, String> {
+    let overflow = || "Data fora do intervalo suportado.".to_string();
+    let ts = match interval.months.cmp(&0) {
+        Ordering::Greater => ts.checked_add_months(Months::new(interval.months as u32)),
+        Ordering::Less => ts.checked_sub_months(Months::new(interval.months.unsigned_abs())),
+        Ordering::Equal => Some(ts),
+    }
+    .ok_or_else(overflow)?;
+    Duration::try_days(interval.days as i64)
+        .and_then(|days| ts.checked_add_signed(days))
+        .and_then(|ts| ts.checked_add_signed(Duration::microseconds(interval.micros)))
+        .ok_or_else(overflow)
+}
+
+pub fn add_interval_to_time(time: NaiveTime, interval: &Interval) -> NaiveTime {
+    time.overflowing_add_signed(Duration::microseconds(interval.micros))
+        .0
+}
+
+pub fn difference(left: NaiveDateTime, right: NaiveDateTime) -> Interval {
+    let micros = (left - right).num_microseconds().unwrap_or(i64::MAX);
+    Interval {
+        months: 0,
+        days: (micros / MICROS_PER_DAY) as i32,
+        micros: micros % MICROS_PER_DAY,
+    }
+}
+
+pub fn truncate(field: &str, ts: NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let date = ts.date();
+    let truncated = match field.to_lowercase().as_str() {
+        "year" => NaiveDate::from_ymd_opt(date.year(), 1, 1),
+        "quarter" => NaiveDate::from_ymd_opt(date.year(), (date.month0() / 3) * 3 + 1, 1),
+        "month" => NaiveDate::from_ymd_opt(date.year(), date.month(), 1),
+        "week" => Some(date - Duration::days(date.weekday().num_days_from_monday() as i64)),
+        "day" => Some(date),
+        "hour" => return Ok(date.and_hms_opt(ts.hour(), 0, 0).unwrap_or(ts)),
+        "minute" => return Ok(date.and_hms_opt(ts.hour(), ts.minute(), 0).unwrap_or(ts)),
+        "second" => {
+            return Ok(date
+                .and_hms_opt(ts.hour(), ts.minute(), ts.second())
+                .unwrap_or(ts))
+        }
+        other => return Err(format!("Unidade '{}' inválida para DATE_TRUNC.", other)),
+    };
+    Ok(truncated.and_then(|d| d.and_hms_opt(0, 0, 0)).unwrap_or(ts))
+}
+
+pub fn extract_from_timestamp(field: &str, ts: NaiveDateTime) -> Result<f64, String> {
+    let date = ts.date();
+    let seconds = ts.second() as f64 + ts.nanosecond() as f64 / 1e9;
+    Ok(match field.to_lowercase().as_str() {
+        "year" => date.year() as f64,
+        "quarter" => (date.month0() / 3 + 1) as f64,
+        "month" => date.month() as f64,
+        "week" => date.iso_week().week() as f64,
+        "day" => date.day() as f64,
+        "dow" => date.weekday().num_days_from_sunday() as f64,
+        "doy" => date.ordinal() as f64,
+        "hour" => ts.hour() as f64,
+        "minute" => ts.minute() as f64,
+        "second" => seconds,
+        "epoch" => ts.and_utc().timestamp_micros() as f64 / 1e6,
+        other => return Err(format!("Campo '{}' inválido para EXTRACT.", other)),
+    })
+}
+
+pub fn extract_from_interval(field: &str, interval: &Interval) -> Result<f64, String> {
+    let seconds = (interval.micros % (60 * MICROS_PER_SECOND)) as f64 / 1e6;
+    Ok(match field.to_lowercase().as_str() {
+        "year" => (interval.months / 12) as f64,
+        "month" => (interval.months % 12) as f64,
+        "day" => interval.days as f64,
+        "hour" => (interval.micros / (3600 * MICROS_PER_SECOND)) as f64,
+        "minute" => (interval.micros / (60 * MICROS_PER_SECOND) % 60) as f64,
+        "second" => seconds,
+        "epoch" => interval.to_seconds(),
+        other => {
+            return Err(format!(
+                "Campo '{}' inválido para EXTRACT de INTERVAL.",
+                other
+            ))
+        }
+    })
+}
+
+// Tipos resultantes da aritmética com datas, no estilo do PostgreSQL.
+pub fn arithmetic_type(op: BinaryOperator, left: DataType, right: DataType) -> Option<DataType> {
+    use DataType::*;
+    match (op, left, right) {
+        (BinaryOperator::Plus, Date, Integer) | (BinaryOperator::Plus, Integer, Date) => Some(Date),
+        (BinaryOperator::Minus, Date, Integer) => Some(Date),
+        (BinaryOperator::Minus, Date, Date) => Some(Integer),
+        (BinaryOperator::Plus, Date | Timestamp, Interval)
+        | (BinaryOperator::Plus, Interval, Date | Timestamp)
+        | (BinaryOperator::Minus, Date | Timestamp, Interval) => Some(Timestamp),
+        (BinaryOperator::Minus, Timestamp, Date | Timestamp)
+        | (BinaryOperator::Minus, Date, Timestamp)
+        | (BinaryOperator::Minus, Time, Time) => Some(Interval),
+        (BinaryOperator::Plus, Time, Interval)
+        | (BinaryOperator::Plus, Interval, Time)
+        | (BinaryOperator::Minus, Time, Interval) => Some(Time),
+        (BinaryOperator::Plus | BinaryOperator::Minus, Interval, Interval) => Some(Interval),
+        (BinaryOperator::Multiply, Interval, Integer | Float)
+        | (BinaryOperator::Multiply, Integer | Float, Interval)
+        | (BinaryOperator::Divide, Interval, Integer | Float) => Some(Interval),
+        _ => None,
+    }
+}
+
+pub fn arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value, String> {
+    let invalid = || {
+        format!(
+            "Operador '{}' não se aplica aos valores '{}' e '{}'.",
+            op.symbol(),
+            left,
+            right
+        )
+    };
+    let overflow = || "Data fora do intervalo suportado.".to_string();
+    match (op, left, right) {
+        (BinaryOperator::Plus, Value::Date(d), Value::Integer(n))
+        | (BinaryOperator::Plus, Value::Integer(n), Value::Date(d)) => Duration::try_days(*n)
+            .and_then(|days| d.checked_add_signed(days))
+            .map(Value::Date)
+            .ok_or_else(overflow),
+        (BinaryOperator::Minus, Value::Date(d), Value::Integer(n)) => Duration::try_days(*n)
+            .and_then(|days| d.checked_sub_signed(days))
+            .map(Value::Date)
+            .ok_or_else(overflow),
+        (BinaryOperator::Minus, Value::Date(l), Value::Date(r)) => {
+            Ok(Value::Integer((*l - *r).num_days()))
+        }
+        (BinaryOperator::Plus, ts @ (Value::Date(_) | Value::Timestamp(_)), Value::Interval(i))
+        | (BinaryOperator::Plus, Value::Interval(i), ts @ (Value::Date(_) | Value::Timestamp(_))) => {
+            add_interval(ts.as_timestamp().ok_or_else(invalid)?, i).map(Value::Timestamp)
+        }
+        (
+            BinaryOperator::Minus,
+            ts @ (Value::Date(_) | Value::Timestamp(_)),
+            Value::Interval(i),
+        ) => {
+            add_interval(ts.as_timestamp().ok_or_else(invalid)?, &i.negate()?).map(Value::Timestamp)
+        }
+        (
+            BinaryOperator::Minus,
+            l @ (Value::Date(_) | Value::Timestamp(_)),
+            r @ (Value::Date(_) | Value::Timestamp(_)),
+        ) => Ok(Value::Interval(difference(
+            l.as_timestamp().ok_or_else(invalid)?,
+            r.as_timestamp().ok_or_else(invalid)?,
+        ))),
+        (BinaryOperator::Minus, Value::Time(l), Value::Time(r)) => Ok(Value::Interval(Interval {
+            micros: (*l - *r).num_microseconds().unwrap_or(0),
+            ..Default::default()
+        })),
+        (BinaryOperator::Plus, Value::Time(t), Value::Interval(i))
+        | (BinaryOperator::Plus, Value::Interval(i), Value::Time(t)) => {
+            Ok(Value::Time(add_interval_to_time(*t, i)))
+        }
+        (BinaryOperator::Minus, Value::Time(t), Value::Interval(i)) => {
+            Ok(Value::Time(add_interval_to_time(*t, &i.negate()?)))
+        }
+        (BinaryOperator::Plus, Value::Interval(l), Value::Interval(r)) => {
+            l.add(r).map(Value::Interval)
+        }
+        (BinaryOperator::Minus, Value::Interval(l), Value::Interval(r)) => {
+            l.add(&r.negate()?).map(Value::Interval)
+        }
+        (BinaryOperator::Multiply, Value::Interval(i), n)
+        | (BinaryOperator::Multiply, n, Value::Interval(i)) => i
+            .multiply(n.as_f64().ok_or_else(invalid)?)
+            .map(Value::Interval)
+            .ok_or_else(|| INTERVAL_OUT_OF_RANGE.to_string()),
+        (BinaryOperator::Divide, Value::Interval(i), n) => match n.as_f64() {
+            Some(0.0) => Err("Divisão por zero.".to_string()),
+            Some(divisor) => i
+                .multiply(1.0 / divisor)
+                .map(Value::Interval)
+                .ok_or_else(|| INTERVAL_OUT_OF_RANGE.to_string()),
+            None => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::TestDatabase;
+
+    fn interval(text: &str) -> Interval {
+        Interval::parse(text).unwrap().unwrap()
+    }
+
+    fn out_of_range<T>() -> Result<T, String> {
+        Err(INTERVAL_OUT_OF_RANGE.to_string())
+    }
+
+    fn date(text: &str) -> Value {
+        Value::Date(parse_date(text).unwrap())
+    }
+
+    #[test]
+    fn parses_both_interval_formats() {
+        let expected = Interval {
+            months: 14,
+            days: 3,
+            micros: 4 * 3600 * MICROS_PER_SECOND
+                + 5 * 60 * MICROS_PER_SECOND
+                + 6 * MICROS_PER_SECOND,
+        };
+        assert_eq!(interval("P1Y2M3DT4H5M6S"), expected);
+        assert_eq!(interval("1 year 2 months 3 days 04:05:06"), expected);
+        assert_eq!(Interval::parse("três dias"), Ok(None));
+    }
+
+    #[test]
+    fn interval_fields_out_of_range_are_rejected() {
+        assert_eq!(Interval::parse("9999999999 days"), out_of_range());
+        assert_eq!(Interval::parse("2147483647 days 1 day"), out_of_range());
+        assert_eq!(Interval::parse("99999999999999999 hours"), out_of_range());
+        assert_eq!(Interval::parse("9999999999999:00:00"), out_of_range());
+
+        let max_days = interval("2147483647 days");
+        assert_eq!(max_days.add(&interval("1 day")), out_of_range());
+        let min_months = Interval {
+            months: i32::MIN,
+            ..Default::default()
+        };
+        assert_eq!(min_months.negate(), out_of_range());
+        assert_eq!(
+            arithmetic(
+                BinaryOperator::Multiply,
+                &Value::Interval(max_days),
+                &Value::Integer(2)
+            ),
+            out_of_range()
+        );
+    }
+
+    #[test]
+    fn date_arithmetic() {
+        assert_eq!(
+            arithmetic(
+                BinaryOperator::Plus,
+                &date("2024-02-28"),
+                &Value::Integer(2)
+            ),
+            Ok(date("2024-03-01"))
+        );
+        assert_eq!(
+            arithmetic(
+                BinaryOperator::Minus,
+                &date("2024-03-01"),
+                &date("2024-02-28")
+            ),
+            Ok(Value::Integer(2))
+        );
+        let month_end = parse_timestamp("2024-01-31 10:00:00").unwrap();
+        assert_eq!(
+            add_interval(month_end, &interval("1 month 1 hour")),
+            Ok(parse_timestamp("2024-02-29 11:00:00").unwrap())
+        );
+    }
+
+    #[test]
+    fn date_arithmetic_out_of_range_is_an_error() {
+        let error = Err("Data fora do intervalo suportado.".to_string());
+        assert_eq!(
+            arithmetic(
+                BinaryOperator::Plus,
+                &date("2024-01-01"),
+                &Value::Integer(i64::MAX)
+            ),
+            error
+        );
+        assert_eq!(
+            arithmetic(
+                BinaryOperator::Minus,
+                &date("2024-01-01"),
+                &Value::Integer(i64::MIN)
+            ),
+            error
+        );
+    }
+
+    #[test]
+    fn date_and_time_columns_in_queries() {
+        let mut test = TestDatabase::new("datetime_queries");
+        test.exec("CREATE TABLE a (d DATE, t TIME, ts TIMESTAMP, i INTERVAL)");
+        test.exec(
+            "INSERT INTO a VALUES \
+             ('2024-03-01', '13:45:00', '2024-03-01T10:00:00-03:00', '1 day 2 hours')",
+        );
+        test.fail("INSERT INTO a (d) VALUES ('2024-02-30')");
+        assert_eq!(
+            test.rows("SELECT d, t, ts, i FROM a"),
+            ["2024-03-01 | 13:45:00 | 2024-03-01 13:00:00 | 1 day 02:00:00"]
+        );
+        assert_eq!(
+            test.rows(
+                "SELECT d + 30, ts + INTERVAL '1 month', ts - TIMESTAMP '2024-02-29 00:00', \
+                 t + INTERVAL '11 hours', i * 2 FROM a"
+            ),
+            ["2024-03-31 | 2024-04-01 13:00:00 | 1 day 13:00:00 | 00:45:00 | 2 days 04:00:00"]
+        );
+        assert_eq!(
+            test.rows(
+                "SELECT EXTRACT(year FROM d), EXTRACT(dow FROM d), DATE_PART('hour', ts), \
+                 EXTRACT(epoch FROM i), DATE_TRUNC('month', ts) FROM a"
+            ),
+            ["2024 | 5 | 13 | 93600 | 2024-03-01 00:00:00"]
+        );
+        assert_eq!(
+            test.rows(
+                "SELECT DATE '2024-03-01' - DATE '2024-01-01', INTERVAL 'P1M2DT3H', \
+                 INTERVAL '90 minutos'"
+            ),
+            ["60 | 1 mon 2 days 03:00:00 | 01:30:00"]
+        );
+        assert_eq!(
+            test.rows("SELECT d FROM a WHERE d >= DATE_TRUNC('month', ts)"),
+            ["2024-03-01"]
+        );
+        test.fail("SELECT EXTRACT(nada FROM d) FROM a");
+    }
+}
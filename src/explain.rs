@@ -0,0 +1,222 @@
+use crate::query::{self, Context, OutputColumn, Plan, ResultSet};
+use crate::sql::Query;
+use crate::statistics;
+use crate::table::Table;
+use crate::value::{DataType, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Devolve o plano escolhido, uma linha por nó; com ANALYZE a consulta é executada e cada
+// nó mostra também as linhas produzidas e o tempo gasto (incluindo os nós abaixo dele).
+pub fn explain(
+    tables: &HashMap<String, Table>,
+    query: &Query,
+    analyze: bool,
+    context: &Context,
+) -> Result<ResultSet, String> {
+    let (plan, _) = query::plan_query(tables, query)?;
+    let mut total = None;
+    if analyze {
+        context.enable_profiling();
+        let start = Instant::now();
+        query::execute_plan(tables, &plan, context)?;
+        total = Some(start.elapsed());
+    }
+
+    let mut lines = Vec::new();
+    describe(tables, &plan, context, 0, &mut lines);
+    if let Some(total) = total {
+        lines.push(format!("Tempo de execução: {:.3} ms", milliseconds(total)));
+    }
+    Ok(ResultSet {
+        columns: vec![OutputColumn {
+            table: None,
+            name: "QUERY PLAN".to_string(),
+            data_type: Some(DataType::Text),
+        }],
+        rows: lines
+            .into_iter()
+            .map(|line| vec![Value::Text(line)])
+            .collect(),
+    })
+}
+
+fn describe(
+    tables: &HashMap<String, Table>,
+    plan: &Plan,
+    context: &Context,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let label = match plan {
+        Plan::SeqScan { table, alias } if alias != table => {
+            format!("Seq Scan em {} {}", table, alias)
+        }
+        Plan::SeqScan { table, .. } => format!("Seq Scan em {}", table),
+        Plan::SingleRow => "Resultado".to_string(),
+        Plan::Filter { predicate, .. } => format!("Filtro: {}", predicate),
+        Plan::Sort { keys, .. } => format!("Ordenação: {}", join(keys)),
+        Plan::Window { functions, .. } => format!("Janela: {}", join(functions)),
+        Plan::Project { columns, .. } => format!(
+            "Projeção: {}",
+            columns
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Plan::SetOperation { op, all, .. } => {
+            format!("{}{}", op.keyword(), if *all { " ALL" } else { "" })
+        }
+        Plan::Limit { limit, offset, .. } => {
+            let mut label = "Limite".to_string();
+            if let Some(limit) = limit {
+                label += &format!(" {}", limit);
+            }
+            if let Some(offset) = offset {
+                label += &format!(" OFFSET {}", offset);
+            }
+            label
+        }
+        Plan::LockRows {
+            table,
+            locking,
+            limit,
+            offset,
+            ..
+        } => {
+            let mut label = format!("Bloqueio de linhas em {}: {}", table, locking);
+            if let Some(limit) = limit {
+                label += &format!(" LIMIT {}", limit);
+            }
+            if let Some(offset) = offset {
+                label += &format!(" OFFSET {}", offset);
+            }
+            label
+        }
+        Plan::ViewScan { view, .. } => format!("View {}", view),
+    };
+
+    let prefix = match depth {
+        0 => String::new(),
+        _ => format!("{}-> ", "   ".repeat(depth - 1)),
+    };
+    let mut line = format!(
+        "{}{}  (linhas estimadas: {:.0})",
+        prefix,
+        label,
+        statistics::estimate_rows(tables, plan)
+    );
+    if let Some((rows, time)) = context.node_stats(plan) {
+        line += &format!(
+            " (linhas reais: {}, tempo: {:.3} ms)",
+            rows,
+            milliseconds(time)
+        );
+    }
+    lines.push(line);
+
+    for child in children(plan) {
+        describe(tables, child, context, depth + 1, lines);
+    }
+}
+
+fn children(plan: &Plan) -> Vec<&Plan> {
+    match plan {
+        Plan::SeqScan { .. } | Plan::SingleRow => Vec::new(),
+        Plan::Filter { input, .. }
+        | Plan::Sort { input, .. }
+        | Plan::Window { input, .. }
+        | Plan::Project { input, .. }
+        | Plan::Limit { input, .. }
+        | Plan::LockRows { input, .. }
+        | Plan::ViewScan { input, .. } => vec![input],
+        Plan::SetOperation { left, right, .. } => vec![left, right],
+    }
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    // Linhas do plano sem os tempos, que variam a cada execução.
+    fn plan(test: &mut TestDatabase, query: &str) -> Vec<String> {
+        test.rows(query)
+            .into_iter()
+            .filter(|line| !line.starts_with("Tempo de execução"))
+            .map(|line| match line.find(", tempo:") {
+                Some(position) => format!("{})", &line[..position]),
+                None => line,
+            })
+            .collect()
+    }
+
+    fn database(name: &str) -> TestDatabase {
+        let mut test = TestDatabase::new(name);
+        test.exec("CREATE TABLE t (id INTEGER, nome TEXT)");
+        test.exec("INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'c')");
+        test
+    }
+
+    #[test]
+    fn explain_shows_one_node_per_line() {
+        let mut test = database("explain_nodes");
+        assert_eq!(
+            plan(
+                &mut test,
+                "EXPLAIN SELECT nome FROM t WHERE id > 1 ORDER BY nome LIMIT 1"
+            ),
+            [
+                "Limite 1  (linhas estimadas: 1)",
+                "-> Projeção: nome  (linhas estimadas: 1)",
+                "   -> Ordenação: nome  (linhas estimadas: 1)",
+                "      -> Filtro: (id > 1)  (linhas estimadas: 1)",
+                "         -> Seq Scan em t  (linhas estimadas: 3)"
+            ]
+        );
+        assert_eq!(
+            plan(&mut test, "EXPLAIN SELECT id FROM t UNION SELECT 1"),
+            [
+                "UNION  (linhas estimadas: 4)",
+                "-> Projeção: id  (linhas estimadas: 3)",
+                "   -> Seq Scan em t  (linhas estimadas: 3)",
+                "-> Projeção: coluna1  (linhas estimadas: 1)",
+                "   -> Resultado  (linhas estimadas: 1)"
+            ]
+        );
+        test.fail("EXPLAIN SELECT nada FROM t");
+    }
+
+    #[test]
+    fn explain_analyze_runs_the_query() {
+        let mut test = database("explain_analyze");
+        test.exec("CREATE SEQUENCE s");
+        test.exec("EXPLAIN SELECT NEXTVAL('s') FROM t");
+        test.fail("SELECT CURRVAL('s')");
+
+        let lines = test.rows("EXPLAIN ANALYZE SELECT nome FROM t WHERE id > 1");
+        assert!(lines.last().unwrap().starts_with("Tempo de execução: "));
+        assert_eq!(
+            plan(&mut test, "EXPLAIN ANALYZE SELECT nome FROM t WHERE id > 1"),
+            [
+                "Projeção: nome  (linhas estimadas: 1) (linhas reais: 2)",
+                "-> Filtro: (id > 1)  (linhas estimadas: 1) (linhas reais: 2)",
+                "   -> Seq Scan em t  (linhas estimadas: 3) (linhas reais: 3)"
+            ]
+        );
+        test.exec("EXPLAIN ANALYZE SELECT NEXTVAL('s') FROM t");
+        assert_eq!(test.rows("SELECT CURRVAL('s')"), ["3"]);
+    }
+}
@@ -0,0 +1,725 @@
+use crate::datetime;
+use crate::value::{DataType, Value};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+
+type ReturnType = fn(&str, &[Option<DataType>]) -> Result<Option<DataType>, String>;
+
+pub struct ScalarFunction {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub return_type: ReturnType,
+    pub evaluate: fn(&[Value]) -> Result<Value, String>,
+}
+
+static FUNCTIONS: &[ScalarFunction] = &[
+    ScalarFunction {
+        name: "UPPER",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: text_to_text,
+        evaluate: upper,
+    },
+    ScalarFunction {
+        name: "LOWER",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: text_to_text,
+        evaluate: lower,
+    },
+    ScalarFunction {
+        name: "LENGTH",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: length_type,
+        evaluate: length,
+    },
+    ScalarFunction {
+        name: "SUBSTR",
+        min_args: 2,
+        max_args: Some(3),
+        return_type: substr_type,
+        evaluate: substr,
+    },
+    ScalarFunction {
+        name: "TRIM",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: text_to_text,
+        evaluate: trim,
+    },
+    ScalarFunction {
+        name: "LTRIM",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: text_to_text,
+        evaluate: ltrim,
+    },
+    ScalarFunction {
+        name: "RTRIM",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: text_to_text,
+        evaluate: rtrim,
+    },
+    ScalarFunction {
+        name: "REPLACE",
+        min_args: 3,
+        max_args: Some(3),
+        return_type: text_to_text,
+        evaluate: replace,
+    },
+    ScalarFunction {
+        name: "CONCAT",
+        min_args: 1,
+        max_args: None,
+        return_type: concat_type,
+        evaluate: concat,
+    },
+    ScalarFunction {
+        name: "ABS",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: numeric_same_type,
+        evaluate: abs,
+    },
+    ScalarFunction {
+        name: "ROUND",
+        min_args: 1,
+        max_args: Some(2),
+        return_type: round_type,
+        evaluate: round,
+    },
+    ScalarFunction {
+        name: "FLOOR",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: numeric_same_type,
+        evaluate: floor,
+    },
+    ScalarFunction {
+        name: "CEIL",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: numeric_same_type,
+        evaluate: ceil,
+    },
+    ScalarFunction {
+        name: "CEILING",
+        min_args: 1,
+        max_args: Some(1),
+        return_type: numeric_same_type,
+        evaluate: ceil,
+    },
+    ScalarFunction {
+        name: "MOD",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: numeric_common_type,
+        evaluate: modulo,
+    },
+    ScalarFunction {
+        name: "POWER",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: power_type,
+        evaluate: power,
+    },
+    ScalarFunction {
+        name: "COALESCE",
+        min_args: 1,
+        max_args: None,
+        return_type: common_type,
+        evaluate: coalesce,
+    },
+    ScalarFunction {
+        name: "NULLIF",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: nullif_type,
+        evaluate: nullif,
+    },
+    ScalarFunction {
+        name: "NOW",
+        min_args: 0,
+        max_args: Some(0),
+        return_type: timestamp_type,
+        evaluate: now,
+    },
+    ScalarFunction {
+        name: "CURRENT_TIMESTAMP",
+        min_args: 0,
+        max_args: Some(0),
+        return_type: timestamp_type,
+        evaluate: now,
+    },
+    ScalarFunction {
+        name: "CURRENT_DATE",
+        min_args: 0,
+        max_args: Some(0),
+        return_type: date_type,
+        evaluate: current_date,
+    },
+    ScalarFunction {
+        name: "CURRENT_TIME",
+        min_args: 0,
+        max_args: Some(0),
+        return_type: time_type,
+        evaluate: current_time,
+    },
+    ScalarFunction {
+        name: "DATE_TRUNC",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: date_trunc_type,
+        evaluate: date_trunc,
+    },
+    ScalarFunction {
+        name: "EXTRACT",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: extract_type,
+        evaluate: extract,
+    },
+    ScalarFunction {
+        name: "DATE_PART",
+        min_args: 2,
+        max_args: Some(2),
+        return_type: extract_type,
+        evaluate: extract,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static ScalarFunction> {
+    FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+pub fn check_call(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    let function = lookup(name).ok_or_else(|| format!("Função '{}' não existe.", name))?;
+    if args.len() < function.min_args || function.max_args.is_some_and(|max| args.len() > max) {
+        return Err(format!(
+            "Número de argumentos inválido para a função '{}'.",
+            name
+        ));
+    }
+    (function.return_type)(name, args)
+}
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let function = lookup(name).ok_or_else(|| format!("Função '{}' não existe.", name))?;
+    (function.evaluate)(args)
+}
+
+fn expect_arg(
+    name: &str,
+    args: &[Option<DataType>],
+    index: usize,
+    accepts: fn(DataType) -> bool,
+    expected: &str,
+) -> Result<(), String> {
+    match args.get(index) {
+        Some(Some(t)) if !accepts(*t) => Err(format!(
+            "A função '{}' espera {} no argumento {}, mas recebeu {}.",
+            name,
+            expected,
+            index + 1,
+            t
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn is_text(t: DataType) -> bool {
+    t == DataType::Text
+}
+
+fn is_numeric(t: DataType) -> bool {
+    t.is_numeric()
+}
+
+fn is_integer(t: DataType) -> bool {
+    t == DataType::Integer
+}
+
+fn text_to_text(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    for i in 0..args.len() {
+        expect_arg(name, args, i, is_text, "TEXT")?;
+    }
+    Ok(Some(DataType::Text))
+}
+
+fn length_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    text_to_text(name, args)?;
+    Ok(Some(DataType::Integer))
+}
+
+fn substr_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    expect_arg(name, args, 0, is_text, "TEXT")?;
+    expect_arg(name, args, 1, is_integer, "INTEGER")?;
+    expect_arg(name, args, 2, is_integer, "INTEGER")?;
+    Ok(Some(DataType::Text))
+}
+
+fn concat_type(_: &str, _: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    Ok(Some(DataType::Text))
+}
+
+fn numeric_same_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    expect_arg(name, args, 0, is_numeric, "um número")?;
+    Ok(args[0])
+}
+
+fn round_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    expect_arg(name, args, 1, is_integer, "INTEGER")?;
+    numeric_same_type(name, args)
+}
+
+fn numeric_common_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    for i in 0..args.len() {
+        expect_arg(name, args, i, is_numeric, "um número")?;
+    }
+    DataType::unify(args[0], args[1])
+}
+
+fn power_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    numeric_common_type(name, args)?;
+    Ok(Some(DataType::Float))
+}
+
+fn common_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    args.iter()
+        .try_fold(None, |acc, t| DataType::unify(acc, *t))
+        .map_err(|e| format!("Argumentos de '{}': {}", name, e))
+}
+
+fn nullif_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    common_type(name, args)?;
+    Ok(args[0])
+}
+
+fn is_date_or_timestamp(t: DataType) -> bool {
+    matches!(t, DataType::Date | DataType::Timestamp)
+}
+
+fn timestamp_type(_: &str, _: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    Ok(Some(DataType::Timestamp))
+}
+
+fn date_type(_: &str, _: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    Ok(Some(DataType::Date))
+}
+
+fn time_type(_: &str, _: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    Ok(Some(DataType::Time))
+}
+
+fn date_trunc_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    expect_arg(name, args, 0, is_text, "TEXT")?;
+    expect_arg(name, args, 1, is_date_or_timestamp, "DATE ou TIMESTAMP")?;
+    Ok(Some(DataType::Timestamp))
+}
+
+fn extract_type(name: &str, args: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    expect_arg(name, args, 0, is_text, "TEXT")?;
+    expect_arg(
+        name,
+        args,
+        1,
+        |t| t.is_temporal(),
+        "uma data, hora ou intervalo",
+    )?;
+    Ok(Some(DataType::Float))
+}
+
+fn text_arg(args: &[Value], index: usize) -> Option<String> {
+    match args.get(index) {
+        None | Some(Value::Null) => None,
+        Some(value) => Some(value.to_string()),
+    }
+}
+
+fn integer_arg(args: &[Value], index: usize) -> Result<Option<i64>, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_i64()
+            .map(Some)
+            .ok_or_else(|| format!("Valor '{}' não é um inteiro.", value)),
+    }
+}
+
+fn map_text(args: &[Value], f: impl Fn(&str) -> String) -> Result<Value, String> {
+    Ok(match text_arg(args, 0) {
+        Some(text) => Value::Text(f(&text)),
+        None => Value::Null,
+    })
+}
+
+fn upper(args: &[Value]) -> Result<Value, String> {
+    map_text(args, |s| s.to_uppercase())
+}
+
+fn lower(args: &[Value]) -> Result<Value, String> {
+    map_text(args, |s| s.to_lowercase())
+}
+
+fn trim(args: &[Value]) -> Result<Value, String> {
+    map_text(args, |s| s.trim().to_string())
+}
+
+fn ltrim(args: &[Value]) -> Result<Value, String> {
+    map_text(args, |s| s.trim_start().to_string())
+}
+
+fn rtrim(args: &[Value]) -> Result<Value, String> {
+    map_text(args, |s| s.trim_end().to_string())
+}
+
+fn length(args: &[Value]) -> Result<Value, String> {
+    Ok(match text_arg(args, 0) {
+        Some(text) => Value::Integer(text.chars().count() as i64),
+        None => Value::Null,
+    })
+}
+
+// SUBSTR usa posições a partir de 1, como no SQL padrão.
+fn substr(args: &[Value]) -> Result<Value, String> {
+    let (text, start) = match (text_arg(args, 0), integer_arg(args, 1)?) {
+        (Some(text), Some(start)) => (text, start),
+        _ => return Ok(Value::Null),
+    };
+    let length = match args.get(2) {
+        Some(_) => match integer_arg(args, 2)? {
+            Some(length) if length < 0 => {
+                return Err("O tamanho de SUBSTR não pode ser negativo.".to_string())
+            }
+            Some(length) => Some(length),
+            None => return Ok(Value::Null),
+        },
+        None => None,
+    };
+    let end = length.map(|l| start.saturating_add(l));
+    let chars: Vec<char> = text.chars().collect();
+    let from = start.saturating_sub(1).clamp(0, chars.len() as i64) as usize;
+    let to = end
+        .map(|e| e.saturating_sub(1).clamp(0, chars.len() as i64) as usize)
+        .unwrap_or(chars.len());
+    Ok(Value::Text(chars[from..to.max(from)].iter().collect()))
+}
+
+fn replace(args: &[Value]) -> Result<Value, String> {
+    match (text_arg(args, 0), text_arg(args, 1), text_arg(args, 2)) {
+        (Some(text), Some(from), Some(to)) if !from.is_empty() => {
+            Ok(Value::Text(text.replace(&from, &to)))
+        }
+        (Some(text), Some(_), Some(_)) => Ok(Value::Text(text)),
+        _ => Ok(Value::Null),
+    }
+}
+
+fn concat(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Text(
+        args.iter()
+            .filter(|v| !v.is_null())
+            .map(|v| v.to_string())
+            .collect(),
+    ))
+}
+
+fn abs(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Integer(i) => i
+            .checked_abs()
+            .map(Value::Integer)
+            .ok_or_else(|| "Estouro de inteiro.".to_string()),
+        Value::Null => Ok(Value::Null),
+        value => numeric(value).map(|f| Value::Float(f.abs())),
+    }
+}
+
+fn numeric(value: &Value) -> Result<f64, String> {
+    value
+        .as_f64()
+        .ok_or_else(|| format!("Valor '{}' não é numérico.", value))
+}
+
+fn round(args: &[Value]) -> Result<Value, String> {
+    let digits = match args.get(1) {
+        Some(_) => match integer_arg(args, 1)? {
+            Some(digits) => i32::try_from(digits)
+                .map_err(|_| format!("Número de casas decimais inválido em ROUND: {}.", digits))?,
+            None => return Ok(Value::Null),
+        },
+        None => 0,
+    };
+    match &args[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Integer(i) if digits >= 0 => Ok(Value::Integer(*i)),
+        // Inteiros são arredondados sem passar por f64, para não perder precisão.
+        Value::Integer(i) => {
+            let Some(scale) = 10i128.checked_pow(digits.unsigned_abs()) else {
+                return Ok(Value::Integer(0));
+            };
+            let value = *i as i128;
+            let rounded = (value + value.signum() * (scale / 2)) / scale * scale;
+            i64::try_from(rounded)
+                .map(Value::Integer)
+                .map_err(|_| "Estouro de inteiro.".to_string())
+        }
+        // Um f64 não tem mais do que 15 casas significativas, e abaixo de 10^-308 o fator
+        // deixa de ser representável.
+        value if digits > f64::DIGITS as i32 => numeric(value).map(Value::Float),
+        value if digits < f64::MIN_10_EXP => numeric(value).map(|_| Value::Float(0.0)),
+        // Valores grandes demais para o fator já não têm casas decimais a arredondar.
+        value => {
+            let value = numeric(value)?;
+            let factor = 10f64.powi(digits);
+            let scaled = value * factor;
+            match scaled.is_finite() {
+                true => Ok(Value::Float(scaled.round() / factor)),
+                false => Ok(Value::Float(value)),
+            }
+        }
+    }
+}
+
+fn floor(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        value => numeric(value).map(|f| Value::Float(f.floor())),
+    }
+}
+
+fn ceil(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        value => numeric(value).map(|f| Value::Float(f.ceil())),
+    }
+}
+
+fn modulo(args: &[Value]) -> Result<Value, String> {
+    match (&args[0], &args[1]) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (_, r) if numeric(r)? == 0.0 => Err("Divisão por zero.".to_string()),
+        (Value::Integer(l), Value::Integer(r)) => Ok(Value::Integer(l.wrapping_rem(*r))),
+        (l, r) => Ok(Value::Float(numeric(l)? % numeric(r)?)),
+    }
+}
+
+fn power(args: &[Value]) -> Result<Value, String> {
+    match (&args[0], &args[1]) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (base, exponent) => {
+            let result = numeric(base)?.powf(numeric(exponent)?);
+            if result.is_nan() {
+                return Err("Resultado de POWER indefinido.".to_string());
+            }
+            if result.is_infinite() {
+                return Err("Resultado de POWER fora do intervalo suportado.".to_string());
+            }
+            Ok(Value::Float(result))
+        }
+    }
+}
+
+fn coalesce(args: &[Value]) -> Result<Value, String> {
+    Ok(args
+        .iter()
+        .find(|v| !v.is_null())
+        .cloned()
+        .unwrap_or(Value::Null))
+}
+
+fn nullif(args: &[Value]) -> Result<Value, String> {
+    if args[0].compare(&args[1]) == Some(std::cmp::Ordering::Equal) {
+        return Ok(Value::Null);
+    }
+    Ok(args[0].clone())
+}
+
+fn now(_: &[Value]) -> Result<Value, String> {
+    Ok(Value::Timestamp(Local::now().naive_local()))
+}
+
+fn current_date(_: &[Value]) -> Result<Value, String> {
+    Ok(Value::Date(Local::now().date_naive()))
+}
+
+fn current_time(_: &[Value]) -> Result<Value, String> {
+    Ok(Value::Time(Local::now().time()))
+}
+
+fn date_trunc(args: &[Value]) -> Result<Value, String> {
+    match (text_arg(args, 0), &args[1]) {
+        (None, _) | (_, Value::Null) => Ok(Value::Null),
+        (Some(field), value) => {
+            let ts = value
+                .as_timestamp()
+                .ok_or_else(|| format!("Valor '{}' não é uma data.", value))?;
+            datetime::truncate(&field, ts).map(Value::Timestamp)
+        }
+    }
+}
+
+fn extract(args: &[Value]) -> Result<Value, String> {
+    match (text_arg(args, 0), &args[1]) {
+        (None, _) | (_, Value::Null) => Ok(Value::Null),
+        (Some(field), Value::Interval(interval)) => {
+            datetime::extract_from_interval(&field, interval).map(Value::Float)
+        }
+        (Some(field), Value::Time(time)) => {
+            let ts = NaiveDateTime::new(NaiveDate::default(), *time);
+            match field.to_lowercase().as_str() {
+                "hour" | "minute" | "second" => {}
+                _ => return Err(format!("Campo '{}' inválido para EXTRACT de TIME.", field)),
+            }
+            datetime::extract_from_timestamp(&field, ts).map(Value::Float)
+        }
+        (Some(field), value) => {
+            let ts = value
+                .as_timestamp()
+                .ok_or_else(|| format!("Valor '{}' não é uma data.", value))?;
+            datetime::extract_from_timestamp(&field, ts).map(Value::Float)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::TestDatabase;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    #[test]
+    fn text_functions() {
+        assert_eq!(call("UPPER", &[text("ação")]), Ok(text("AÇÃO")));
+        assert_eq!(call("LENGTH", &[text("ação")]), Ok(Value::Integer(4)));
+        assert_eq!(
+            call(
+                "SUBSTR",
+                &[text("abcdef"), Value::Integer(2), Value::Integer(3)]
+            ),
+            Ok(text("bcd"))
+        );
+        assert_eq!(
+            call("REPLACE", &[text("a-b-c"), text("-"), text("+")]),
+            Ok(text("a+b+c"))
+        );
+        assert_eq!(call("UPPER", &[Value::Null]), Ok(Value::Null));
+    }
+
+    #[test]
+    fn substr_with_extreme_positions() {
+        assert_eq!(
+            call("SUBSTR", &[text("abc"), Value::Integer(i64::MIN)]),
+            Ok(text("abc"))
+        );
+        assert_eq!(
+            call(
+                "SUBSTR",
+                &[
+                    text("abc"),
+                    Value::Integer(i64::MAX),
+                    Value::Integer(i64::MAX)
+                ]
+            ),
+            Ok(text(""))
+        );
+    }
+
+    #[test]
+    fn numeric_functions() {
+        assert_eq!(call("ABS", &[Value::Integer(-3)]), Ok(Value::Integer(3)));
+        assert!(call("ABS", &[Value::Integer(i64::MIN)]).is_err());
+        assert_eq!(
+            call("ROUND", &[Value::Float(2.345), Value::Integer(2)]),
+            Ok(Value::Float(2.35))
+        );
+        assert_eq!(
+            call("ROUND", &[Value::Integer(1250), Value::Integer(-2)]),
+            Ok(Value::Integer(1300))
+        );
+        assert_eq!(
+            call("ROUND", &[Value::Integer(-1250), Value::Integer(-2)]),
+            Ok(Value::Integer(-1300))
+        );
+        assert_eq!(
+            call("MOD", &[Value::Integer(7), Value::Integer(3)]),
+            Ok(Value::Integer(1))
+        );
+        assert!(call("MOD", &[Value::Integer(7), Value::Integer(0)]).is_err());
+        assert_eq!(
+            call("POWER", &[Value::Integer(2), Value::Integer(10)]),
+            Ok(Value::Float(1024.0))
+        );
+    }
+
+    #[test]
+    fn round_with_extreme_digits() {
+        let value = Value::Float(1.23456789);
+        assert_eq!(
+            call("ROUND", &[value.clone(), Value::Integer(400)]),
+            Ok(value.clone())
+        );
+        assert_eq!(
+            call("ROUND", &[value.clone(), Value::Integer(-400)]),
+            Ok(Value::Float(0.0))
+        );
+        assert_eq!(
+            call("ROUND", &[Value::Float(1e300), Value::Integer(15)]),
+            Ok(Value::Float(1e300))
+        );
+        assert!(call("ROUND", &[value, Value::Integer(i64::MAX)]).is_err());
+        assert_eq!(
+            call("ROUND", &[Value::Integer(i64::MAX), Value::Integer(-400)]),
+            Ok(Value::Integer(0))
+        );
+        assert!(call("ROUND", &[Value::Integer(i64::MAX), Value::Integer(-1)]).is_err());
+    }
+
+    #[test]
+    fn power_out_of_range_is_an_error() {
+        assert!(call("POWER", &[Value::Integer(10), Value::Integer(400)]).is_err());
+        assert!(call("POWER", &[Value::Integer(-1), Value::Float(0.5)]).is_err());
+    }
+
+    #[test]
+    fn functions_in_queries() {
+        let mut test = TestDatabase::new("functions_in_queries");
+        test.exec("CREATE TABLE c (nome TEXT, email TEXT, saldo FLOAT)");
+        test.exec("INSERT INTO c VALUES ('ana', NULL, -1.234), ('  bia', 'b@x', 10)");
+        assert_eq!(
+            test.rows(
+                "SELECT UPPER(TRIM(nome)) || ' <' || COALESCE(email, 'sem email') || '>', \
+                 CASE WHEN ROUND(saldo, 2) < 0 THEN 'devedor' ELSE 'ok' END, ROUND(saldo, 2) \
+                 FROM c ORDER BY nome DESC"
+            ),
+            ["ANA <sem email> | devedor | -1.23", "BIA <b@x> | ok | 10"]
+        );
+        assert_eq!(
+            test.rows(
+                "SELECT CASE LENGTH(nome) WHEN 3 THEN 'tres' END, NULLIF(nome, 'ana'), \
+                 CAST('12' AS INTEGER) + 1, CAST(saldo AS INTEGER), CONCAT(email, NULL, 1) \
+                 FROM c WHERE LENGTH(nome) = 3"
+            ),
+            ["tres | NULL | 13 | -1 | 1"]
+        );
+        test.fail("SELECT CAST('x' AS INTEGER)");
+        test.fail("SELECT MOD(7, 0)");
+        test.fail("SELECT NADA(1)");
+    }
+
+    #[test]
+    fn argument_types_are_checked_before_running_the_query() {
+        let mut test = TestDatabase::new("function_types");
+        test.exec("CREATE TABLE vazia (nome TEXT)");
+        let error = test.fail("SELECT ABS(nome) FROM vazia");
+        assert!(error.contains("argumento 1"), "{}", error);
+        test.fail("SELECT nome FROM vazia WHERE UPPER(1) = 'A'");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::datetime;
+use crate::table::Table;
+use chrono::{Duration, Local, NaiveDateTime};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+// Quinze minutos, quando o banco não define outra janela.
+pub const DEFAULT_RETENTION: u64 = 15 * 60 * 1000;
+
+// Estados confirmados do banco na janela de retenção, lidos pelas consultas com AS OF. Os
+// dados de cada tabela são compartilhados com o estado atual e entre as versões até serem
+// alterados, então guardar uma versão por commit é barato. O histórico fica só em memória.
+pub struct History {
+    pub retention: u64,
+    versions: VecDeque<Version>,
+}
+
+pub struct Version {
+    // Transação que produziu o estado; `None` para o estado carregado ao abrir o banco.
+    pub xid: Option<u64>,
+    pub committed_at: NaiveDateTime,
+    // Tabelas criadas, removidas ou com dados alterados em relação à versão anterior.
+    pub changed: Vec<String>,
+    pub tables: Arc<HashMap<String, Table>>,
+}
+
+impl History {
+    pub fn new(retention: u64, tables: &HashMap<String, Table>) -> Self {
+        History {
+            retention,
+            versions: VecDeque::from([Version {
+                xid: None,
+                committed_at: Local::now().naive_local(),
+                changed: Vec::new(),
+                tables: Arc::new(tables.clone()),
+            }]),
+        }
+    }
+
+    // Registra o estado depois do commit de `xid`. Sem alterações, a versão anterior é
+    // reaproveitada.
+    pub fn record(&mut self, xid: u64, tables: &HashMap<String, Table>, changed_schema: bool) {
+        let previous = self.versions.back().map(|version| version.tables.clone());
+        let mut changed = tables
+            .iter()
+            .filter(|(name, table)| {
+                previous.as_ref().is_none_or(|previous| {
+                    previous
+                        .get(*name)
+                        .is_none_or(|before| !Arc::ptr_eq(&before.data, &table.data))
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if let Some(previous) = &previous {
+            changed.extend(
+                previous
+                    .keys()
+                    .filter(|name| !tables.contains_key(*name))
+                    .cloned(),
+            );
+        }
+        changed.sort();
+        let tables = match previous {
+            Some(previous) if changed.is_empty() && !changed_schema => previous,
+            _ => Arc::new(tables.clone()),
+        };
+        self.versions.push_back(Version {
+            xid: Some(xid),
+            committed_at: Local::now().naive_local(),
+            changed,
+            tables,
+        });
+        self.prune();
+    }
+
+    pub fn set_retention(&mut self, retention: u64) {
+        self.retention = retention;
+        self.prune();
+    }
+
+    // Uma versão sai do histórico quando a seguinte já era a atual no início da janela.
+    fn prune(&mut self) {
+        let start = self.start();
+        while self.versions.len() > 1 && self.versions[1].committed_at <= start {
+            self.versions.pop_front();
+        }
+    }
+
+    // Uma retenção maior que o calendário suportado guarda todas as versões.
+    fn start(&self) -> NaiveDateTime {
+        i64::try_from(self.retention)
+            .ok()
+            .and_then(Duration::try_milliseconds)
+            .and_then(|retention| Local::now().naive_local().checked_sub_signed(retention))
+            .unwrap_or(NaiveDateTime::MIN)
+    }
+
+    // Versões ainda na janela, mesmo que nenhum commit recente tenha removido as antigas.
+    pub fn versions(&self) -> impl Iterator<Item = &Version> {
+        let start = self.start();
+        self.versions
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| {
+                self.versions
+                    .get(i + 1)
+                    .is_none_or(|next| next.committed_at > start)
+            })
+            .map(|(_, version)| version)
+    }
+
+    // Estado confirmado no instante dado.
+    pub fn at_timestamp(
+        &self,
+        timestamp: NaiveDateTime,
+    ) -> Result<&HashMap<String, Table>, String> {
+        if timestamp < self.start() {
+            return Err(format!(
+                "O instante {} está fora da janela de retenção do histórico ({} ms).",
+                datetime::format_timestamp(&timestamp),
+                self.retention
+            ));
+        }
+        self.versions
+            .iter()
+            .rev()
+            .find(|version| version.committed_at <= timestamp)
+            .map(|version| version.tables.as_ref())
+            .ok_or_else(|| {
+                format!(
+                    "Não há histórico do banco antes de {}, quando ele foi aberto.",
+                    datetime::format_timestamp(&self.versions[0].committed_at)
+                )
+            })
+    }
+
+    // Estado logo depois do commit da transação.
+    pub fn at_transaction(&self, xid: u64) -> Result<&HashMap<String, Table>, String> {
+        self.versions()
+            .find(|version| version.xid == Some(xid))
+            .map(|version| version.tables.as_ref())
+            .ok_or_else(|| {
+                format!(
+                    "A transação {} não está no histórico: ela não foi confirmada ou já saiu da janela de retenção.",
+                    xid
+                )
+            })
+    }
+}
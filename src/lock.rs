@@ -0,0 +1,283 @@
+use crate::sql::{LockWait, RowLockMode, TableLockMode};
+use crate::transaction;
+use crate::value::Value;
+use std::collections::{HashMap, HashSet};
+
+// Locks de linha e de tabela, mantidos até o fim da transação que os obteve, e o grafo de
+// espera entre as transações bloqueadas. As linhas são identificadas pelos seus valores, como
+// na combinação de alterações do MVCC.
+#[derive(Default)]
+pub struct LockManager {
+    rows: HashMap<(String, String), Vec<(u64, RowLockMode)>>,
+    tables: HashMap<String, Vec<(u64, TableLockMode)>>,
+    // Transações pelas quais cada transação bloqueada está esperando.
+    waits_for: HashMap<u64, Vec<u64>>,
+}
+
+impl LockManager {
+    // Devolve `Ok(false)` quando a linha está travada por outra transação e deve ser pulada
+    // (SKIP LOCKED).
+    pub fn lock_row(
+        &mut self,
+        xid: u64,
+        table: &str,
+        row: &[Value],
+        mode: RowLockMode,
+        wait: LockWait,
+    ) -> Result<bool, String> {
+        let key = (table.to_string(), transaction::row_key(row));
+        let holders = self.rows.entry(key).or_default();
+        let blockers = holders
+            .iter()
+            .filter(|(holder, held)| {
+                *holder != xid && (mode == RowLockMode::Update || *held == RowLockMode::Update)
+            })
+            .map(|(holder, _)| *holder)
+            .collect::<Vec<_>>();
+        if blockers.is_empty() {
+            if !holders.contains(&(xid, mode)) {
+                holders.push((xid, mode));
+            }
+            return Ok(true);
+        }
+        match wait {
+            LockWait::SkipLocked => Ok(false),
+            LockWait::NoWait => Err(format!(
+                "[55P03] Não foi possível obter o lock de uma linha da tabela '{}'.",
+                table
+            )),
+            LockWait::Wait => Err(self.wait(xid, blockers)),
+        }
+    }
+
+    pub fn lock_table(
+        &mut self,
+        xid: u64,
+        table: &str,
+        mode: TableLockMode,
+        nowait: bool,
+    ) -> Result<(), String> {
+        let holders = self.tables.entry(table.to_string()).or_default();
+        let blockers = holders
+            .iter()
+            .filter(|(holder, held)| *holder != xid && conflicts(*held, mode))
+            .map(|(holder, _)| *holder)
+            .collect::<Vec<_>>();
+        if blockers.is_empty() {
+            if !holders.contains(&(xid, mode)) {
+                holders.push((xid, mode));
+            }
+            return Ok(());
+        }
+        if nowait {
+            return Err(format!(
+                "[55P03] Não foi possível obter o lock da tabela '{}'.",
+                table
+            ));
+        }
+        Err(self.wait(xid, blockers))
+    }
+
+    // Registra a espera, a menos que ela feche um ciclo no grafo: nesse caso a transação que
+    // pediu o lock é a vítima do deadlock e recebe o erro.
+    fn wait(&mut self, xid: u64, blockers: Vec<u64>) -> String {
+        let mut visited = HashSet::new();
+        if let Some(cycle) = blockers
+            .iter()
+            .find_map(|&blocker| self.path(blocker, xid, &mut visited))
+        {
+            let description = cycle
+                .iter()
+                .map(|xid| format!("transação {}", xid))
+                .collect::<Vec<_>>()
+                .join(", que espera pela ");
+            return format!(
+                "[40P01] Deadlock detectado: a transação {} espera pela {}.",
+                xid, description
+            );
+        }
+        let message = format!(
+            "A transação {} está aguardando a transação {}.",
+            xid, blockers[0]
+        );
+        self.waits_for.insert(xid, blockers);
+        message
+    }
+
+    // Caminho de `from` até `to` seguindo o grafo de espera.
+    fn path(&self, from: u64, to: u64, visited: &mut HashSet<u64>) -> Option<Vec<u64>> {
+        if from == to {
+            return Some(vec![to]);
+        }
+        if !visited.insert(from) {
+            return None;
+        }
+        for &next in self.waits_for.get(&from)? {
+            if let Some(mut path) = self.path(next, to, visited) {
+                path.insert(0, from);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    pub fn is_waiting(&self, xid: u64) -> bool {
+        self.waits_for.contains_key(&xid)
+    }
+
+    pub fn blockers(&self, xid: u64) -> Vec<u64> {
+        self.waits_for.get(&xid).cloned().unwrap_or_default()
+    }
+
+    // Os locks só são liberados no fim da transação, então quem ainda tem algum continua ativa.
+    pub fn holds_locks(&self, xid: u64) -> bool {
+        self.rows
+            .values()
+            .any(|holders| holders.iter().any(|(holder, _)| *holder == xid))
+            || self
+                .tables
+                .values()
+                .any(|holders| holders.iter().any(|(holder, _)| *holder == xid))
+    }
+
+    pub fn stop_waiting(&mut self, xid: u64) {
+        self.waits_for.remove(&xid);
+    }
+
+    // Libera tudo o que a transação tinha, no commit ou no rollback.
+    pub fn release(&mut self, xid: u64) {
+        self.rows.retain(|_, holders| {
+            holders.retain(|(holder, _)| *holder != xid);
+            !holders.is_empty()
+        });
+        self.tables.retain(|_, holders| {
+            holders.retain(|(holder, _)| *holder != xid);
+            !holders.is_empty()
+        });
+        self.waits_for.remove(&xid);
+    }
+}
+
+fn conflicts(held: TableLockMode, requested: TableLockMode) -> bool {
+    use TableLockMode::*;
+    match (held, requested) {
+        (Exclusive, _) | (_, Exclusive) => true,
+        (RowShare, _) | (_, RowShare) => false,
+        (RowExclusive, RowExclusive) | (Share, Share) => false,
+        (RowExclusive, Share) | (Share, RowExclusive) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i64) -> Vec<Value> {
+        vec![Value::Integer(id)]
+    }
+
+    #[test]
+    fn update_locks_conflict_and_share_locks_do_not() {
+        let mut locks = LockManager::default();
+        assert_eq!(
+            locks.lock_row(1, "t", &row(1), RowLockMode::Share, LockWait::Wait),
+            Ok(true)
+        );
+        assert_eq!(
+            locks.lock_row(2, "t", &row(1), RowLockMode::Share, LockWait::Wait),
+            Ok(true)
+        );
+        assert_eq!(
+            locks.lock_row(3, "t", &row(1), RowLockMode::Update, LockWait::SkipLocked),
+            Ok(false)
+        );
+        let error = locks
+            .lock_row(3, "t", &row(1), RowLockMode::Update, LockWait::NoWait)
+            .unwrap_err();
+        assert!(error.starts_with("[55P03]"));
+        assert!(!locks.is_waiting(3));
+    }
+
+    #[test]
+    fn waiting_records_the_blockers_until_they_release() {
+        let mut locks = LockManager::default();
+        locks
+            .lock_row(1, "t", &row(1), RowLockMode::Update, LockWait::Wait)
+            .unwrap();
+        assert!(locks
+            .lock_row(2, "t", &row(1), RowLockMode::Update, LockWait::Wait)
+            .is_err());
+        assert!(locks.is_waiting(2));
+        assert_eq!(locks.blockers(2), vec![1]);
+        assert!(locks.holds_locks(1));
+
+        locks.release(1);
+        assert!(!locks.holds_locks(1));
+        locks.stop_waiting(2);
+        assert_eq!(
+            locks.lock_row(2, "t", &row(1), RowLockMode::Update, LockWait::Wait),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn closing_a_cycle_is_reported_as_a_deadlock() {
+        let mut locks = LockManager::default();
+        locks
+            .lock_row(1, "t", &row(1), RowLockMode::Update, LockWait::Wait)
+            .unwrap();
+        locks
+            .lock_row(2, "t", &row(2), RowLockMode::Update, LockWait::Wait)
+            .unwrap();
+        locks
+            .lock_table(3, "u", TableLockMode::Exclusive, false)
+            .unwrap();
+
+        // 1 espera 2 e 2 espera 3: ainda não há ciclo.
+        let error = locks
+            .lock_row(1, "t", &row(2), RowLockMode::Update, LockWait::Wait)
+            .unwrap_err();
+        assert!(!error.starts_with("[40P01]"));
+        let error = locks
+            .lock_table(2, "u", TableLockMode::Share, false)
+            .unwrap_err();
+        assert!(!error.starts_with("[40P01]"));
+
+        // 3 pedindo a linha de 1 fecharia o ciclo 3 -> 1 -> 2 -> 3.
+        let error = locks
+            .lock_row(3, "t", &row(1), RowLockMode::Update, LockWait::Wait)
+            .unwrap_err();
+        assert!(error.starts_with("[40P01]"), "{}", error);
+        assert!(!locks.is_waiting(3));
+        assert_eq!(locks.path(1, 3, &mut HashSet::new()), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn table_lock_modes() {
+        let mut locks = LockManager::default();
+        locks
+            .lock_table(1, "t", TableLockMode::RowExclusive, false)
+            .unwrap();
+        locks
+            .lock_table(2, "t", TableLockMode::RowExclusive, false)
+            .unwrap();
+        locks
+            .lock_table(3, "t", TableLockMode::RowShare, false)
+            .unwrap();
+        assert!(locks
+            .lock_table(4, "t", TableLockMode::Share, true)
+            .is_err());
+        assert!(locks
+            .lock_table(4, "t", TableLockMode::Exclusive, true)
+            .is_err());
+        // Os locks da própria transação não a bloqueiam, só os das outras.
+        assert!(locks
+            .lock_table(1, "t", TableLockMode::Exclusive, true)
+            .is_err());
+        locks.release(2);
+        locks.release(3);
+        locks
+            .lock_table(1, "t", TableLockMode::Exclusive, true)
+            .unwrap();
+    }
+}
@@ -1,8 +1,22 @@
 mod client;
 mod database;
+mod datetime;
+mod explain;
+mod functions;
+mod history;
+mod lock;
+mod procedure;
+mod query;
+mod sequence;
 mod server;
+mod session;
+mod sql;
+mod statistics;
 mod table;
-mod user_manager;
+mod transaction;
+mod trigger;
+mod value;
+mod window;
 
 use std::env;
 
@@ -20,7 +34,7 @@ async fn main() {
             server.start("127.0.0.1:8060").await;
         }
         "client" => {
-            let mut client = client::HexenClient::new("127.0.0.1:8060");
+            let client = client::HexenClient::new("127.0.0.1:8060");
             client::run_client(client);
         }
         _ => println!("Modo inválido. Use 'server' ou 'client'."),
@@ -0,0 +1,220 @@
+use crate::query::{self, Context};
+use crate::sql::{Expr, Query, Statement};
+use crate::value::{DataType, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Procedure {
+    pub name: String,
+    // Comando CREATE PROCEDURE original; o corpo é lido novamente a cada chamada.
+    pub definition: String,
+}
+
+// Variáveis e registros de FOR visíveis durante uma chamada de procedimento.
+#[derive(Default)]
+pub struct Variables {
+    values: HashMap<String, (DataType, Value)>,
+    records: HashMap<String, (Vec<String>, Vec<Value>)>,
+}
+
+impl Variables {
+    pub fn declare(&mut self, name: &str, data_type: DataType, value: Value) -> Result<(), String> {
+        let value = value
+            .cast(data_type)
+            .map_err(|e| format!("Variável '{}': {}", name, e))?;
+        self.values.insert(name.to_string(), (data_type, value));
+        Ok(())
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let (data_type, current) = self
+            .values
+            .get_mut(name)
+            .ok_or_else(|| format!("Variável '{}' não foi declarada.", name))?;
+        *current = value
+            .cast(*data_type)
+            .map_err(|e| format!("Variável '{}': {}", name, e))?;
+        Ok(())
+    }
+
+    pub fn set_record(&mut self, name: &str, columns: Vec<String>, row: Vec<Value>) {
+        self.records.insert(name.to_string(), (columns, row));
+    }
+
+    pub fn evaluate(&self, expr: &Expr, context: &Context) -> Result<Value, String> {
+        query::evaluate(&self.bind_expr(expr)?, &[], &[], context)
+    }
+
+    pub fn condition(&self, expr: &Expr, clause: &str, context: &Context) -> Result<bool, String> {
+        let bound = self.bind_expr(expr)?;
+        query::check_condition(&bound, &[], clause)?;
+        Ok(query::evaluate(&bound, &[], &[], context)?.is_true())
+    }
+
+    // Cada '%' da mensagem é trocado pelo argumento seguinte, como no RAISE do PostgreSQL.
+    pub fn format_message(
+        &self,
+        message: &Expr,
+        arguments: &[Expr],
+        context: &Context,
+    ) -> Result<String, String> {
+        let template = self.evaluate(message, context)?.to_string();
+        let mut values = arguments.iter();
+        let mut text = String::new();
+        for c in template.chars() {
+            if c == '%' {
+                if let Some(argument) = values.next() {
+                    text.push_str(&self.evaluate(argument, context)?.to_string());
+                    continue;
+                }
+            }
+            text.push(c);
+        }
+        Ok(text)
+    }
+
+    // Referências a variáveis e campos de registro viram literais; variáveis têm precedência
+    // sobre colunas com o mesmo nome.
+    pub fn bind_expr(&self, expr: &Expr) -> Result<Expr, String> {
+        let mut error = None;
+        let bound = expr.transform(&mut |e| self.bind_reference(e, &mut error));
+        error.map_or(Ok(bound), Err)
+    }
+
+    pub fn bind_query(&self, query: &Query) -> Result<Query, String> {
+        let mut error = None;
+        let bound = query.transform(&mut |e| self.bind_reference(e, &mut error));
+        error.map_or(Ok(bound), Err)
+    }
+
+    pub fn bind_statement(&self, statement: &Statement) -> Result<Statement, String> {
+        let mut error = None;
+        let bound = statement.transform(&mut |e| self.bind_reference(e, &mut error));
+        error.map_or(Ok(bound), Err)
+    }
+
+    fn bind_reference(&self, expr: &Expr, error: &mut Option<String>) -> Option<Expr> {
+        match expr {
+            Expr::Column { table: None, name } => self
+                .values
+                .get(name)
+                .map(|(_, value)| Expr::Literal(value.clone())),
+            Expr::Column {
+                table: Some(record),
+                name,
+            } => {
+                let (columns, row) = self.records.get(record)?;
+                match columns.iter().position(|c| c == name) {
+                    Some(index) => Some(Expr::Literal(row[index].clone())),
+                    None => {
+                        error.get_or_insert(format!(
+                            "O registro '{}' não tem o campo '{}'.",
+                            record, name
+                        ));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    fn database(name: &str) -> TestDatabase {
+        let mut test = TestDatabase::new(name);
+        test.exec("CREATE TABLE contas (id INTEGER PRIMARY KEY, saldo FLOAT)");
+        test.exec("INSERT INTO contas VALUES (1, 100), (2, 0)");
+        test.exec(
+            "CREATE PROCEDURE transferir(origem INTEGER, destino INTEGER, valor FLOAT) AS
+             BEGIN
+                 DECLARE disponivel FLOAT;
+                 SELECT saldo FROM contas WHERE id = origem INTO disponivel;
+                 IF disponivel IS NULL OR disponivel < valor THEN
+                     RAISE EXCEPTION 'Saldo insuficiente na conta %', origem;
+                 END IF;
+                 UPDATE contas SET saldo = saldo - valor WHERE id = origem;
+                 UPDATE contas SET saldo = saldo + valor WHERE id = destino;
+             END",
+        );
+        test
+    }
+
+    #[test]
+    fn procedure_changes_are_all_or_nothing() {
+        let mut test = database("procedure_transfer");
+        test.exec("CALL transferir(1, 2, 30)");
+        assert_eq!(
+            test.rows("SELECT id, saldo FROM contas ORDER BY id"),
+            ["1 | 70", "2 | 30"]
+        );
+        let error = test.fail("CALL transferir(2, 1, 50)");
+        assert!(error.contains("Saldo insuficiente na conta 2"), "{}", error);
+        test.fail("CALL transferir(1, 2)");
+
+        test.exec(
+            "CREATE PROCEDURE zerar() AS
+             BEGIN
+                 UPDATE contas SET saldo = 0;
+                 RAISE EXCEPTION 'desistiu';
+             END",
+        );
+        test.fail("CALL zerar()");
+        assert_eq!(
+            test.rows("SELECT id, saldo FROM contas ORDER BY id"),
+            ["1 | 70", "2 | 30"]
+        );
+        test.reopen();
+        test.exec("CALL transferir(1, 2, 70)");
+        assert_eq!(test.rows("SELECT saldo FROM contas WHERE id = 1"), ["0"]);
+        test.exec("DROP PROCEDURE transferir");
+        test.fail("CALL transferir(2, 1, 1)");
+    }
+
+    #[test]
+    fn loops_records_and_notices() {
+        let mut test = database("procedure_loops");
+        test.exec(
+            "CREATE PROCEDURE abrir(n INTEGER) AS
+             BEGIN
+                 DECLARE i INTEGER := 3;
+                 WHILE i < n + 3 LOOP
+                     INSERT INTO contas VALUES (i, i * 10);
+                     i := i + 1;
+                 END LOOP;
+             END",
+        );
+        test.exec(
+            "CREATE PROCEDURE listar() AS
+             BEGIN
+                 DECLARE total FLOAT := 0;
+                 FOR c IN SELECT id, saldo FROM contas ORDER BY id LOOP
+                     IF c.saldo = 0 THEN
+                         RAISE NOTICE 'conta % vazia', c.id;
+                     ELSIF c.saldo > 40 THEN
+                         RETURN;
+                     ELSE
+                         total := total + c.saldo;
+                     END IF;
+                 END LOOP;
+                 RAISE NOTICE 'total %', total;
+             END",
+        );
+        test.exec("CALL abrir(3)");
+        assert_eq!(
+            test.rows("SELECT id, saldo FROM contas ORDER BY id"),
+            ["1 | 100", "2 | 0", "3 | 30", "4 | 40", "5 | 50"]
+        );
+        test.exec("UPDATE contas SET saldo = 1 WHERE id = 1");
+        let output = test.exec("CALL listar()");
+        assert!(output.contains("conta 2 vazia"), "{}", output);
+        assert!(!output.contains("total"), "{}", output);
+        test.exec("DELETE FROM contas WHERE id = 5");
+        let output = test.exec("CALL listar()");
+        assert!(output.contains("total 71"), "{}", output);
+    }
+}
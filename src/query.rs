@@ -0,0 +1,1713 @@
+use crate::datetime;
+use crate::functions;
+use crate::lock::LockManager;
+use crate::sequence::{self, Sequence};
+use crate::sql::{
+    self, Assignment, BinaryOperator, ConflictAction, Expr, FunctionCall, InsertSource, LockClause,
+    LockWait, OnConflict, OrderByItem, Query, RowLockMode, Select, SelectItem, SetExpr,
+    SetOperator, Statement, TableLockMode, UnaryOperator,
+};
+use crate::statistics;
+use crate::table::Table;
+use crate::value::{DataType, Value};
+use crate::window;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct OutputColumn {
+    pub table: Option<String>,
+    pub name: String,
+    pub data_type: Option<DataType>,
+}
+
+// Estado que as expressões podem alterar durante a execução de um comando. As sequências
+// ficam aqui porque `nextval` e `setval` não seguem as transações.
+#[derive(Default)]
+pub struct Context {
+    sequences: RefCell<HashMap<String, Sequence>>,
+    sequences_changed: Cell<bool>,
+    parameters: Vec<Value>,
+    // Linhas e tempo de cada nó executado, coletados apenas no EXPLAIN ANALYZE.
+    profile: RefCell<Option<HashMap<usize, (usize, Duration)>>>,
+    // Leituras feitas pelo comando, registradas apenas em transações SERIALIZABLE.
+    reads: RefCell<Option<Vec<TableRead>>>,
+    // Locks do banco, emprestados ao comando junto com a transação que o executa.
+    locks: RefCell<Option<(u64, LockManager)>>,
+    // statement_timeout: quando o comando deve ser cancelado e o limite em milissegundos.
+    deadline: Option<(Instant, u64)>,
+}
+
+// Linhas de uma tabela lidas por um comando: todas, ou apenas as que satisfazem o predicado.
+pub struct TableRead {
+    pub table: String,
+    pub alias: String,
+    pub predicate: Option<Expr>,
+}
+
+impl Context {
+    pub fn new(sequences: HashMap<String, Sequence>) -> Self {
+        Context {
+            sequences: RefCell::new(sequences),
+            sequences_changed: Cell::new(false),
+            parameters: Vec::new(),
+            profile: RefCell::new(None),
+            reads: RefCell::new(None),
+            locks: RefCell::new(None),
+            deadline: None,
+        }
+    }
+
+    pub fn enable_profiling(&self) {
+        *self.profile.borrow_mut() = Some(HashMap::new());
+    }
+
+    pub fn node_stats(&self, plan: &Plan) -> Option<(usize, Duration)> {
+        self.profile
+            .borrow()
+            .as_ref()?
+            .get(&node_key(plan))
+            .copied()
+    }
+
+    // Valores de $1, $2, ... de um comando preparado.
+    pub fn with_parameters(mut self, parameters: Vec<Value>) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    // Passa a registrar leituras, acrescentando às já feitas pela transação.
+    pub fn with_reads(self, reads: Vec<TableRead>) -> Self {
+        *self.reads.borrow_mut() = Some(reads);
+        self
+    }
+
+    pub fn record_read(&self, table: &str, alias: &str, predicate: Option<&Expr>) {
+        let mut reads = self.reads.borrow_mut();
+        let Some(reads) = reads.as_mut() else {
+            return;
+        };
+        // Os parâmetros são substituídos para que o predicado possa ser avaliado no commit.
+        let predicate = predicate.map(|predicate| {
+            predicate.transform(&mut |e| match e {
+                Expr::Parameter(index) => {
+                    Some(Expr::Literal(self.parameter(*index).unwrap_or(Value::Null)))
+                }
+                _ => None,
+            })
+        });
+        let key = format!("{:?}", predicate);
+        if !reads
+            .iter()
+            .any(|r| r.table == table && r.alias == alias && format!("{:?}", r.predicate) == key)
+        {
+            reads.push(TableRead {
+                table: table.to_string(),
+                alias: alias.to_string(),
+                predicate,
+            });
+        }
+    }
+
+    pub fn take_reads(&self) -> Vec<TableRead> {
+        self.reads.borrow_mut().take().unwrap_or_default()
+    }
+
+    pub fn with_deadline(mut self, deadline: Option<(Instant, u64)>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    // Verificado entre os nós do plano e a cada bloco dos procedimentos.
+    pub fn check_deadline(&self) -> Result<(), String> {
+        match self.deadline {
+            Some((deadline, milliseconds)) if Instant::now() >= deadline => {
+                Err(statement_timeout_error(milliseconds))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn with_locks(self, xid: u64, locks: LockManager) -> Self {
+        *self.locks.borrow_mut() = Some((xid, locks));
+        self
+    }
+
+    pub fn take_locks(&self) -> Option<LockManager> {
+        self.locks.borrow_mut().take().map(|(_, locks)| locks)
+    }
+
+    // Sem transação (como no EXPLAIN ANALYZE) não há o que travar.
+    pub fn lock_row(
+        &self,
+        table: &str,
+        row: &[Value],
+        mode: RowLockMode,
+        wait: LockWait,
+    ) -> Result<bool, String> {
+        match self.locks.borrow_mut().as_mut() {
+            Some((xid, locks)) => locks.lock_row(*xid, table, row, mode, wait),
+            None => Ok(true),
+        }
+    }
+
+    pub fn lock_table(&self, table: &str, mode: TableLockMode, nowait: bool) -> Result<(), String> {
+        match self.locks.borrow_mut().as_mut() {
+            Some((xid, locks)) => locks.lock_table(*xid, table, mode, nowait),
+            None => Ok(()),
+        }
+    }
+
+    fn parameter(&self, index: usize) -> Result<Value, String> {
+        self.parameters
+            .get(index - 1)
+            .cloned()
+            .ok_or_else(|| format!("Parâmetro ${} sem valor.", index))
+    }
+
+    pub fn sequences(&self) -> std::cell::RefMut<'_, HashMap<String, Sequence>> {
+        self.sequences_changed.set(true);
+        self.sequences.borrow_mut()
+    }
+
+    // Devolve as sequências e indica se alguma foi alterada.
+    pub fn finish(self) -> (HashMap<String, Sequence>, bool) {
+        (self.sequences.into_inner(), self.sequences_changed.get())
+    }
+
+    fn call_sequence_function(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        let sequence_name = match args.first() {
+            Some(Value::Null) | None => return Ok(Value::Null),
+            Some(value) => value.to_string(),
+        };
+        let mut sequences = self.sequences();
+        let sequence = sequences
+            .get_mut(&sequence_name)
+            .ok_or_else(|| format!("Sequência '{}' não existe.", sequence_name))?;
+        sequence::call(name, args, sequence, &sequence_name)
+    }
+}
+
+pub fn statement_timeout_error(milliseconds: u64) -> String {
+    format!(
+        "[57014] O comando excedeu o tempo limite de {} ms (statement_timeout).",
+        milliseconds
+    )
+}
+
+pub struct ResultSet {
+    pub columns: Vec<OutputColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    pub fn render(&self) -> String {
+        let header: Vec<String> = self.columns.iter().map(|c| c.name.clone()).collect();
+        let separator = format!(
+            "+{}\n",
+            "-".repeat(
+                (header.iter().map(|h| h.len() + 2).sum::<usize>() + header.len())
+                    .saturating_sub(1)
+            )
+        );
+        let mut result = separator.clone();
+        result += &format!("| {}\n", header.join(" | "));
+        result += &separator;
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            result += &format!("| {}\n", values.join(" | "));
+        }
+        result += &separator;
+        result
+    }
+}
+
+pub enum Plan {
+    SeqScan {
+        table: String,
+        alias: String,
+    },
+    SingleRow,
+    Filter {
+        input: Box<Plan>,
+        predicate: Expr,
+    },
+    Sort {
+        input: Box<Plan>,
+        keys: Vec<OrderByItem>,
+    },
+    Window {
+        input: Box<Plan>,
+        functions: Vec<FunctionCall>,
+        columns: Vec<OutputColumn>,
+    },
+    Project {
+        input: Box<Plan>,
+        exprs: Vec<Expr>,
+        columns: Vec<OutputColumn>,
+    },
+    SetOperation {
+        op: SetOperator,
+        all: bool,
+        left: Box<Plan>,
+        right: Box<Plan>,
+        columns: Vec<OutputColumn>,
+    },
+    Limit {
+        input: Box<Plan>,
+        limit: Option<Expr>,
+        offset: Option<Expr>,
+    },
+    // FOR UPDATE / FOR SHARE: trava as linhas da tabela antes da projeção. LIMIT e OFFSET
+    // ficam aqui para contar apenas as linhas travadas, sem as puladas por SKIP LOCKED.
+    LockRows {
+        input: Box<Plan>,
+        table: String,
+        locking: LockClause,
+        limit: Option<Expr>,
+        offset: Option<Expr>,
+    },
+    ViewScan {
+        view: String,
+        input: Box<Plan>,
+        columns: Vec<OutputColumn>,
+    },
+}
+
+// Nós são identificados pelo endereço, que não muda enquanto o plano existe.
+fn node_key(plan: &Plan) -> usize {
+    plan as *const Plan as usize
+}
+
+pub fn execute_query(
+    tables: &HashMap<String, Table>,
+    query: &Query,
+    context: &Context,
+) -> Result<ResultSet, String> {
+    let (plan, _) = plan_query(tables, query)?;
+    execute_plan(tables, &plan, context)
+}
+
+pub fn plan_query(
+    tables: &HashMap<String, Table>,
+    query: &Query,
+) -> Result<(Plan, Vec<OutputColumn>), String> {
+    let (mut plan, columns) = match &query.body {
+        SetExpr::Select(select) => plan_select(tables, select, &query.order_by)?,
+        body => {
+            let (plan, columns) = plan_set_expr(tables, body)?;
+            if query.order_by.is_empty() {
+                (plan, columns)
+            } else {
+                let keys = query
+                    .order_by
+                    .iter()
+                    .map(|item| {
+                        let expr = resolve_output_position(&item.expr, &columns)?;
+                        infer_type(&expr, &columns)?;
+                        Ok(OrderByItem {
+                            expr,
+                            descending: item.descending,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                (
+                    Plan::Sort {
+                        input: Box::new(plan),
+                        keys,
+                    },
+                    columns,
+                )
+            }
+        }
+    };
+
+    // LIMIT e OFFSET são avaliados na execução, pois podem vir de parâmetros.
+    for expr in query.limit.iter().chain(&query.offset) {
+        infer_type(expr, &[])?;
+    }
+    if let Some(locking) = query.locking {
+        plan = plan_locking(tables, query, plan, locking)?;
+    } else if query.limit.is_some() || query.offset.is_some() {
+        plan = Plan::Limit {
+            input: Box::new(plan),
+            limit: query.limit.clone(),
+            offset: query.offset.clone(),
+        };
+    }
+    Ok((plan, columns))
+}
+
+fn plan_locking(
+    tables: &HashMap<String, Table>,
+    query: &Query,
+    plan: Plan,
+    locking: LockClause,
+) -> Result<Plan, String> {
+    let keyword = locking.mode.keyword();
+    let SetExpr::Select(select) = &query.body else {
+        return Err(format!(
+            "{} não pode ser usado com UNION, INTERSECT ou EXCEPT.",
+            keyword
+        ));
+    };
+    let Some(reference) = &select.from else {
+        return Err(format!("{} exige uma tabela no FROM.", keyword));
+    };
+    if tables
+        .get(&reference.name)
+        .is_some_and(|table| table.view.is_some())
+    {
+        return Err(format!("{} não pode ser usado com views.", keyword));
+    }
+    if reference.as_of.is_some() {
+        return Err(format!("{} não pode ser usado com AS OF.", keyword));
+    }
+    let Plan::Project {
+        input,
+        exprs,
+        columns,
+    } = plan
+    else {
+        return Err(format!("{} não pode ser usado nesta consulta.", keyword));
+    };
+    let mut node = input.as_ref();
+    while let Plan::Sort { input, .. } | Plan::Filter { input, .. } = node {
+        node = input;
+    }
+    if !matches!(node, Plan::SeqScan { .. }) {
+        return Err(format!(
+            "{} não pode ser usado com funções de janela.",
+            keyword
+        ));
+    }
+    Ok(Plan::Project {
+        input: Box::new(Plan::LockRows {
+            input,
+            table: reference.name.clone(),
+            locking,
+            limit: query.limit.clone(),
+            offset: query.offset.clone(),
+        }),
+        exprs,
+        columns,
+    })
+}
+
+fn constant_count(expr: &Expr, clause: &str, context: &Context) -> Result<usize, String> {
+    match evaluate(expr, &[], &[], context)? {
+        Value::Integer(n) if n >= 0 => Ok(n as usize),
+        other => Err(format!("Valor inválido para {}: {}.", clause, other)),
+    }
+}
+
+fn plan_set_expr(
+    tables: &HashMap<String, Table>,
+    body: &SetExpr,
+) -> Result<(Plan, Vec<OutputColumn>), String> {
+    match body {
+        SetExpr::Select(select) => plan_select(tables, select, &[]),
+        SetExpr::SetOperation {
+            op,
+            all,
+            left,
+            right,
+        } => {
+            let (left, left_columns) = plan_set_expr(tables, left)?;
+            let (right, right_columns) = plan_set_expr(tables, right)?;
+            if left_columns.len() != right_columns.len() {
+                return Err(format!(
+                    "Os comandos de {} devem ter o mesmo número de colunas ({} e {}).",
+                    op.keyword(),
+                    left_columns.len(),
+                    right_columns.len()
+                ));
+            }
+            let columns = left_columns
+                .iter()
+                .zip(&right_columns)
+                .enumerate()
+                .map(|(i, (l, r))| {
+                    let data_type = DataType::unify(l.data_type, r.data_type)
+                        .map_err(|e| format!("Coluna {} do {}: {}", i + 1, op.keyword(), e))?;
+                    Ok(OutputColumn {
+                        table: None,
+                        name: l.name.clone(),
+                        data_type,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok((
+                Plan::SetOperation {
+                    op: *op,
+                    all: *all,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    columns: columns.clone(),
+                },
+                columns,
+            ))
+        }
+    }
+}
+
+fn plan_select(
+    tables: &HashMap<String, Table>,
+    select: &Select,
+    order_by: &[OrderByItem],
+) -> Result<(Plan, Vec<OutputColumn>), String> {
+    let (mut plan, mut input_columns) = match &select.from {
+        Some(reference) => {
+            let table = tables
+                .get(&reference.key())
+                .ok_or_else(|| format!("Tabela '{}' não existe.", reference.name))?;
+            let alias = reference.alias.clone().unwrap_or(reference.name.clone());
+            match &table.view {
+                Some(view) if !view.materialized => {
+                    plan_view(tables, &reference.name, table, &view.query, &alias)?
+                }
+                _ => {
+                    let columns = table_columns(table, &alias);
+                    (
+                        Plan::SeqScan {
+                            table: reference.key(),
+                            alias,
+                        },
+                        columns,
+                    )
+                }
+            }
+        }
+        None => (Plan::SingleRow, Vec::new()),
+    };
+
+    if let Some(predicate) = &select.selection {
+        check_condition(predicate, &input_columns, "WHERE")?;
+        // Com a tabela analisada, as condições mais seletivas são testadas primeiro.
+        let analysis = match &plan {
+            Plan::SeqScan { table, .. } => tables
+                .get(table)
+                .and_then(|t| t.statistics.analysis.as_ref()),
+            _ => None,
+        };
+        plan = Plan::Filter {
+            input: Box::new(plan),
+            predicate: match analysis {
+                Some(analysis) => statistics::order_conjuncts(predicate, analysis),
+                None => predicate.clone(),
+            },
+        };
+    }
+
+    let (exprs, names, aliases) = expand_projection(&select.projection, &input_columns)?;
+
+    let mut keys = order_by
+        .iter()
+        .map(|item| {
+            Ok(OrderByItem {
+                expr: resolve_order_by_alias(&item.expr, &exprs, &aliases)?,
+                descending: item.descending,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Funções de janela são calculadas antes da ordenação e da projeção e
+    // referenciadas por colunas internas.
+    let mut window_calls: Vec<FunctionCall> = Vec::new();
+    let mut extract = |expr: &Expr| {
+        expr.transform(&mut |e| match e {
+            Expr::Function(call) if call.over.is_some() => {
+                let key = format!("{:?}", call);
+                let index = match window_calls.iter().position(|c| format!("{:?}", c) == key) {
+                    Some(index) => index,
+                    None => {
+                        window_calls.push(call.clone());
+                        window_calls.len() - 1
+                    }
+                };
+                Some(Expr::Column {
+                    table: None,
+                    name: format!("#janela{}", index),
+                })
+            }
+            _ => None,
+        })
+    };
+    let exprs: Vec<Expr> = exprs.iter().map(&mut extract).collect();
+    for key in &mut keys {
+        key.expr = extract(&key.expr);
+    }
+
+    if !window_calls.is_empty() {
+        let mut columns = input_columns.clone();
+        for (i, call) in window_calls.iter().enumerate() {
+            columns.push(OutputColumn {
+                table: None,
+                name: format!("#janela{}", i),
+                data_type: window::infer_window_type(call, &input_columns)?,
+            });
+        }
+        plan = Plan::Window {
+            input: Box::new(plan),
+            functions: window_calls,
+            columns: columns.clone(),
+        };
+        input_columns = columns;
+    }
+
+    let mut output_columns = Vec::with_capacity(exprs.len());
+    for (expr, name) in exprs.iter().zip(names) {
+        output_columns.push(OutputColumn {
+            table: match expr {
+                Expr::Column { table, .. } if !name.starts_with('#') => table.clone(),
+                _ => None,
+            },
+            name,
+            data_type: infer_type(expr, &input_columns)?,
+        });
+    }
+
+    if !keys.is_empty() {
+        for key in &keys {
+            infer_type(&key.expr, &input_columns)?;
+        }
+        plan = Plan::Sort {
+            input: Box::new(plan),
+            keys,
+        };
+    }
+
+    Ok((
+        Plan::Project {
+            input: Box::new(plan),
+            exprs,
+            columns: output_columns.clone(),
+        },
+        output_columns,
+    ))
+}
+
+fn table_columns(table: &Table, alias: &str) -> Vec<OutputColumn> {
+    table
+        .columns
+        .iter()
+        .map(|c| OutputColumn {
+            table: Some(alias.to_string()),
+            name: c.name.clone(),
+            data_type: Some(c.data_type),
+        })
+        .collect()
+}
+
+// Tipos dos parâmetros sem tipo declarado, deduzidos como no PostgreSQL: um parâmetro
+// comparado a uma expressão de tipo conhecido, ou gravado em uma coluna, recebe o tipo dela.
+// Os demais ficam sem tipo.
+pub fn infer_parameter_types(
+    tables: &HashMap<String, Table>,
+    statement: &Statement,
+    count: usize,
+) -> Vec<Option<DataType>> {
+    let mut types = vec![None; count];
+    match statement {
+        Statement::Select(query) => infer_in_set_expr(tables, &query.body, &mut types),
+        Statement::Insert(insert) => {
+            let Some(table) = tables.get(&insert.table) else {
+                return types;
+            };
+            let targets: Vec<Option<DataType>> = match &insert.columns {
+                Some(names) => names
+                    .iter()
+                    .map(|name| table.column_index(name).map(|i| table.columns[i].data_type))
+                    .collect(),
+                None => table.columns.iter().map(|c| Some(c.data_type)).collect(),
+            };
+            match &insert.source {
+                InsertSource::Values(rows) => {
+                    for row in rows {
+                        for (expr, data_type) in row.iter().zip(&targets) {
+                            set_parameter_type(expr, *data_type, &mut types);
+                        }
+                    }
+                }
+                InsertSource::Query(query) => infer_in_set_expr(tables, &query.body, &mut types),
+            }
+            if let Some(OnConflict {
+                action:
+                    ConflictAction::DoUpdate {
+                        assignments,
+                        selection,
+                    },
+                ..
+            }) = &insert.on_conflict
+            {
+                let columns = table_columns(table, insert.alias.as_ref().unwrap_or(&insert.table));
+                infer_in_assignments(table, &columns, assignments, &mut types);
+                if let Some(selection) = selection {
+                    infer_in_expr(selection, &columns, &mut types);
+                }
+            }
+        }
+        Statement::Update(update) => {
+            let Some(table) = tables.get(&update.table) else {
+                return types;
+            };
+            let columns = table_columns(table, update.alias.as_ref().unwrap_or(&update.table));
+            infer_in_assignments(table, &columns, &update.assignments, &mut types);
+            if let Some(selection) = &update.selection {
+                infer_in_expr(selection, &columns, &mut types);
+            }
+        }
+        Statement::Delete(delete) => {
+            let (Some(table), Some(selection)) = (tables.get(&delete.table), &delete.selection)
+            else {
+                return types;
+            };
+            let columns = table_columns(table, delete.alias.as_ref().unwrap_or(&delete.table));
+            infer_in_expr(selection, &columns, &mut types);
+        }
+        _ => {}
+    }
+    types
+}
+
+fn infer_in_set_expr(
+    tables: &HashMap<String, Table>,
+    body: &SetExpr,
+    types: &mut [Option<DataType>],
+) {
+    match body {
+        SetExpr::Select(select) => {
+            let columns = select
+                .from
+                .as_ref()
+                .and_then(|reference| {
+                    let alias = reference.alias.as_ref().unwrap_or(&reference.name);
+                    Some(table_columns(tables.get(&reference.name)?, alias))
+                })
+                .unwrap_or_default();
+            for item in &select.projection {
+                if let SelectItem::Expr { expr, .. } = item {
+                    infer_in_expr(expr, &columns, types);
+                }
+            }
+            if let Some(selection) = &select.selection {
+                infer_in_expr(selection, &columns, types);
+            }
+        }
+        SetExpr::SetOperation { left, right, .. } => {
+            infer_in_set_expr(tables, left, types);
+            infer_in_set_expr(tables, right, types);
+        }
+    }
+}
+
+fn infer_in_assignments(
+    table: &Table,
+    columns: &[OutputColumn],
+    assignments: &[Assignment],
+    types: &mut [Option<DataType>],
+) {
+    for assignment in assignments {
+        let data_type = table
+            .column_index(&assignment.column)
+            .map(|i| table.columns[i].data_type);
+        set_parameter_type(&assignment.value, data_type, types);
+        infer_in_expr(&assignment.value, columns, types);
+    }
+}
+
+fn infer_in_expr(expr: &Expr, columns: &[OutputColumn], types: &mut [Option<DataType>]) {
+    expr.transform(&mut |e| {
+        if let Expr::Binary { left, op, right } = e {
+            if matches!(
+                op,
+                BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq
+            ) {
+                set_parameter_type(left, infer_type(right, columns).ok().flatten(), types);
+                set_parameter_type(right, infer_type(left, columns).ok().flatten(), types);
+            }
+        }
+        None
+    });
+}
+
+fn set_parameter_type(expr: &Expr, data_type: Option<DataType>, types: &mut [Option<DataType>]) {
+    if let (Expr::Parameter(n), Some(data_type)) = (expr, data_type) {
+        if let Some(slot @ None) = n.checked_sub(1).and_then(|i| types.get_mut(i)) {
+            *slot = Some(data_type);
+        }
+    }
+}
+
+// Troca cada `*` pelas colunas que a tabela do FROM tem agora, como o PostgreSQL faz ao criar
+// uma view: colunas adicionadas depois à tabela não mudam a view.
+pub fn expand_wildcards(tables: &HashMap<String, Table>, query: &Query) -> Query {
+    Query {
+        body: expand_set_expr(tables, &query.body),
+        ..query.clone()
+    }
+}
+
+fn expand_set_expr(tables: &HashMap<String, Table>, body: &SetExpr) -> SetExpr {
+    match body {
+        SetExpr::Select(select) => {
+            let Some((reference, table)) = select
+                .from
+                .as_ref()
+                .and_then(|reference| Some((reference, tables.get(&reference.key())?)))
+            else {
+                return body.clone();
+            };
+            let alias = reference.alias.as_ref().unwrap_or(&reference.name);
+            let projection = select
+                .projection
+                .iter()
+                .flat_map(|item| match item {
+                    SelectItem::Wildcard | SelectItem::QualifiedWildcard(_) => table
+                        .columns
+                        .iter()
+                        .map(|column| SelectItem::Expr {
+                            expr: Expr::Column {
+                                table: Some(alias.clone()),
+                                name: column.name.clone(),
+                            },
+                            alias: None,
+                        })
+                        .collect(),
+                    item => vec![item.clone()],
+                })
+                .collect();
+            SetExpr::Select(Box::new(Select {
+                projection,
+                ..(**select).clone()
+            }))
+        }
+        SetExpr::SetOperation {
+            op,
+            all,
+            left,
+            right,
+        } => SetExpr::SetOperation {
+            op: *op,
+            all: *all,
+            left: Box::new(expand_set_expr(tables, left)),
+            right: Box::new(expand_set_expr(tables, right)),
+        },
+    }
+}
+
+// A consulta da view é planejada novamente a cada uso, com os nomes de coluna da definição.
+fn plan_view(
+    tables: &HashMap<String, Table>,
+    name: &str,
+    table: &Table,
+    query: &str,
+    alias: &str,
+) -> Result<(Plan, Vec<OutputColumn>), String> {
+    let planned = match sql::parse(query) {
+        Ok(Statement::Select(query)) => plan_query(tables, &query),
+        Ok(_) => Err("a definição não é uma consulta.".to_string()),
+        Err(error) => Err(error),
+    };
+    let (plan, planned_columns) =
+        planned.map_err(|e| format!("View '{}' inválida: {}", name, e))?;
+    if planned_columns.len() != table.columns.len() {
+        return Err(format!(
+            "View '{}' inválida: a consulta devolve {} colunas, mas a view tem {}.",
+            name,
+            planned_columns.len(),
+            table.columns.len()
+        ));
+    }
+    let columns: Vec<OutputColumn> = table
+        .columns
+        .iter()
+        .zip(planned_columns)
+        .map(|(column, planned)| OutputColumn {
+            table: Some(alias.to_string()),
+            name: column.name.clone(),
+            data_type: planned.data_type,
+        })
+        .collect();
+    Ok((
+        Plan::ViewScan {
+            view: name.to_string(),
+            input: Box::new(plan),
+            columns: columns.clone(),
+        },
+        columns,
+    ))
+}
+
+type Projection = (Vec<Expr>, Vec<String>, Vec<Option<String>>);
+
+// Expande `*` e `t.*` e dá nome às colunas projetadas.
+fn expand_projection(
+    items: &[SelectItem],
+    input_columns: &[OutputColumn],
+) -> Result<Projection, String> {
+    let mut exprs = Vec::new();
+    let mut names = Vec::new();
+    let mut aliases = Vec::new();
+    for item in items {
+        match item {
+            SelectItem::Wildcard | SelectItem::QualifiedWildcard(_) => {
+                let qualifier = match item {
+                    SelectItem::QualifiedWildcard(table) => Some(table),
+                    _ => None,
+                };
+                let mut matched = false;
+                for column in input_columns {
+                    if qualifier.is_some() && column.table.as_ref() != qualifier {
+                        continue;
+                    }
+                    matched = true;
+                    exprs.push(Expr::Column {
+                        table: column.table.clone(),
+                        name: column.name.clone(),
+                    });
+                    names.push(column.name.clone());
+                    aliases.push(None);
+                }
+                if !matched {
+                    if let Some(table) = qualifier {
+                        return Err(format!("Tabela '{}' não encontrada na consulta.", table));
+                    }
+                    return Err("SELECT * exige uma cláusula FROM.".to_string());
+                }
+            }
+            SelectItem::Expr { expr, alias } => {
+                names.push(alias.clone().unwrap_or_else(|| match expr {
+                    Expr::Column { name, .. } => name.clone(),
+                    Expr::Function(call) => call.name.to_lowercase(),
+                    _ => format!("coluna{}", names.len() + 1),
+                }));
+                aliases.push(alias.clone());
+                exprs.push(expr.clone());
+            }
+        }
+    }
+    Ok((exprs, names, aliases))
+}
+
+// Monta o resultado de RETURNING a partir das linhas afetadas por INSERT, UPDATE ou DELETE.
+pub fn returning(
+    items: &[SelectItem],
+    columns: &[OutputColumn],
+    rows: &[&Vec<Value>],
+    context: &Context,
+) -> Result<ResultSet, String> {
+    let (exprs, names, _) = expand_projection(items, columns)?;
+    if exprs.iter().any(Expr::contains_window_function) {
+        return Err("Funções de janela não são permitidas em RETURNING.".to_string());
+    }
+    let mut output_columns = Vec::with_capacity(exprs.len());
+    for (expr, name) in exprs.iter().zip(names) {
+        output_columns.push(OutputColumn {
+            table: None,
+            name,
+            data_type: infer_type(expr, columns)?,
+        });
+    }
+    let rows = rows
+        .iter()
+        .map(|row| {
+            exprs
+                .iter()
+                .map(|expr| evaluate(expr, columns, row, context))
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(ResultSet {
+        columns: output_columns,
+        rows,
+    })
+}
+
+// ORDER BY aceita a posição (ORDER BY 2) ou o alias de uma coluna projetada.
+fn resolve_order_by_alias(
+    expr: &Expr,
+    exprs: &[Expr],
+    aliases: &[Option<String>],
+) -> Result<Expr, String> {
+    match expr {
+        Expr::Literal(Value::Integer(position)) => {
+            let index = (*position as usize).wrapping_sub(1);
+            exprs.get(index).cloned().ok_or_else(|| {
+                format!("Posição {} do ORDER BY fora da lista de seleção.", position)
+            })
+        }
+        Expr::Column { table: None, name } => {
+            match aliases.iter().position(|a| a.as_ref() == Some(name)) {
+                Some(index) => Ok(exprs[index].clone()),
+                None => Ok(expr.clone()),
+            }
+        }
+        _ => Ok(expr.clone()),
+    }
+}
+
+fn resolve_output_position(expr: &Expr, columns: &[OutputColumn]) -> Result<Expr, String> {
+    match expr {
+        Expr::Literal(Value::Integer(position)) => {
+            let index = (*position as usize).wrapping_sub(1);
+            columns
+                .get(index)
+                .map(|c| Expr::Column {
+                    table: None,
+                    name: c.name.clone(),
+                })
+                .ok_or_else(|| {
+                    format!("Posição {} do ORDER BY fora da lista de seleção.", position)
+                })
+        }
+        _ => Ok(expr.clone()),
+    }
+}
+
+// Valida uma condição de filtro: deve ser BOOLEAN e não pode conter funções de janela.
+pub fn check_condition(expr: &Expr, columns: &[OutputColumn], clause: &str) -> Result<(), String> {
+    if expr.contains_window_function() {
+        return Err(format!(
+            "Funções de janela não são permitidas em {}.",
+            clause
+        ));
+    }
+    match infer_type(expr, columns)? {
+        Some(data_type) if data_type != DataType::Boolean => Err(format!(
+            "A cláusula {} deve ser do tipo BOOLEAN, não {}.",
+            clause, data_type
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn resolve_column(
+    columns: &[OutputColumn],
+    table: Option<&str>,
+    name: &str,
+) -> Result<usize, String> {
+    let mut matches = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.name == name && (table.is_none() || c.table.as_deref() == table));
+    match (matches.next(), matches.next()) {
+        (Some((index, _)), None) => Ok(index),
+        (Some(_), Some(_)) => Err(format!("Coluna '{}' é ambígua.", name)),
+        (None, _) => match table {
+            Some(table) => Err(format!("Coluna '{}.{}' não encontrada.", table, name)),
+            None => Err(format!("Coluna '{}' não encontrada.", name)),
+        },
+    }
+}
+
+pub fn infer_type(expr: &Expr, columns: &[OutputColumn]) -> Result<Option<DataType>, String> {
+    match expr {
+        Expr::Column { table, name } => {
+            let index = resolve_column(columns, table.as_deref(), name)?;
+            Ok(columns[index].data_type)
+        }
+        Expr::Literal(value) => Ok(value.data_type()),
+        Expr::Parameter(_) => Ok(None),
+        Expr::IsNull { expr, .. } => {
+            infer_type(expr, columns)?;
+            Ok(Some(DataType::Boolean))
+        }
+        Expr::Unary { op, expr } => {
+            let data_type = infer_type(expr, columns)?;
+            match (op, data_type) {
+                (_, None) => Ok(data_type),
+                (UnaryOperator::Minus, Some(t)) if t.is_numeric() || t == DataType::Interval => {
+                    Ok(data_type)
+                }
+                (UnaryOperator::Not, Some(DataType::Boolean)) => Ok(data_type),
+                (UnaryOperator::Minus, Some(t)) => {
+                    Err(format!("Operador '-' não se aplica ao tipo {}.", t))
+                }
+                (UnaryOperator::Not, Some(t)) => {
+                    Err(format!("Operador 'NOT' não se aplica ao tipo {}.", t))
+                }
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = infer_type(left, columns)?;
+            let right = infer_type(right, columns)?;
+            infer_binary_type(*op, left, right)
+        }
+        Expr::Function(call) if call.over.is_some() => window::infer_window_type(call, columns),
+        Expr::Function(call) if window::is_aggregate(&call.name) => Err(format!(
+            "A função de agregação '{}' exige uma cláusula OVER.",
+            call.name
+        )),
+        Expr::Function(call) => {
+            if call.wildcard {
+                return Err(format!("A função '{}' não aceita '*'.", call.name));
+            }
+            let arg_types = call
+                .args
+                .iter()
+                .map(|a| infer_type(a, columns))
+                .collect::<Result<Vec<_>, String>>()?;
+            if sequence::is_sequence_function(&call.name) {
+                return sequence::check_call(&call.name, &arg_types);
+            }
+            functions::check_call(&call.name, &arg_types)
+        }
+        Expr::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            let operand_type = match operand {
+                Some(operand) => Some(infer_type(operand, columns)?),
+                None => None,
+            };
+            let mut result_type = None;
+            for (when, then) in branches {
+                let when_type = infer_type(when, columns)?;
+                match operand_type {
+                    Some(operand_type) => {
+                        infer_binary_type(BinaryOperator::Eq, operand_type, when_type)?;
+                    }
+                    None if when_type.is_some_and(|t| t != DataType::Boolean) => {
+                        return Err(format!(
+                            "A condição de WHEN deve ser do tipo BOOLEAN, não {}.",
+                            when_type.unwrap_or_default()
+                        ));
+                    }
+                    None => {}
+                }
+                result_type = DataType::unify(result_type, infer_type(then, columns)?)
+                    .map_err(|e| format!("Resultados de CASE: {}", e))?;
+            }
+            if let Some(else_result) = else_result {
+                result_type = DataType::unify(result_type, infer_type(else_result, columns)?)
+                    .map_err(|e| format!("Resultados de CASE: {}", e))?;
+            }
+            Ok(result_type)
+        }
+        Expr::Cast { expr, data_type } => {
+            infer_type(expr, columns)?;
+            Ok(Some(*data_type))
+        }
+    }
+}
+
+fn infer_binary_type(
+    op: BinaryOperator,
+    left: Option<DataType>,
+    right: Option<DataType>,
+) -> Result<Option<DataType>, String> {
+    let mismatch = || {
+        Err(format!(
+            "Operador '{}' não se aplica aos tipos {} e {}.",
+            op.symbol(),
+            left.map(|t| t.to_string()).unwrap_or("NULL".to_string()),
+            right.map(|t| t.to_string()).unwrap_or("NULL".to_string())
+        ))
+    };
+    match op {
+        BinaryOperator::Concat => Ok(Some(DataType::Text)),
+        BinaryOperator::And | BinaryOperator::Or => {
+            for t in [left, right].into_iter().flatten() {
+                if t != DataType::Boolean {
+                    return mismatch();
+                }
+            }
+            Ok(Some(DataType::Boolean))
+        }
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Lt
+        | BinaryOperator::LtEq
+        | BinaryOperator::Gt
+        | BinaryOperator::GtEq => match (left, right) {
+            (Some(l), Some(r))
+                if DataType::unify(left, right).is_err()
+                    && !(l == DataType::Text && (r.is_numeric() || r.is_temporal())
+                        || r == DataType::Text && (l.is_numeric() || l.is_temporal())) =>
+            {
+                mismatch()
+            }
+            _ => Ok(Some(DataType::Boolean)),
+        },
+        _ if left.is_some_and(|t| t.is_temporal()) || right.is_some_and(|t| t.is_temporal()) => {
+            match (left, right) {
+                (Some(l), Some(r)) => match datetime::arithmetic_type(op, l, r) {
+                    Some(data_type) => Ok(Some(data_type)),
+                    None => mismatch(),
+                },
+                (Some(t), None) | (None, Some(t)) => Ok(Some(t)),
+                (None, None) => Ok(None),
+            }
+        }
+        _ => {
+            for t in [left, right].into_iter().flatten() {
+                if !t.is_numeric() {
+                    return mismatch();
+                }
+            }
+            match (left, right) {
+                (Some(DataType::Integer), Some(DataType::Integer)) => Ok(Some(DataType::Integer)),
+                (None, None) => Ok(None),
+                (Some(DataType::Integer), None) | (None, Some(DataType::Integer)) => {
+                    Ok(Some(DataType::Integer))
+                }
+                _ => Ok(Some(DataType::Float)),
+            }
+        }
+    }
+}
+
+pub fn evaluate(
+    expr: &Expr,
+    columns: &[OutputColumn],
+    row: &[Value],
+    context: &Context,
+) -> Result<Value, String> {
+    match expr {
+        Expr::Column { table, name } => {
+            let index = resolve_column(columns, table.as_deref(), name)?;
+            Ok(row.get(index).cloned().unwrap_or(Value::Null))
+        }
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Parameter(index) => context.parameter(*index),
+        Expr::IsNull { expr, negated } => {
+            let value = evaluate(expr, columns, row, context)?;
+            Ok(Value::Boolean(value.is_null() != *negated))
+        }
+        Expr::Unary { op, expr } => {
+            let value = evaluate(expr, columns, row, context)?;
+            match (op, value) {
+                (_, Value::Null) => Ok(Value::Null),
+                (UnaryOperator::Minus, Value::Integer(i)) => i
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or_else(|| "Estouro de inteiro.".to_string()),
+                (UnaryOperator::Minus, Value::Float(f)) => Ok(Value::Float(-f)),
+                (UnaryOperator::Minus, Value::Interval(i)) => i.negate().map(Value::Interval),
+                (UnaryOperator::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+                (_, value) => Err(format!("Operação inválida sobre o valor '{}'.", value)),
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = evaluate(left, columns, row, context)?;
+            match op {
+                BinaryOperator::And if !left.is_null() && !left.is_true() => {
+                    return Ok(Value::Boolean(false))
+                }
+                BinaryOperator::Or if left.is_true() => return Ok(Value::Boolean(true)),
+                _ => {}
+            }
+            let right = evaluate(right, columns, row, context)?;
+            evaluate_binary(*op, left, right)
+        }
+        Expr::Function(call) if call.over.is_some() || window::is_aggregate(&call.name) => {
+            Err(format!(
+                "Função '{}' não pode ser avaliada neste contexto.",
+                call.name
+            ))
+        }
+        Expr::Function(call) => {
+            let args = call
+                .args
+                .iter()
+                .map(|a| evaluate(a, columns, row, context))
+                .collect::<Result<Vec<_>, String>>()?;
+            if sequence::is_sequence_function(&call.name) {
+                return context.call_sequence_function(&call.name, &args);
+            }
+            functions::call(&call.name, &args)
+        }
+        Expr::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            let operand = match operand {
+                Some(operand) => Some(evaluate(operand, columns, row, context)?),
+                None => None,
+            };
+            for (when, then) in branches {
+                let when = evaluate(when, columns, row, context)?;
+                let matched = match &operand {
+                    Some(operand) => operand.compare(&when) == Some(Ordering::Equal),
+                    None => when.is_true(),
+                };
+                if matched {
+                    return evaluate(then, columns, row, context);
+                }
+            }
+            match else_result {
+                Some(else_result) => evaluate(else_result, columns, row, context),
+                None => Ok(Value::Null),
+            }
+        }
+        Expr::Cast { expr, data_type } => evaluate(expr, columns, row, context)?.cast(*data_type),
+    }
+}
+
+fn evaluate_binary(op: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    match op {
+        BinaryOperator::Concat if left.is_null() || right.is_null() => Ok(Value::Null),
+        BinaryOperator::Concat => Ok(Value::Text(format!("{}{}", left, right))),
+        BinaryOperator::And => Ok(match (left.is_null(), right.is_null()) {
+            _ if !right.is_null() && !right.is_true() => Value::Boolean(false),
+            (false, false) => Value::Boolean(true),
+            _ => Value::Null,
+        }),
+        BinaryOperator::Or => Ok(match (left.is_null(), right.is_null()) {
+            _ if right.is_true() => Value::Boolean(true),
+            (false, false) => Value::Boolean(false),
+            _ => Value::Null,
+        }),
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Lt
+        | BinaryOperator::LtEq
+        | BinaryOperator::Gt
+        | BinaryOperator::GtEq => {
+            let ordering = match left.compare(&right) {
+                Some(ordering) => ordering,
+                None => return Ok(Value::Null),
+            };
+            Ok(Value::Boolean(match op {
+                BinaryOperator::Eq => ordering == Ordering::Equal,
+                BinaryOperator::NotEq => ordering != Ordering::Equal,
+                BinaryOperator::Lt => ordering == Ordering::Less,
+                BinaryOperator::LtEq => ordering != Ordering::Greater,
+                BinaryOperator::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        _ => evaluate_arithmetic(op, left, right),
+    }
+}
+
+fn evaluate_arithmetic(op: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    match (&left, &right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        _ if left.data_type().is_some_and(|t| t.is_temporal())
+            || right.data_type().is_some_and(|t| t.is_temporal()) =>
+        {
+            datetime::arithmetic(op, &left, &right)
+        }
+        (Value::Integer(l), Value::Integer(r)) => {
+            let (l, r) = (*l, *r);
+            let result = match op {
+                BinaryOperator::Plus => l.checked_add(r),
+                BinaryOperator::Minus => l.checked_sub(r),
+                BinaryOperator::Multiply => l.checked_mul(r),
+                BinaryOperator::Divide if r == 0 => return Err("Divisão por zero.".to_string()),
+                BinaryOperator::Divide => l.checked_div(r),
+                BinaryOperator::Modulo if r == 0 => return Err("Divisão por zero.".to_string()),
+                _ => l.checked_rem(r),
+            };
+            result
+                .map(Value::Integer)
+                .ok_or_else(|| "Estouro de inteiro.".to_string())
+        }
+        _ => {
+            let (l, r) = match (left.as_f64(), right.as_f64()) {
+                (Some(l), Some(r)) => (l, r),
+                _ => {
+                    return Err(format!(
+                        "Operador '{}' não se aplica aos valores '{}' e '{}'.",
+                        op.symbol(),
+                        left,
+                        right
+                    ))
+                }
+            };
+            match op {
+                BinaryOperator::Plus => Ok(Value::Float(l + r)),
+                BinaryOperator::Minus => Ok(Value::Float(l - r)),
+                BinaryOperator::Multiply => Ok(Value::Float(l * r)),
+                _ if r == 0.0 => Err("Divisão por zero.".to_string()),
+                BinaryOperator::Divide => Ok(Value::Float(l / r)),
+                _ => Ok(Value::Float(l % r)),
+            }
+        }
+    }
+}
+
+pub fn execute_plan(
+    tables: &HashMap<String, Table>,
+    plan: &Plan,
+    context: &Context,
+) -> Result<ResultSet, String> {
+    if context.reads.borrow().is_some() {
+        record_reads(plan, context);
+    }
+    run_plan(tables, plan, context)
+}
+
+// Um filtro logo acima da leitura da tabela restringe as linhas lidas ao seu predicado.
+fn record_reads(plan: &Plan, context: &Context) {
+    match plan {
+        Plan::SeqScan { table, alias } => context.record_read(table, alias, None),
+        Plan::Filter { input, predicate } => match input.as_ref() {
+            Plan::SeqScan { table, alias } => context.record_read(table, alias, Some(predicate)),
+            input => record_reads(input, context),
+        },
+        Plan::SingleRow => {}
+        Plan::Sort { input, .. }
+        | Plan::Window { input, .. }
+        | Plan::Project { input, .. }
+        | Plan::Limit { input, .. }
+        | Plan::LockRows { input, .. }
+        | Plan::ViewScan { input, .. } => record_reads(input, context),
+        Plan::SetOperation { left, right, .. } => {
+            record_reads(left, context);
+            record_reads(right, context);
+        }
+    }
+}
+
+fn run_plan(
+    tables: &HashMap<String, Table>,
+    plan: &Plan,
+    context: &Context,
+) -> Result<ResultSet, String> {
+    context.check_deadline()?;
+    if context.profile.borrow().is_none() {
+        return execute_node(tables, plan, context);
+    }
+    let start = Instant::now();
+    let result = execute_node(tables, plan, context)?;
+    if let Some(profile) = context.profile.borrow_mut().as_mut() {
+        profile.insert(node_key(plan), (result.rows.len(), start.elapsed()));
+    }
+    Ok(result)
+}
+
+fn execute_node(
+    tables: &HashMap<String, Table>,
+    plan: &Plan,
+    context: &Context,
+) -> Result<ResultSet, String> {
+    match plan {
+        Plan::SeqScan { table, alias } => {
+            let data = tables
+                .get(table)
+                .ok_or_else(|| format!("Tabela '{}' não existe.", table))?;
+            Ok(ResultSet {
+                columns: data
+                    .columns
+                    .iter()
+                    .map(|c| OutputColumn {
+                        table: Some(alias.clone()),
+                        name: c.name.clone(),
+                        data_type: Some(c.data_type),
+                    })
+                    .collect(),
+                rows: data.data.to_vec(),
+            })
+        }
+        Plan::ViewScan { input, columns, .. } => Ok(ResultSet {
+            columns: columns.clone(),
+            rows: run_plan(tables, input, context)?.rows,
+        }),
+        Plan::SingleRow => Ok(ResultSet {
+            columns: Vec::new(),
+            rows: vec![Vec::new()],
+        }),
+        Plan::Filter { input, predicate } => {
+            let mut result = run_plan(tables, input, context)?;
+            let mut rows = Vec::new();
+            for row in result.rows {
+                if evaluate(predicate, &result.columns, &row, context)?.is_true() {
+                    rows.push(row);
+                }
+            }
+            result.rows = rows;
+            Ok(result)
+        }
+        Plan::Sort { input, keys } => {
+            let mut result = run_plan(tables, input, context)?;
+            let mut keyed = Vec::with_capacity(result.rows.len());
+            for row in result.rows {
+                let key = keys
+                    .iter()
+                    .map(|k| evaluate(&k.expr, &result.columns, &row, context))
+                    .collect::<Result<Vec<_>, String>>()?;
+                keyed.push((key, row));
+            }
+            keyed.sort_by(|(a, _), (b, _)| compare_sort_keys(keys, a, b));
+            result.rows = keyed.into_iter().map(|(_, row)| row).collect();
+            Ok(result)
+        }
+        Plan::Window {
+            input,
+            functions,
+            columns,
+        } => {
+            let mut result = run_plan(tables, input, context)?;
+            let mut computed = Vec::with_capacity(functions.len());
+            for call in functions {
+                computed.push(window::compute_window(
+                    call,
+                    &result.columns,
+                    &result.rows,
+                    context,
+                )?);
+            }
+            for (i, row) in result.rows.iter_mut().enumerate() {
+                row.extend(computed.iter().map(|values| values[i].clone()));
+            }
+            result.columns = columns.clone();
+            Ok(result)
+        }
+        Plan::Project {
+            input,
+            exprs,
+            columns,
+        } => {
+            let result = run_plan(tables, input, context)?;
+            let mut rows = Vec::with_capacity(result.rows.len());
+            for row in &result.rows {
+                rows.push(
+                    exprs
+                        .iter()
+                        .map(|e| evaluate(e, &result.columns, row, context))
+                        .collect::<Result<Vec<_>, String>>()?,
+                );
+            }
+            Ok(ResultSet {
+                columns: columns.clone(),
+                rows,
+            })
+        }
+        Plan::SetOperation {
+            op,
+            all,
+            left,
+            right,
+            columns,
+        } => {
+            let left = conform_rows(run_plan(tables, left, context)?.rows, columns)?;
+            let right = conform_rows(run_plan(tables, right, context)?.rows, columns)?;
+            Ok(ResultSet {
+                columns: columns.clone(),
+                rows: combine(*op, *all, left, right),
+            })
+        }
+        Plan::Limit {
+            input,
+            limit,
+            offset,
+        } => {
+            let (limit, offset) = limit_counts(limit, offset, context)?;
+            let mut result = run_plan(tables, input, context)?;
+            let rows = result.rows.into_iter().skip(offset);
+            result.rows = match limit {
+                Some(limit) => rows.take(limit).collect(),
+                None => rows.collect(),
+            };
+            Ok(result)
+        }
+        Plan::LockRows {
+            input,
+            table,
+            locking,
+            limit,
+            offset,
+        } => {
+            let (limit, offset) = limit_counts(limit, offset, context)?;
+            context.lock_table(
+                table,
+                TableLockMode::RowShare,
+                locking.wait == LockWait::NoWait,
+            )?;
+            let mut result = run_plan(tables, input, context)?;
+            let mut rows = Vec::new();
+            let mut skipped = 0;
+            for row in result.rows {
+                if limit.is_some_and(|limit| rows.len() >= limit) {
+                    break;
+                }
+                if !context.lock_row(table, &row, locking.mode, locking.wait)? {
+                    continue;
+                }
+                if skipped < offset {
+                    skipped += 1;
+                } else {
+                    rows.push(row);
+                }
+            }
+            result.rows = rows;
+            Ok(result)
+        }
+    }
+}
+
+fn limit_counts(
+    limit: &Option<Expr>,
+    offset: &Option<Expr>,
+    context: &Context,
+) -> Result<(Option<usize>, usize), String> {
+    let limit = match limit {
+        Some(expr) => Some(constant_count(expr, "LIMIT", context)?),
+        None => None,
+    };
+    let offset = match offset {
+        Some(expr) => constant_count(expr, "OFFSET", context)?,
+        None => 0,
+    };
+    Ok((limit, offset))
+}
+
+fn compare_sort_keys(keys: &[OrderByItem], a: &[Value], b: &[Value]) -> Ordering {
+    for (key, (a, b)) in keys.iter().zip(a.iter().zip(b)) {
+        let ordering = a.sort_cmp(b);
+        let ordering = if key.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn conform_rows(
+    rows: Vec<Vec<Value>>,
+    columns: &[OutputColumn],
+) -> Result<Vec<Vec<Value>>, String> {
+    rows.into_iter()
+        .map(|row| {
+            row.iter()
+                .zip(columns)
+                .map(|(value, column)| match column.data_type {
+                    Some(data_type) => value.cast(data_type),
+                    None => Ok(value.clone()),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn row_key(row: &[Value]) -> String {
+    format!("{:?}", row)
+}
+
+// Semântica de multiconjunto: com ALL as duplicatas são preservadas, sem ALL o resultado é distinto.
+fn combine(
+    op: SetOperator,
+    all: bool,
+    left: Vec<Vec<Value>>,
+    right: Vec<Vec<Value>>,
+) -> Vec<Vec<Value>> {
+    let mut right_counts: HashMap<String, usize> = HashMap::new();
+    if op != SetOperator::Union {
+        for row in &right {
+            *right_counts.entry(row_key(row)).or_insert(0) += 1;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut rows = Vec::new();
+    let mut keep = |row: Vec<Value>, rows: &mut Vec<Vec<Value>>| {
+        if all || seen.insert(row_key(&row)) {
+            rows.push(row);
+        }
+    };
+
+    match op {
+        SetOperator::Union => {
+            for row in left.into_iter().chain(right) {
+                keep(row, &mut rows);
+            }
+        }
+        SetOperator::Intersect => {
+            for row in left {
+                let key = row_key(&row);
+                match right_counts.get_mut(&key) {
+                    Some(count) if *count > 0 => {
+                        if all {
+                            *count -= 1;
+                        }
+                        keep(row, &mut rows);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        SetOperator::Except => {
+            for row in left {
+                let key = row_key(&row);
+                match right_counts.get_mut(&key) {
+                    Some(count) if *count > 0 => {
+                        if all {
+                            *count -= 1;
+                        }
+                    }
+                    _ => keep(row, &mut rows),
+                }
+            }
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    fn database(name: &str) -> TestDatabase {
+        let mut test = TestDatabase::new(name);
+        test.exec("CREATE TABLE a (x INTEGER)");
+        test.exec("CREATE TABLE b (x INTEGER)");
+        test.exec("INSERT INTO a VALUES (1), (2), (2), (3)");
+        test.exec("INSERT INTO b VALUES (2), (3), (4)");
+        test
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut test = database("set_operations");
+        assert_eq!(
+            test.rows("SELECT x FROM a UNION SELECT x FROM b ORDER BY x"),
+            ["1", "2", "3", "4"]
+        );
+        assert_eq!(
+            test.rows("SELECT x FROM a UNION ALL SELECT x FROM b ORDER BY x"),
+            ["1", "2", "2", "2", "3", "3", "4"]
+        );
+        assert_eq!(
+            test.rows("SELECT x FROM a INTERSECT SELECT x FROM b ORDER BY x"),
+            ["2", "3"]
+        );
+        assert_eq!(test.rows("SELECT x FROM a EXCEPT SELECT x FROM b"), ["1"]);
+    }
+
+    #[test]
+    fn set_operations_require_matching_columns() {
+        let mut test = database("set_operation_columns");
+        test.fail("SELECT x, x FROM a UNION SELECT x FROM b");
+    }
+
+    #[test]
+    fn integer_literal_limits() {
+        let mut test = TestDatabase::new("integer_limits");
+        assert_eq!(
+            test.rows("SELECT -9223372036854775808"),
+            ["-9223372036854775808"]
+        );
+        assert_eq!(
+            test.rows("SELECT - -9223372036854775807"),
+            ["9223372036854775807"]
+        );
+        test.fail("SELECT -(-9223372036854775807 - 1)");
+        test.fail("SELECT 9223372036854775807 + 1");
+    }
+}
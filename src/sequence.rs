@@ -0,0 +1,218 @@
+use crate::sql::CreateSequence;
+use crate::value::{DataType, Value};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Sequence {
+    pub increment: i64,
+    pub min_value: i64,
+    pub max_value: i64,
+    pub cycle: bool,
+    pub last_value: i64,
+    pub is_called: bool,
+    // Coluna SERIAL dona da sequência; ela é removida junto com a coluna ou a tabela.
+    #[serde(default)]
+    pub owned_by: Option<(String, String)>,
+}
+
+impl Sequence {
+    pub fn new(options: &CreateSequence) -> Result<Sequence, String> {
+        let increment = options.increment.unwrap_or(1);
+        if increment == 0 {
+            return Err("O incremento de uma sequência não pode ser zero.".to_string());
+        }
+        let ascending = increment > 0;
+        let min_value = options
+            .min_value
+            .unwrap_or(if ascending { 1 } else { i64::MIN });
+        let max_value = options
+            .max_value
+            .unwrap_or(if ascending { i64::MAX } else { -1 });
+        let start = options
+            .start
+            .unwrap_or(if ascending { min_value } else { max_value });
+        if min_value > max_value {
+            return Err(format!(
+                "MINVALUE ({}) deve ser menor que MAXVALUE ({}).",
+                min_value, max_value
+            ));
+        }
+        if start < min_value || start > max_value {
+            return Err(format!(
+                "O valor inicial {} está fora do intervalo da sequência ({} a {}).",
+                start, min_value, max_value
+            ));
+        }
+        Ok(Sequence {
+            increment,
+            min_value,
+            max_value,
+            cycle: options.cycle,
+            last_value: start,
+            is_called: false,
+            owned_by: None,
+        })
+    }
+
+    // Compara tudo menos a posição atual da sequência.
+    pub fn same_definition(&self, other: &Sequence) -> bool {
+        self.increment == other.increment
+            && self.min_value == other.min_value
+            && self.max_value == other.max_value
+            && self.cycle == other.cycle
+            && self.owned_by == other.owned_by
+    }
+
+    pub fn next_value(&mut self, name: &str) -> Result<i64, String> {
+        if !self.is_called {
+            self.is_called = true;
+            return Ok(self.last_value);
+        }
+        let next = match self.last_value.checked_add(self.increment) {
+            Some(next) if next >= self.min_value && next <= self.max_value => next,
+            _ if self.cycle && self.increment > 0 => self.min_value,
+            _ if self.cycle => self.max_value,
+            _ if self.increment > 0 => {
+                return Err(format!(
+                    "A sequência '{}' atingiu o valor máximo ({}).",
+                    name, self.max_value
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "A sequência '{}' atingiu o valor mínimo ({}).",
+                    name, self.min_value
+                ))
+            }
+        };
+        self.last_value = next;
+        Ok(next)
+    }
+
+    pub fn current_value(&self, name: &str) -> Result<i64, String> {
+        if !self.is_called {
+            return Err(format!(
+                "O valor atual da sequência '{}' ainda não foi definido; use nextval primeiro.",
+                name
+            ));
+        }
+        Ok(self.last_value)
+    }
+
+    pub fn set_value(&mut self, name: &str, value: i64, is_called: bool) -> Result<(), String> {
+        if value < self.min_value || value > self.max_value {
+            return Err(format!(
+                "O valor {} está fora do intervalo da sequência '{}' ({} a {}).",
+                value, name, self.min_value, self.max_value
+            ));
+        }
+        self.last_value = value;
+        self.is_called = is_called;
+        Ok(())
+    }
+}
+
+pub fn is_sequence_function(name: &str) -> bool {
+    matches!(name, "NEXTVAL" | "CURRVAL" | "SETVAL")
+}
+
+pub fn check_call(name: &str, arg_types: &[Option<DataType>]) -> Result<Option<DataType>, String> {
+    let (min_args, max_args) = match name {
+        "SETVAL" => (2, 3),
+        _ => (1, 1),
+    };
+    if arg_types.len() < min_args || arg_types.len() > max_args {
+        return Err(format!(
+            "Número de argumentos inválido para a função '{}'.",
+            name
+        ));
+    }
+    let expected = [DataType::Text, DataType::Integer, DataType::Boolean];
+    for (i, (arg, expected)) in arg_types.iter().zip(expected).enumerate() {
+        if let Some(t) = arg {
+            if *t != expected {
+                return Err(format!(
+                    "A função '{}' espera {} no argumento {}, mas recebeu {}.",
+                    name,
+                    expected,
+                    i + 1,
+                    t
+                ));
+            }
+        }
+    }
+    Ok(Some(DataType::Integer))
+}
+
+// Os argumentos já foram verificados por `check_call`; NULL em qualquer um resulta em NULL.
+pub fn call(
+    name: &str,
+    args: &[Value],
+    sequence: &mut Sequence,
+    sequence_name: &str,
+) -> Result<Value, String> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    match name {
+        "NEXTVAL" => sequence.next_value(sequence_name).map(Value::Integer),
+        "CURRVAL" => sequence.current_value(sequence_name).map(Value::Integer),
+        _ => {
+            let value = args[1].as_i64().unwrap_or(0);
+            let is_called = args.get(2).is_none_or(Value::is_true);
+            sequence.set_value(sequence_name, value, is_called)?;
+            Ok(Value::Integer(value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    #[test]
+    fn serial_columns_take_values_from_their_sequence() {
+        let mut test = TestDatabase::new("serial");
+        test.exec("CREATE TABLE pedidos (id SERIAL PRIMARY KEY, descricao TEXT)");
+        assert_eq!(
+            test.rows("INSERT INTO pedidos (descricao) VALUES ('a'), ('b') RETURNING id"),
+            ["1", "2"]
+        );
+        test.exec("INSERT INTO pedidos VALUES (10, 'c')");
+        assert_eq!(test.rows("SELECT CURRVAL('pedidos_id_seq')"), ["2"]);
+        // Valores consumidos não voltam com o ROLLBACK.
+        test.exec("BEGIN");
+        test.exec("INSERT INTO pedidos (descricao) VALUES ('d')");
+        test.exec("ROLLBACK");
+        assert_eq!(
+            test.rows("INSERT INTO pedidos (descricao) VALUES ('e') RETURNING id"),
+            ["4"]
+        );
+        test.reopen();
+        assert_eq!(test.rows("SELECT NEXTVAL('pedidos_id_seq')"), ["5"]);
+        test.exec("DROP TABLE pedidos");
+        test.fail("SELECT NEXTVAL('pedidos_id_seq')");
+    }
+
+    #[test]
+    fn sequence_options() {
+        let mut test = TestDatabase::new("sequence_options");
+        test.exec("CREATE SEQUENCE s START WITH 1000 INCREMENT BY 10");
+        test.fail("SELECT CURRVAL('s')");
+        assert_eq!(test.rows("SELECT NEXTVAL('s')"), ["1000"]);
+        assert_eq!(test.rows("SELECT NEXTVAL('s')"), ["1010"]);
+        test.exec("SELECT SETVAL('s', 5, FALSE)");
+        assert_eq!(test.rows("SELECT NEXTVAL('s')"), ["5"]);
+
+        test.exec("CREATE SEQUENCE c MINVALUE 1 MAXVALUE 2 CYCLE");
+        test.exec("CREATE SEQUENCE n MAXVALUE 1");
+        assert_eq!(
+            test.rows("SELECT NEXTVAL('c'), NEXTVAL('c'), NEXTVAL('c')"),
+            ["1 | 2 | 1"]
+        );
+        test.exec("SELECT NEXTVAL('n')");
+        test.fail("SELECT NEXTVAL('n')");
+        test.fail("CREATE SEQUENCE z INCREMENT BY 0");
+        test.fail("CREATE SEQUENCE z START WITH 5 MAXVALUE 3");
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
-    collections::HashMap,
-    io::{Read, Write},
+    collections::{hash_map::Entry, HashMap},
+    io::{ErrorKind, Read, Write},
     net::{TcpListener, TcpStream},
-    sync::{Arc, Mutex},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
 };
 use serde::{Deserialize, Serialize};
 use crate::database::Database;
+use crate::session::Session;
+use crate::value::Value;
 
 #[derive(Serialize, Deserialize)]
 enum ServerCommand {
@@ -13,10 +17,22 @@ enum ServerCommand {
     Disconnect,
     Execute { command: String },
     Backup { db_name: String },
-    Restore { db_name: String, backup_file: String },
+    // Clientes antigos não enviam a chave; o pedido é recusado com uma mensagem clara.
+    Restore {
+        db_name: String,
+        backup_file: String,
+        #[serde(default)]
+        encryption_key: Option<String>,
+    },
     BeginTransaction,
     CommitTransaction,
     RollbackTransaction,
+    Savepoint { name: String },
+    RollbackToSavepoint { name: String },
+    ReleaseSavepoint { name: String },
+    Prepare { name: String, command: String },
+    ExecutePrepared { name: String, parameters: Vec<Value> },
+    Deallocate { name: String },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -25,8 +41,27 @@ enum ServerResponse {
     Error(String),
 }
 
+// Banco aberto no servidor. Cada banco tem o seu próprio Mutex: conexões em bancos
+// diferentes não esperam umas pelas outras. Dentro do mesmo banco os comandos ainda são
+// executados um de cada vez; os locks de linha e de tabela (lock.rs) decidem quem espera por
+// quem entre as transações abertas.
+struct SharedDatabase {
+    database: Mutex<Database>,
+    // Avisado depois de cada requisição no banco, quando locks podem ter sido liberados.
+    released: Condvar,
+}
+
+impl SharedDatabase {
+    fn new(database: Database) -> Arc<Self> {
+        Arc::new(SharedDatabase { database: Mutex::new(database), released: Condvar::new() })
+    }
+}
+
+// O Mutex do mapa só é mantido enquanto um banco é procurado, aberto ou substituído.
+type Databases = Arc<Mutex<HashMap<String, Arc<SharedDatabase>>>>;
+
 pub struct HexenServer {
-    databases: Arc<Mutex<HashMap<String, Database>>>,
+    databases: Databases,
 }
 
 impl HexenServer {
@@ -43,9 +78,10 @@ impl HexenServer {
         for stream in listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    // As leituras do socket bloqueiam, então cada conexão tem sua própria thread.
                     let databases = self.databases.clone();
-                    tokio::spawn(async move {
-                        Self::handle_client(stream, databases).await;
+                    thread::spawn(move || {
+                        Self::handle_client(stream, databases);
                     });
                 }
                 Err(e) => {
@@ -55,20 +91,44 @@ impl HexenServer {
         }
     }
 
-    async fn handle_client(mut stream: TcpStream, databases: Arc<Mutex<HashMap<String, Database>>>) {
-        println!("Novo cliente conectado: {:?}", stream.peer_addr().unwrap());
+    fn handle_client(mut stream: TcpStream, databases: Databases) {
+        if let Ok(address) = stream.peer_addr() {
+            println!("Novo cliente conectado: {:?}", address);
+        }
 
         let mut buffer = [0; 1024];
+        let mut pending: Vec<u8> = Vec::new();
+        let mut session = Session::default();
         loop {
+            // Com uma transação aberta, a leitura espera no máximo o idle_in_transaction_timeout.
+            // Qualquer falha de socket conta como desconexão, para que a sessão seja sempre encerrada.
+            if stream.set_read_timeout(session.idle_timeout()).is_err() {
+                break;
+            }
             match stream.read(&mut buffer) {
                 Ok(0) => {
-                    println!("Cliente desconectado: {:?}", stream.peer_addr().unwrap());
+                    println!("Cliente desconectado.");
                     break;
                 }
                 Ok(n) => {
-                    let request = String::from_utf8_lossy(&buffer[..n]);
-                    let response = Self::process_request(&request, &databases);
-                    stream.write_all(response.as_bytes()).unwrap();
+                    // Comandos grandes chegam em vários pedaços; aguarda até o JSON estar completo.
+                    pending.extend_from_slice(&buffer[..n]);
+                    if matches!(serde_json::from_slice::<serde_json::Value>(&pending), Err(e) if e.is_eof()) {
+                        continue;
+                    }
+                    let request = String::from_utf8_lossy(&pending).to_string();
+                    pending.clear();
+                    let response = Self::process_request(&request, &databases, &mut session);
+                    if stream.write_all(response.as_bytes()).is_err() {
+                        eprintln!("Erro ao responder ao cliente; encerrando a conexão.");
+                        break;
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    Self::with_database(&databases, &mut session, |db, session| {
+                        db.expire_idle_transaction(session);
+                        String::new()
+                    });
                 }
                 Err(e) => {
                     eprintln!("Erro ao ler do cliente: {}", e);
@@ -76,67 +136,186 @@ impl HexenServer {
                 }
             }
         }
+        Self::end_session(&databases, &mut session);
     }
 
-    fn process_request(request: &str, databases: &Arc<Mutex<HashMap<String, Database>>>) -> String {
+    // Um comando que entrou em pânico não derruba as demais conexões: o Mutex envenenado
+    // continua sendo usado.
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Banco escolhido pela conexão; sem Connect, o primeiro aberto no servidor.
+    fn database(databases: &Databases, session: &Session) -> Option<Arc<SharedDatabase>> {
+        let databases = Self::lock(databases);
+        match &session.database {
+            Some(name) => databases.get(name).cloned(),
+            None if session.disconnected => None,
+            None => databases.values().next().cloned(),
+        }
+    }
+
+    // Executa `run` com o banco da conexão travado. Um comando bloqueado por um lock de outra
+    // conexão espera ela terminar e é repetido; só o banco dele fica parado enquanto isso.
+    fn with_database(databases: &Databases, session: &mut Session, mut run: impl FnMut(&mut Database, &mut Session) -> String) -> Option<String> {
+        let shared = Self::database(databases, session)?;
+        let mut db = Self::lock(&shared.database);
+        let output = loop {
+            let output = run(&mut db, session);
+            let Some(blockers) = session.waiting.take() else {
+                break output;
+            };
+            db = Self::wait_for_release(&shared, db, session, &blockers);
+        };
+        drop(db);
+        shared.released.notify_all();
+        Some(output)
+    }
+
+    // Espera até alguma das transações que bloquearam o comando terminar. Enquanto isso o
+    // comando não é repetido; ele só volta antes quando um dos tempos limite vence, para
+    // devolver o erro.
+    fn wait_for_release<'a>(
+        shared: &'a SharedDatabase,
+        mut db: MutexGuard<'a, Database>,
+        session: &mut Session,
+        blockers: &[u64],
+    ) -> MutexGuard<'a, Database> {
+        loop {
+            db = shared.released.wait_timeout(db, Duration::from_millis(100)).unwrap_or_else(PoisonError::into_inner).0;
+            if !db.is_blocked(blockers) || session.wait_expired().is_some() {
+                return db;
+            }
+        }
+    }
+
+    // Reverte a transação que a conexão deixou aberta.
+    fn end_session(databases: &Databases, session: &mut Session) {
+        Self::with_database(databases, session, |db, session| {
+            db.end_session(session);
+            String::new()
+        });
+    }
+
+    fn respond(output: Option<String>) -> String {
+        match output {
+            Some(output) => serde_json::to_string(&ServerResponse::Success(output)).unwrap(),
+            None => serde_json::to_string(&ServerResponse::Error("Nenhum banco de dados conectado.".to_string())).unwrap(),
+        }
+    }
+
+    fn process_request(request: &str, databases: &Databases, session: &mut Session) -> String {
         let command: Result<ServerCommand, _> = serde_json::from_str(request);
 
         match command {
             Ok(ServerCommand::Connect { db_name, encryption_key }) => {
-                let mut databases = databases.lock().unwrap();
-                if databases.contains_key(&db_name) {
-                    return serde_json::to_string(&ServerResponse::Error("Banco de dados já está conectado.".to_string()))
-                        .unwrap();
+                if session.transaction.is_some() {
+                    return serde_json::to_string(&ServerResponse::Error("Conclua a transação atual antes de trocar de banco de dados.".to_string())).unwrap();
                 }
-
-                let db = Database::new(format!("{}.hxn", db_name), &encryption_key, "UTF-8");
-                databases.insert(db_name, db);
+                // Outra conexão já abriu o banco: passa a usá-lo também, com a mesma chave. A chave é
+                // conferida fora do Mutex do mapa, que não deve esperar por um banco ocupado.
+                let shared = Self::lock(databases).get(&db_name).cloned();
+                if let Some(shared) = shared {
+                    if !Self::lock(&shared.database).has_key(&encryption_key) {
+                        return serde_json::to_string(&ServerResponse::Error("Chave de criptografia inválida ou arquivo corrompido.".to_string())).unwrap();
+                    }
+                } else {
+                    let mut databases = Self::lock(databases);
+                    if !databases.contains_key(&db_name) {
+                        let db = match Database::open(format!("{}.hxn", db_name), &encryption_key) {
+                            Ok(db) => db,
+                            Err(error) => return serde_json::to_string(&ServerResponse::Error(error)).unwrap(),
+                        };
+                        databases.insert(db_name.clone(), SharedDatabase::new(db));
+                    } else if !Self::lock(&databases[&db_name].database).has_key(&encryption_key) {
+                        return serde_json::to_string(&ServerResponse::Error("Chave de criptografia inválida ou arquivo corrompido.".to_string())).unwrap();
+                    }
+                }
+                session.database = Some(db_name);
+                session.disconnected = false;
                 serde_json::to_string(&ServerResponse::Success("Conexão estabelecida.".to_string())).unwrap()
             }
             Ok(ServerCommand::Disconnect) => {
+                Self::end_session(databases, session);
+                session.database = None;
+                session.disconnected = true;
                 serde_json::to_string(&ServerResponse::Success("Desconectado.".to_string())).unwrap()
             }
             Ok(ServerCommand::Execute { command }) => {
-                let mut databases = databases.lock().unwrap();
-                if let Some(db) = databases.values_mut().next() {
-                    return serde_json::to_string(&ServerResponse::Success(db.execute_command(&command))).unwrap();
-                }
-                serde_json::to_string(&ServerResponse::Error("Nenhum banco de dados conectado.".to_string())).unwrap()
+                Self::respond(Self::with_database(databases, session, |db, session| db.execute_command(&command, session)))
             }
             Ok(ServerCommand::Backup { db_name }) => {
-                match Database::create_backup(&db_name, databases) {
+                let shared = Self::lock(databases).get(&db_name).cloned();
+                let Some(shared) = shared else {
+                    return serde_json::to_string(&ServerResponse::Error("Banco de dados não encontrado.".to_string())).unwrap();
+                };
+                let result = Self::lock(&shared.database).create_backup(&db_name);
+                match result {
                     Ok(message) => serde_json::to_string(&ServerResponse::Success(message)).unwrap(),
                     Err(error) => serde_json::to_string(&ServerResponse::Error(error)).unwrap(),
                 }
             }
-            Ok(ServerCommand::Restore { db_name, backup_file }) => {
-                match Database::restore_backup(&db_name, &backup_file, databases) {
-                    Ok(message) => serde_json::to_string(&ServerResponse::Success(message)).unwrap(),
-                    Err(error) => serde_json::to_string(&ServerResponse::Error(error)).unwrap(),
+            Ok(ServerCommand::Restore { db_name, backup_file, encryption_key }) => {
+                let Some(encryption_key) = encryption_key else {
+                    return serde_json::to_string(&ServerResponse::Error("Informe a chave de criptografia do banco para restaurar o backup.".to_string())).unwrap();
+                };
+                let existing = Self::lock(databases).get(&db_name).cloned();
+                let restored = match Database::restore_backup(&db_name, &backup_file, &encryption_key) {
+                    Ok(restored) => restored,
+                    Err(error) => return serde_json::to_string(&ServerResponse::Error(error)).unwrap(),
+                };
+                match existing {
+                    // Um banco já aberto só é substituído por quem conhece a chave dele; as
+                    // conexões que o usam passam a ver o conteúdo restaurado.
+                    Some(shared) => {
+                        let mut db = Self::lock(&shared.database);
+                        if !db.has_key(&encryption_key) {
+                            return serde_json::to_string(&ServerResponse::Error("Chave de criptografia inválida ou arquivo corrompido.".to_string())).unwrap();
+                        }
+                        *db = restored;
+                        drop(db);
+                        shared.released.notify_all();
+                    }
+                    None => match Self::lock(databases).entry(db_name.clone()) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(SharedDatabase::new(restored));
+                        }
+                        Entry::Occupied(_) => {
+                            return serde_json::to_string(&ServerResponse::Error(format!("O banco '{}' foi aberto durante a restauração; tente novamente.", db_name))).unwrap();
+                        }
+                    },
                 }
+                serde_json::to_string(&ServerResponse::Success(format!("Backup restaurado com sucesso para '{}'.", db_name))).unwrap()
             }
             Ok(ServerCommand::BeginTransaction) => {
-                let mut databases = databases.lock().unwrap();
-                if let Some(db) = databases.values_mut().next() {
-                    return serde_json::to_string(&ServerResponse::Success(db.begin_transaction())).unwrap();
-                }
-                serde_json::to_string(&ServerResponse::Error("Nenhum banco de dados conectado.".to_string())).unwrap()
+                Self::respond(Self::with_database(databases, session, |db, session| db.begin_transaction(session, None)))
             }
             Ok(ServerCommand::CommitTransaction) => {
-                let mut databases = databases.lock().unwrap();
-                if let Some(db) = databases.values_mut().next() {
-                    return serde_json::to_string(&ServerResponse::Success(db.commit_transaction())).unwrap();
-                }
-                serde_json::to_string(&ServerResponse::Error("Nenhum banco de dados conectado.".to_string())).unwrap()
+                Self::respond(Self::with_database(databases, session, |db, session| db.commit_transaction(session)))
             }
             Ok(ServerCommand::RollbackTransaction) => {
-                let mut databases = databases.lock().unwrap();
-                if let Some(db) = databases.values_mut().next() {
-                    return serde_json::to_string(&ServerResponse::Success(db.rollback_transaction())).unwrap();
-                }
-                serde_json::to_string(&ServerResponse::Error("Nenhum banco de dados conectado.".to_string())).unwrap()
+                Self::respond(Self::with_database(databases, session, |db, session| db.rollback_transaction(session)))
+            }
+            Ok(ServerCommand::Savepoint { name }) => {
+                Self::respond(Self::with_database(databases, session, |db, session| db.create_savepoint(session, &name)))
             }
+            Ok(ServerCommand::RollbackToSavepoint { name }) => {
+                Self::respond(Self::with_database(databases, session, |db, session| db.rollback_to_savepoint(session, &name)))
+            }
+            Ok(ServerCommand::ReleaseSavepoint { name }) => {
+                Self::respond(Self::with_database(databases, session, |db, session| db.release_savepoint(session, &name)))
+            }
+            Ok(ServerCommand::Prepare { name, command }) => {
+                Self::respond(Self::with_database(databases, session, |db, session| db.prepare_command(session, &name, &command)))
+            }
+            Ok(ServerCommand::ExecutePrepared { name, parameters }) => {
+                Self::respond(Self::with_database(databases, session, |db, session| db.execute_prepared(session, &name, parameters.clone())))
+            }
+            Ok(ServerCommand::Deallocate { name }) => match session.deallocate(Some(&name)) {
+                Ok(message) => serde_json::to_string(&ServerResponse::Success(message)).unwrap(),
+                Err(error) => serde_json::to_string(&ServerResponse::Error(error)).unwrap(),
+            },
             Err(_) => serde_json::to_string(&ServerResponse::Error("Comando inválido.".to_string())).unwrap(),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,177 @@
+use crate::query::{self, Plan};
+use crate::sql::{IsolationLevel, Statement, Timeout};
+use crate::transaction::Transaction;
+use crate::value::{DataType, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub struct PreparedStatement {
+    pub statement: Statement,
+    // Tipos declarados em PREPARE.
+    pub parameter_types: Vec<DataType>,
+    // Tipos deduzidos das colunas comparadas ou gravadas, para os parâmetros sem tipo
+    // declarado: um texto recebido é convertido para eles, pelo protocolo ou por EXECUTE.
+    pub inferred_types: Vec<Option<DataType>>,
+    pub parameter_count: usize,
+    // Plano do SELECT e a versão do esquema para a qual foi gerado.
+    pub plan: Option<(u64, Plan)>,
+}
+
+impl PreparedStatement {
+    pub fn bind(&self, name: &str, values: Vec<Value>) -> Result<Vec<Value>, String> {
+        if values.len() != self.parameter_count {
+            return Err(format!(
+                "Comando preparado '{}' espera {} parâmetro(s), mas recebeu {}.",
+                name,
+                self.parameter_count,
+                values.len()
+            ));
+        }
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let data_type = match (self.parameter_types.get(i), &value) {
+                    (Some(data_type), _) => Some(*data_type),
+                    (None, Value::Text(_)) => self.inferred_types.get(i).copied().flatten(),
+                    (None, _) => None,
+                };
+                match data_type {
+                    Some(data_type) => value
+                        .cast(data_type)
+                        .map_err(|e| format!("Parâmetro ${}: {}", i + 1, e)),
+                    None => Ok(value),
+                }
+            })
+            .collect()
+    }
+}
+
+// Estado de uma conexão de cliente.
+pub struct Session {
+    // Banco usado pela conexão, escolhido no Connect.
+    pub database: Option<String>,
+    // Depois de um Disconnect a conexão não usa mais nenhum banco até o próximo Connect.
+    pub disconnected: bool,
+    prepared: HashMap<String, PreparedStatement>,
+    pub transaction: Option<Transaction>,
+    // Nível usado pelas transações iniciadas sem ISOLATION LEVEL.
+    pub isolation_level: IsolationLevel,
+    // Comandos fora de uma transação explícita são confirmados automaticamente.
+    pub autocommit: bool,
+    // O último comando ficou bloqueado esperando um lock: as transações que o bloquearam.
+    // Ele é repetido quando alguma delas terminar.
+    pub waiting: Option<Vec<u64>>,
+    // Tempos limite em milissegundos; zero desativa.
+    statement_timeout: u64,
+    pub idle_in_transaction_timeout: u64,
+    lock_timeout: u64,
+    // Início do comando atual e da espera pelo lock; um comando bloqueado mantém os valores
+    // da primeira tentativa.
+    statement_started: Option<Instant>,
+    lock_wait_started: Option<Instant>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            database: None,
+            disconnected: false,
+            prepared: HashMap::new(),
+            transaction: None,
+            isolation_level: IsolationLevel::default(),
+            autocommit: true,
+            waiting: None,
+            statement_timeout: 0,
+            idle_in_transaction_timeout: 0,
+            lock_timeout: 0,
+            statement_started: None,
+            lock_wait_started: None,
+        }
+    }
+}
+
+impl Session {
+    pub fn prepare(&mut self, name: &str, prepared: PreparedStatement) -> Result<(), String> {
+        if self.prepared.contains_key(name) {
+            return Err(format!("Comando preparado '{}' já existe.", name));
+        }
+        self.prepared.insert(name.to_string(), prepared);
+        Ok(())
+    }
+
+    pub fn prepared_with_transaction(
+        &mut self,
+        name: &str,
+    ) -> Result<(&mut PreparedStatement, Option<&mut Transaction>), String> {
+        let prepared = self
+            .prepared
+            .get_mut(name)
+            .ok_or_else(|| format!("Comando preparado '{}' não existe.", name))?;
+        Ok((prepared, self.transaction.as_mut()))
+    }
+
+    pub fn set_timeout(&mut self, timeout: Timeout, milliseconds: u64) {
+        match timeout {
+            Timeout::Statement => self.statement_timeout = milliseconds,
+            Timeout::IdleInTransaction => self.idle_in_transaction_timeout = milliseconds,
+            Timeout::Lock => self.lock_timeout = milliseconds,
+        }
+    }
+
+    // Momento em que o comando atual deve ser cancelado, com o limite em milissegundos.
+    pub fn statement_deadline(&mut self) -> Option<(Instant, u64)> {
+        let started = *self.statement_started.get_or_insert_with(Instant::now);
+        (self.statement_timeout > 0).then(|| {
+            (
+                started + Duration::from_millis(self.statement_timeout),
+                self.statement_timeout,
+            )
+        })
+    }
+
+    // Erro do comando bloqueado que já esperou mais do que os limites permitem.
+    pub fn wait_expired(&mut self) -> Option<String> {
+        let now = Instant::now();
+        let waiting_since = *self.lock_wait_started.get_or_insert(now);
+        if let Some((deadline, milliseconds)) = self.statement_deadline() {
+            if now >= deadline {
+                return Some(query::statement_timeout_error(milliseconds));
+            }
+        }
+        let limit = Duration::from_millis(self.lock_timeout);
+        (self.lock_timeout > 0 && now.duration_since(waiting_since) >= limit).then(|| {
+            format!(
+                "[55P03] O lock não foi obtido dentro do tempo limite de {} ms (lock_timeout).",
+                self.lock_timeout
+            )
+        })
+    }
+
+    pub fn end_statement(&mut self) {
+        self.statement_started = None;
+        self.lock_wait_started = None;
+    }
+
+    // Por quanto tempo a conexão pode ficar sem enviar comandos com a transação aberta.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        let transaction = self.transaction.as_ref()?;
+        (self.idle_in_transaction_timeout > 0 && transaction.cancelled.is_none())
+            .then(|| Duration::from_millis(self.idle_in_transaction_timeout))
+    }
+
+    pub fn deallocate(&mut self, name: Option<&str>) -> Result<String, String> {
+        match name {
+            Some(name) => {
+                self.prepared
+                    .remove(name)
+                    .ok_or_else(|| format!("Comando preparado '{}' não existe.", name))?;
+                Ok(format!("Comando preparado '{}' removido.", name))
+            }
+            None => {
+                self.prepared.clear();
+                Ok("Comandos preparados removidos.".to_string())
+            }
+        }
+    }
+}
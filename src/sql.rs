@@ -0,0 +1,2951 @@
+use crate::value::{DataType, Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    QuotedIdentifier(String),
+    String(String),
+    Number(String),
+    Parameter(usize),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Identifier(s) | Token::Number(s) => s.clone(),
+            Token::QuotedIdentifier(s) => format!("\"{}\"", s),
+            Token::String(s) => format!("'{}'", s),
+            Token::Parameter(n) => format!("${}", n),
+            Token::Symbol(s) => s.to_string(),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 20] = [
+    "<=", ">=", "<>", "!=", "||", ":=", "(", ")", ",", ";", "*", ".", "+", "-", "/", "%", "=", "<",
+    ">", "?",
+];
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c == '$' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()) {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match text.parse::<usize>() {
+                Ok(n) if n > 0 => tokens.push(Token::Parameter(n)),
+                _ => return Err(format!("Parâmetro ${} inválido.", text)),
+            }
+        } else if c == '\'' || c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("String não terminada.".to_string()),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::String(text)
+            } else {
+                Token::QuotedIdentifier(text)
+            });
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(symbol));
+                    i += symbol.len();
+                }
+                None => return Err(format!("Caractere inesperado '{}'.", c)),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+pub enum Statement {
+    CreateTable(CreateTable),
+    Insert(Insert),
+    Update(Update),
+    Delete(Delete),
+    AlterTable(AlterTable),
+    DropTable(String),
+    CreateSequence(CreateSequence),
+    DropSequence(String),
+    CreateView(CreateView),
+    RefreshMaterializedView(String),
+    DropView {
+        name: String,
+        materialized: bool,
+    },
+    CreateTrigger(CreateTrigger),
+    DropTrigger {
+        name: String,
+        table: String,
+    },
+    CreateProcedure(CreateProcedure),
+    DropProcedure(String),
+    Call {
+        name: String,
+        arguments: Vec<Expr>,
+    },
+    Prepare(Prepare),
+    Execute {
+        name: String,
+        arguments: Vec<Expr>,
+    },
+    // `None` remove todos os comandos preparados da sessão.
+    Deallocate(Option<String>),
+    Explain {
+        analyze: bool,
+        query: Box<Query>,
+    },
+    // `None` analisa todas as tabelas.
+    Analyze(Option<String>),
+    // `None` usa o nível padrão da sessão.
+    Begin(Option<IsolationLevel>),
+    Commit,
+    Rollback,
+    Savepoint(String),
+    RollbackToSavepoint(String),
+    ReleaseSavepoint(String),
+    SetAutocommit(bool),
+    // Commit em duas fases, identificado pelo gid.
+    PrepareTransaction(String),
+    CommitPrepared(String),
+    RollbackPrepared(String),
+    ShowPreparedTransactions,
+    ShowHistory,
+    // Janela do histórico usado por AS OF, em milissegundos; `None` volta ao padrão.
+    SetHistoryRetention(Option<u64>),
+    SetTimeout {
+        timeout: Timeout,
+        milliseconds: u64,
+    },
+    LockTable {
+        table: String,
+        mode: TableLockMode,
+        nowait: bool,
+    },
+    // Com `session`, muda o nível padrão das próximas transações da sessão.
+    SetTransaction {
+        isolation_level: IsolationLevel,
+        session: bool,
+    },
+    Select(Box<Query>),
+}
+
+impl Statement {
+    // Consultas que não alteram nem travam nada podem rodar fora de uma transação.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Statement::Select(query) => query.locking.is_none(),
+            Statement::Explain { .. } => true,
+            _ => false,
+        }
+    }
+
+    // Aplica `Expr::transform` a todas as expressões de um comando de consulta ou escrita.
+    pub fn transform(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> Statement {
+        match self {
+            Statement::Select(query) => Statement::Select(Box::new(query.transform(f))),
+            Statement::Insert(insert) => Statement::Insert(Insert {
+                source: match &insert.source {
+                    InsertSource::Values(rows) => InsertSource::Values(
+                        rows.iter()
+                            .map(|row| row.iter().map(|e| e.transform(f)).collect())
+                            .collect(),
+                    ),
+                    InsertSource::Query(query) => InsertSource::Query(Box::new(query.transform(f))),
+                },
+                on_conflict: insert.on_conflict.as_ref().map(|on_conflict| OnConflict {
+                    target: on_conflict.target.clone(),
+                    action: match &on_conflict.action {
+                        ConflictAction::DoNothing => ConflictAction::DoNothing,
+                        ConflictAction::DoUpdate {
+                            assignments,
+                            selection,
+                        } => ConflictAction::DoUpdate {
+                            assignments: transform_assignments(assignments, f),
+                            selection: selection.as_ref().map(|e| e.transform(f)),
+                        },
+                    },
+                }),
+                returning: transform_items(&insert.returning, f),
+                ..insert.clone()
+            }),
+            Statement::Update(update) => Statement::Update(Update {
+                assignments: transform_assignments(&update.assignments, f),
+                selection: update.selection.as_ref().map(|e| e.transform(f)),
+                returning: transform_items(&update.returning, f),
+                ..update.clone()
+            }),
+            Statement::Delete(delete) => Statement::Delete(Delete {
+                selection: delete.selection.as_ref().map(|e| e.transform(f)),
+                returning: transform_items(&delete.returning, f),
+                ..delete.clone()
+            }),
+            Statement::Call { name, arguments } => Statement::Call {
+                name: name.clone(),
+                arguments: arguments.iter().map(|e| e.transform(f)).collect(),
+            },
+            other => other.clone(),
+        }
+    }
+
+    // Tabelas lidas ou alteradas por um comando de consulta ou escrita.
+    pub fn referenced_tables(&self) -> Vec<String> {
+        match self {
+            Statement::Select(query) => query.referenced_tables(),
+            Statement::Insert(insert) => {
+                let mut names = vec![insert.table.clone()];
+                if let InsertSource::Query(query) = &insert.source {
+                    names.extend(query.referenced_tables());
+                }
+                names
+            }
+            Statement::Update(update) => vec![update.table.clone()],
+            Statement::Delete(delete) => vec![delete.table.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn transform_assignments(
+    assignments: &[Assignment],
+    f: &mut dyn FnMut(&Expr) -> Option<Expr>,
+) -> Vec<Assignment> {
+    assignments
+        .iter()
+        .map(|a| Assignment {
+            column: a.column.clone(),
+            value: a.value.transform(f),
+        })
+        .collect()
+}
+
+fn transform_items(
+    items: &[SelectItem],
+    f: &mut dyn FnMut(&Expr) -> Option<Expr>,
+) -> Vec<SelectItem> {
+    items
+        .iter()
+        .map(|item| match item {
+            SelectItem::Expr { expr, alias } => SelectItem::Expr {
+                expr: expr.transform(f),
+                alias: alias.clone(),
+            },
+            other => other.clone(),
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub data_type: DataType,
+    pub is_primary_key: bool,
+    pub foreign_key: Option<(String, String)>,
+    pub default: Option<Expr>,
+    pub is_unique: bool,
+    pub is_serial: bool,
+    pub is_not_null: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CreateSequence {
+    pub name: String,
+    pub increment: Option<i64>,
+    pub min_value: Option<i64>,
+    pub max_value: Option<i64>,
+    pub start: Option<i64>,
+    pub cycle: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct TableConstraint {
+    pub name: Option<String>,
+    pub kind: ConstraintKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum ConstraintKind {
+    PrimaryKey(Vec<String>),
+    Unique(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateView {
+    pub name: String,
+    pub columns: Option<Vec<String>>,
+    pub query: Box<Query>,
+    pub materialized: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateTrigger {
+    pub name: String,
+    pub timing: TriggerTiming,
+    pub events: Vec<TriggerEvent>,
+    pub table: String,
+    pub condition: Option<Expr>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Prepare {
+    pub name: String,
+    pub parameter_types: Vec<DataType>,
+    pub statement: Box<Statement>,
+    pub parameter_count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateProcedure {
+    pub name: String,
+    pub parameters: Vec<(String, DataType)>,
+    pub body: Vec<ProcedureStatement>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ProcedureStatement {
+    Declare {
+        name: String,
+        data_type: DataType,
+        default: Option<Expr>,
+    },
+    Assign {
+        name: String,
+        value: Expr,
+    },
+    If {
+        branches: Vec<(Expr, Vec<ProcedureStatement>)>,
+        else_branch: Vec<ProcedureStatement>,
+    },
+    While {
+        condition: Expr,
+        body: Vec<ProcedureStatement>,
+    },
+    For {
+        record: String,
+        query: Box<Query>,
+        body: Vec<ProcedureStatement>,
+    },
+    Raise {
+        level: RaiseLevel,
+        message: Expr,
+        arguments: Vec<Expr>,
+    },
+    Return,
+    // Comando SQL comum; `into` recebe a primeira linha de um SELECT ... INTO.
+    Execute {
+        statement: Statement,
+        into: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RaiseLevel {
+    Notice,
+    Exception,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TriggerTiming {
+    Before,
+    After,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TriggerEvent {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl TriggerEvent {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            TriggerEvent::Insert => "INSERT",
+            TriggerEvent::Update => "UPDATE",
+            TriggerEvent::Delete => "DELETE",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Insert {
+    pub table: String,
+    pub alias: Option<String>,
+    pub columns: Option<Vec<String>>,
+    pub source: InsertSource,
+    pub on_conflict: Option<OnConflict>,
+    pub returning: Vec<SelectItem>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Update {
+    pub table: String,
+    pub alias: Option<String>,
+    pub assignments: Vec<Assignment>,
+    pub selection: Option<Expr>,
+    pub returning: Vec<SelectItem>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Delete {
+    pub table: String,
+    pub alias: Option<String>,
+    pub selection: Option<Expr>,
+    pub returning: Vec<SelectItem>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OnConflict {
+    pub target: Option<Vec<String>>,
+    pub action: ConflictAction,
+}
+
+#[derive(Clone, Debug)]
+pub enum ConflictAction {
+    DoNothing,
+    DoUpdate {
+        assignments: Vec<Assignment>,
+        selection: Option<Expr>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Assignment {
+    pub column: String,
+    pub value: Expr,
+}
+
+#[derive(Clone, Debug)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Query(Box<Query>),
+}
+
+#[derive(Clone, Debug)]
+pub struct AlterTable {
+    pub table: String,
+    pub action: AlterTableAction,
+}
+
+#[derive(Clone, Debug)]
+pub enum AlterTableAction {
+    AddColumn(ColumnDefinition),
+    DropColumn(String),
+    RenameTable(String),
+    RenameColumn {
+        old_name: String,
+        new_name: String,
+    },
+    AlterColumn {
+        column: String,
+        change: ColumnChange,
+    },
+    AddConstraint(TableConstraint),
+    DropConstraint(String),
+    SetAutoanalyzeFraction(f64),
+}
+
+#[derive(Clone, Debug)]
+pub enum ColumnChange {
+    SetType(DataType),
+    SetDefault(Expr),
+    DropDefault,
+    SetNotNull,
+    DropNotNull,
+}
+
+#[derive(Clone, Debug)]
+pub struct Query {
+    pub body: SetExpr,
+    pub order_by: Vec<OrderByItem>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+    // FOR UPDATE / FOR SHARE.
+    pub locking: Option<LockClause>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LockClause {
+    pub mode: RowLockMode,
+    pub wait: LockWait,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowLockMode {
+    Share,
+    Update,
+}
+
+impl RowLockMode {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            RowLockMode::Share => "FOR SHARE",
+            RowLockMode::Update => "FOR UPDATE",
+        }
+    }
+}
+
+// O que fazer ao encontrar uma linha ou tabela travada por outra transação.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockWait {
+    Wait,
+    NoWait,
+    SkipLocked,
+}
+
+// Tempos limite configuráveis por sessão.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timeout {
+    Statement,
+    IdleInTransaction,
+    Lock,
+}
+
+impl Timeout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timeout::Statement => "statement_timeout",
+            Timeout::IdleInTransaction => "idle_in_transaction_timeout",
+            Timeout::Lock => "lock_timeout",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableLockMode {
+    RowShare,
+    RowExclusive,
+    Share,
+    Exclusive,
+}
+
+impl TableLockMode {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            TableLockMode::RowShare => "ROW SHARE",
+            TableLockMode::RowExclusive => "ROW EXCLUSIVE",
+            TableLockMode::Share => "SHARE",
+            TableLockMode::Exclusive => "EXCLUSIVE",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SetExpr {
+    Select(Box<Select>),
+    SetOperation {
+        op: SetOperator,
+        all: bool,
+        left: Box<SetExpr>,
+        right: Box<SetExpr>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetOperator {
+    Union,
+    Intersect,
+    Except,
+}
+
+impl SetOperator {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SetOperator::Union => "UNION",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IsolationLevel {
+    #[default]
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Select {
+    pub projection: Vec<SelectItem>,
+    pub from: Option<TableReference>,
+    pub selection: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TableReference {
+    pub name: String,
+    pub alias: Option<String>,
+    pub as_of: Option<AsOf>,
+}
+
+// Estado passado da tabela lido pela consulta.
+#[derive(Clone, Debug)]
+pub enum AsOf {
+    Timestamp(Expr),
+    Transaction(Expr),
+}
+
+impl TableReference {
+    // Nome da tabela no catálogo usado pelo planejamento; a versão passada de uma tabela
+    // entra no catálogo com o texto da referência.
+    pub fn key(&self) -> String {
+        match &self.as_of {
+            Some(as_of) => format!("{} {}", self.name, as_of),
+            None => self.name.clone(),
+        }
+    }
+
+    fn transform(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> TableReference {
+        TableReference {
+            name: self.name.clone(),
+            alias: self.alias.clone(),
+            as_of: self.as_of.as_ref().map(|as_of| match as_of {
+                AsOf::Timestamp(expr) => AsOf::Timestamp(expr.transform(f)),
+                AsOf::Transaction(expr) => AsOf::Transaction(expr.transform(f)),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SelectItem {
+    Wildcard,
+    QualifiedWildcard(String),
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+#[derive(Clone, Debug)]
+pub struct OrderByItem {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Literal(Value),
+    // $1, $2, ... (ou ?) em comandos preparados.
+    Parameter(usize),
+    Unary {
+        op: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOperator,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Function(FunctionCall),
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataType,
+    },
+}
+
+impl Expr {
+    // Reconstrói a expressão substituindo os nós para os quais `f` retorna um valor.
+    pub fn transform(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> Expr {
+        if let Some(replacement) = f(self) {
+            return replacement;
+        }
+        match self {
+            Expr::Column { .. } | Expr::Literal(_) | Expr::Parameter(_) => self.clone(),
+            Expr::Unary { op, expr } => Expr::Unary {
+                op: *op,
+                expr: Box::new(expr.transform(f)),
+            },
+            Expr::Binary { left, op, right } => Expr::Binary {
+                left: Box::new(left.transform(f)),
+                op: *op,
+                right: Box::new(right.transform(f)),
+            },
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: Box::new(expr.transform(f)),
+                negated: *negated,
+            },
+            Expr::Case {
+                operand,
+                branches,
+                else_result,
+            } => Expr::Case {
+                operand: operand.as_ref().map(|e| Box::new(e.transform(f))),
+                branches: branches
+                    .iter()
+                    .map(|(when, then)| (when.transform(f), then.transform(f)))
+                    .collect(),
+                else_result: else_result.as_ref().map(|e| Box::new(e.transform(f))),
+            },
+            Expr::Cast { expr, data_type } => Expr::Cast {
+                expr: Box::new(expr.transform(f)),
+                data_type: *data_type,
+            },
+            Expr::Function(call) => Expr::Function(FunctionCall {
+                name: call.name.clone(),
+                args: call.args.iter().map(|a| a.transform(f)).collect(),
+                wildcard: call.wildcard,
+                over: call.over.as_ref().map(|spec| WindowSpec {
+                    partition_by: spec.partition_by.iter().map(|e| e.transform(f)).collect(),
+                    order_by: spec
+                        .order_by
+                        .iter()
+                        .map(|o| OrderByItem {
+                            expr: o.expr.transform(f),
+                            descending: o.descending,
+                        })
+                        .collect(),
+                    frame: spec.frame.clone(),
+                }),
+            }),
+        }
+    }
+
+    pub fn contains_window_function(&self) -> bool {
+        match self {
+            Expr::Column { .. } | Expr::Literal(_) | Expr::Parameter(_) => false,
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } => {
+                expr.contains_window_function()
+            }
+            Expr::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                operand
+                    .iter()
+                    .chain(else_result)
+                    .any(|e| e.contains_window_function())
+                    || branches.iter().any(|(when, then)| {
+                        when.contains_window_function() || then.contains_window_function()
+                    })
+            }
+            Expr::Binary { left, right, .. } => {
+                left.contains_window_function() || right.contains_window_function()
+            }
+            Expr::Function(call) => {
+                call.over.is_some() || call.args.iter().any(|a| a.contains_window_function())
+            }
+        }
+    }
+}
+
+// Escreve a expressão de volta em SQL; o resultado pode ser lido novamente por `parse_expression`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Column {
+                table: Some(table),
+                name,
+            } => write!(f, "{}.{}", table, name),
+            Expr::Column { table: None, name } => write!(f, "{}", name),
+            Expr::Literal(value) => match value {
+                Value::Null | Value::Boolean(_) | Value::Integer(_) | Value::Float(_) => {
+                    write!(f, "{}", value)
+                }
+                Value::Text(text) => write!(f, "'{}'", text.replace('\'', "''")),
+                _ => write!(f, "{} '{}'", value.data_type().unwrap_or_default(), value),
+            },
+            Expr::Parameter(n) => write!(f, "${}", n),
+            Expr::Unary {
+                op: UnaryOperator::Minus,
+                expr,
+            } => write!(f, "(- {})", expr),
+            Expr::Unary {
+                op: UnaryOperator::Not,
+                expr,
+            } => write!(f, "(NOT {})", expr),
+            Expr::Binary { left, op, right } => write!(f, "({} {} {})", left, op.symbol(), right),
+            Expr::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", expr, if *negated { "NOT " } else { "" })
+            }
+            Expr::Function(call) => write!(f, "{}", call),
+            Expr::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                write!(f, "CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (when, then) in branches {
+                    write!(f, " WHEN {} THEN {}", when, then)?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {}", else_result)?;
+                }
+                write!(f, " END")
+            }
+            Expr::Cast { expr, data_type } => write!(f, "CAST({} AS {})", expr, data_type),
+        }
+    }
+}
+
+impl Query {
+    pub fn transform(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> Query {
+        Query {
+            body: self.body.transform(f),
+            order_by: self
+                .order_by
+                .iter()
+                .map(|o| OrderByItem {
+                    expr: o.expr.transform(f),
+                    descending: o.descending,
+                })
+                .collect(),
+            limit: self.limit.as_ref().map(|e| e.transform(f)),
+            offset: self.offset.as_ref().map(|e| e.transform(f)),
+            locking: self.locking,
+        }
+    }
+
+    // Tabelas e views lidas pela consulta, na ordem em que aparecem.
+    pub fn referenced_tables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.body.collect_tables(&mut names);
+        names
+    }
+
+    // Diz se a consulta usa a coluna da tabela: por um `*` sobre ela ou pelo nome, em qualquer
+    // expressão. Sem saber a que tabela cada nome pertence, qualquer coluna com o nome conta.
+    pub fn uses_column(&self, table: &str, column: &str) -> bool {
+        let mut used = self.body.selects_all_from(table);
+        self.transform(&mut |e| {
+            used |= matches!(e, Expr::Column { name, .. } if name == column);
+            None
+        });
+        used
+    }
+
+    // Referências que leem um estado passado com AS OF.
+    pub fn as_of_references(&self) -> Vec<&TableReference> {
+        let mut references = Vec::new();
+        self.body.collect_as_of(&mut references);
+        references
+    }
+}
+
+impl SetExpr {
+    fn transform(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> SetExpr {
+        match self {
+            SetExpr::Select(select) => SetExpr::Select(Box::new(Select {
+                projection: transform_items(&select.projection, f),
+                from: select.from.as_ref().map(|from| from.transform(f)),
+                selection: select.selection.as_ref().map(|e| e.transform(f)),
+            })),
+            SetExpr::SetOperation {
+                op,
+                all,
+                left,
+                right,
+            } => SetExpr::SetOperation {
+                op: *op,
+                all: *all,
+                left: Box::new(left.transform(f)),
+                right: Box::new(right.transform(f)),
+            },
+        }
+    }
+
+    fn selects_all_from(&self, table: &str) -> bool {
+        match self {
+            SetExpr::Select(select) => {
+                select.from.as_ref().is_some_and(|from| from.name == table)
+                    && select.projection.iter().any(|item| {
+                        matches!(
+                            item,
+                            SelectItem::Wildcard | SelectItem::QualifiedWildcard(_)
+                        )
+                    })
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                left.selects_all_from(table) || right.selects_all_from(table)
+            }
+        }
+    }
+
+    fn collect_tables(&self, names: &mut Vec<String>) {
+        match self {
+            SetExpr::Select(select) => {
+                if let Some(from) = &select.from {
+                    if !names.contains(&from.name) {
+                        names.push(from.name.clone());
+                    }
+                }
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                left.collect_tables(names);
+                right.collect_tables(names);
+            }
+        }
+    }
+
+    fn collect_as_of<'a>(&'a self, references: &mut Vec<&'a TableReference>) {
+        match self {
+            SetExpr::Select(select) => {
+                references.extend(select.from.iter().filter(|from| from.as_of.is_some()));
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                left.collect_as_of(references);
+                right.collect_as_of(references);
+            }
+        }
+    }
+}
+
+impl fmt::Display for AsOf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsOf::Timestamp(expr) => write!(f, "AS OF TIMESTAMP {}", expr),
+            AsOf::Transaction(expr) => write!(f, "AS OF TRANSACTION {}", expr),
+        }
+    }
+}
+
+// Escreve a consulta de volta em SQL; usado para guardar a definição das views.
+impl fmt::Display for LockClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mode.keyword())?;
+        match self.wait {
+            LockWait::Wait => Ok(()),
+            LockWait::NoWait => write!(f, " NOWAIT"),
+            LockWait::SkipLocked => write!(f, " SKIP LOCKED"),
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.body)?;
+        if !self.order_by.is_empty() {
+            let order: Vec<String> = self.order_by.iter().map(|o| o.to_string()).collect();
+            write!(f, " ORDER BY {}", order.join(", "))?;
+        }
+        if let Some(limit) = &self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if let Some(offset) = &self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+        if let Some(locking) = &self.locking {
+            write!(f, " {}", locking)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SetExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SetExpr::Select(select) => write!(f, "{}", select),
+            SetExpr::SetOperation {
+                op,
+                all,
+                left,
+                right,
+            } => {
+                let operand = |expr: &SetExpr| match expr {
+                    SetExpr::Select(_) => expr.to_string(),
+                    _ => format!("({})", expr),
+                };
+                write!(
+                    f,
+                    "{} {}{} {}",
+                    operand(left),
+                    op.keyword(),
+                    if *all { " ALL" } else { "" },
+                    operand(right)
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for Select {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items: Vec<String> = self.projection.iter().map(|i| i.to_string()).collect();
+        write!(f, "SELECT {}", items.join(", "))?;
+        if let Some(from) = &self.from {
+            write!(f, " FROM {}", from.name)?;
+            if let Some(as_of) = &from.as_of {
+                write!(f, " {}", as_of)?;
+            }
+            if let Some(alias) = &from.alias {
+                write!(f, " AS {}", alias)?;
+            }
+        }
+        if let Some(selection) = &self.selection {
+            write!(f, " WHERE {}", selection)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::QualifiedWildcard(table) => write!(f, "{}.*", table),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+            SelectItem::Expr {
+                expr,
+                alias: Some(alias),
+            } => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+
+impl fmt::Display for FunctionCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|a| a.to_string()).collect();
+        if self.name == "EXTRACT" && args.len() == 2 {
+            write!(
+                f,
+                "EXTRACT({} FROM {})",
+                args[0].trim_matches('\''),
+                args[1]
+            )?;
+        } else if self.wildcard {
+            write!(f, "{}(*)", self.name)?;
+        } else {
+            write!(f, "{}({})", self.name, args.join(", "))?;
+        }
+        if let Some(spec) = &self.over {
+            let mut clauses = Vec::new();
+            if !spec.partition_by.is_empty() {
+                let partition: Vec<String> =
+                    spec.partition_by.iter().map(|e| e.to_string()).collect();
+                clauses.push(format!("PARTITION BY {}", partition.join(", ")));
+            }
+            if !spec.order_by.is_empty() {
+                let order: Vec<String> = spec.order_by.iter().map(|o| o.to_string()).collect();
+                clauses.push(format!("ORDER BY {}", order.join(", ")));
+            }
+            if let Some(frame) = &spec.frame {
+                clauses.push(frame.to_string());
+            }
+            write!(f, " OVER ({})", clauses.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OrderByItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.expr,
+            if self.descending { " DESC" } else { "" }
+        )
+    }
+}
+
+impl fmt::Display for WindowFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bound = |bound: FrameBound| match bound {
+            FrameBound::UnboundedPreceding => "UNBOUNDED PRECEDING".to_string(),
+            FrameBound::Preceding(n) => format!("{} PRECEDING", n),
+            FrameBound::CurrentRow => "CURRENT ROW".to_string(),
+            FrameBound::Following(n) => format!("{} FOLLOWING", n),
+            FrameBound::UnboundedFollowing => "UNBOUNDED FOLLOWING".to_string(),
+        };
+        let units = match self.units {
+            FrameUnits::Rows => "ROWS",
+            FrameUnits::Range => "RANGE",
+        };
+        write!(
+            f,
+            "{} BETWEEN {} AND {}",
+            units,
+            bound(self.start),
+            bound(self.end)
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub wildcard: bool,
+    pub over: Option<WindowSpec>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowSpec {
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderByItem>,
+    pub frame: Option<WindowFrame>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameUnits {
+    Rows,
+    Range,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(usize),
+    CurrentRow,
+    Following(usize),
+    UnboundedFollowing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOperator {
+    Minus,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOperator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Concat,
+}
+
+impl BinaryOperator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Plus => "+",
+            BinaryOperator::Minus => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Eq => "=",
+            BinaryOperator::NotEq => "<>",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::LtEq => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::GtEq => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+            BinaryOperator::Concat => "||",
+        }
+    }
+}
+
+// Palavras que encerram uma expressão e não podem ser usadas como alias implícito.
+const RESERVED: [&str; 32] = [
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "AND",
+    "OR",
+    "NOT",
+    "AS",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "ALL",
+    "ORDER",
+    "BY",
+    "LIMIT",
+    "OFFSET",
+    "ASC",
+    "DESC",
+    "IS",
+    "NULL",
+    "VALUES",
+    "INTO",
+    "CASE",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "END",
+    "DEFAULT",
+    "ON",
+    "SET",
+    "RETURNING",
+    "LOOP",
+    "FOR",
+];
+
+pub fn parse(command: &str) -> Result<Statement, String> {
+    let (statement, parameter_count) = parse_prepared(command)?;
+    if parameter_count > 0 && !matches!(statement, Statement::Prepare(_)) {
+        return Err("Parâmetros só podem ser usados em comandos preparados.".to_string());
+    }
+    Ok(statement)
+}
+
+// Lê um comando que pode conter parâmetros e devolve também quantos ele espera.
+pub fn parse_prepared(command: &str) -> Result<(Statement, usize), String> {
+    let mut parser = Parser::new(tokenize(command)?);
+    let statement = parser.parse_statement()?;
+    parser.consume_symbol(";");
+    parser.expect_end()?;
+    Ok((statement, parser.parameter_count))
+}
+
+// Usado para reler expressões gravadas como texto no catálogo, como o DEFAULT de uma coluna.
+pub fn parse_expression(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser::new(tokenize(text)?);
+    let expr = parser.parse_expr()?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // Maior parâmetro encontrado e quantos '?' já foram numerados.
+    parameter_count: usize,
+    placeholders: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            parameter_count: 0,
+            placeholders: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + n)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(token) => format!("'{}'", token.describe()),
+            None => "fim do comando".to_string(),
+        }
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, String> {
+        Err(format!(
+            "Erro de sintaxe: esperado {}, encontrado {}.",
+            expected,
+            self.found()
+        ))
+    }
+
+    pub fn expect_end(&self) -> Result<(), String> {
+        if self.peek().is_some() {
+            return self.error("fim do comando");
+        }
+        Ok(())
+    }
+
+    fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+        matches!(token, Some(Token::Identifier(s)) if s.eq_ignore_ascii_case(keyword))
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        Self::is_keyword(self.peek(), keyword)
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.consume_keyword(keyword) {
+            return Ok(());
+        }
+        self.error(&format!("'{}'", keyword))
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn consume_symbol(&mut self, symbol: &str) -> bool {
+        if self.peek_symbol(symbol) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.consume_symbol(symbol) {
+            return Ok(());
+        }
+        self.error(&format!("'{}'", symbol))
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Identifier(name)) | Some(Token::QuotedIdentifier(name)) => {
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.error("um identificador"),
+        }
+    }
+
+    fn parse_alias(&mut self) -> Result<Option<String>, String> {
+        if self.consume_keyword("AS") {
+            return self.parse_identifier().map(Some);
+        }
+        match self.peek() {
+            Some(Token::Identifier(name))
+                if !RESERVED.iter().any(|r| name.eq_ignore_ascii_case(r)) =>
+            {
+                self.parse_identifier().map(Some)
+            }
+            Some(Token::QuotedIdentifier(_)) => self.parse_identifier().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_comma_separated<T>(
+        &mut self,
+        mut parse_item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let mut items = vec![parse_item(self)?];
+        while self.consume_symbol(",") {
+            items.push(parse_item(self)?);
+        }
+        Ok(items)
+    }
+
+    pub fn parse_statement(&mut self) -> Result<Statement, String> {
+        if self.peek_keyword("SELECT") || self.peek_symbol("(") {
+            return Ok(Statement::Select(Box::new(self.parse_query()?)));
+        }
+        if self.consume_keyword("CREATE") {
+            if self.consume_keyword("TABLE") {
+                return self.parse_create_table();
+            }
+            if self.consume_keyword("SEQUENCE") {
+                return self.parse_create_sequence();
+            }
+            if self.consume_keyword("MATERIALIZED") {
+                self.expect_keyword("VIEW")?;
+                return self.parse_create_view(true);
+            }
+            if self.consume_keyword("VIEW") {
+                return self.parse_create_view(false);
+            }
+            if self.consume_keyword("TRIGGER") {
+                return self.parse_create_trigger();
+            }
+            if self.consume_keyword("PROCEDURE") {
+                return self.parse_create_procedure();
+            }
+            return self.error("'TABLE', 'VIEW', 'SEQUENCE', 'TRIGGER' ou 'PROCEDURE'");
+        }
+        if self.consume_keyword("EXPLAIN") {
+            let analyze = self.consume_keyword("ANALYZE");
+            return match self.parse_statement()? {
+                Statement::Select(query) => Ok(Statement::Explain { analyze, query }),
+                _ => Err("EXPLAIN aceita apenas consultas SELECT.".to_string()),
+            };
+        }
+        if self.consume_keyword("ANALYZE") {
+            let table = match self.peek() {
+                Some(Token::Identifier(_) | Token::QuotedIdentifier(_)) => {
+                    Some(self.parse_identifier()?)
+                }
+                _ => None,
+            };
+            return Ok(Statement::Analyze(table));
+        }
+        if self.consume_keyword("BEGIN") {
+            if !self.consume_keyword("TRANSACTION") {
+                self.consume_keyword("WORK");
+            }
+            return self.parse_begin();
+        }
+        if self.consume_keyword("START") {
+            self.expect_keyword("TRANSACTION")?;
+            return self.parse_begin();
+        }
+        if self.consume_keyword("COMMIT") {
+            if self.consume_keyword("PREPARED") {
+                return Ok(Statement::CommitPrepared(self.parse_gid()?));
+            }
+            if !self.consume_keyword("TRANSACTION") {
+                self.consume_keyword("WORK");
+            }
+            return Ok(Statement::Commit);
+        }
+        if self.consume_keyword("ROLLBACK") {
+            if self.consume_keyword("PREPARED") {
+                return Ok(Statement::RollbackPrepared(self.parse_gid()?));
+            }
+            if !self.consume_keyword("TRANSACTION") {
+                self.consume_keyword("WORK");
+            }
+            if self.consume_keyword("TO") {
+                self.consume_keyword("SAVEPOINT");
+                return Ok(Statement::RollbackToSavepoint(self.parse_identifier()?));
+            }
+            return Ok(Statement::Rollback);
+        }
+        if self.consume_keyword("LOCK") {
+            return self.parse_lock_table();
+        }
+        if self.consume_keyword("SAVEPOINT") {
+            return Ok(Statement::Savepoint(self.parse_identifier()?));
+        }
+        if self.consume_keyword("RELEASE") {
+            self.consume_keyword("SAVEPOINT");
+            return Ok(Statement::ReleaseSavepoint(self.parse_identifier()?));
+        }
+        if self.consume_keyword("SET") {
+            if self.consume_keyword("AUTOCOMMIT") {
+                if !self.consume_symbol("=") {
+                    self.consume_keyword("TO");
+                }
+                if self.consume_keyword("ON") {
+                    return Ok(Statement::SetAutocommit(true));
+                }
+                if self.consume_keyword("OFF") {
+                    return Ok(Statement::SetAutocommit(false));
+                }
+                return self.error("'ON' ou 'OFF'");
+            }
+            if self.consume_keyword("HISTORY_RETENTION") {
+                if !self.consume_symbol("=") {
+                    self.consume_keyword("TO");
+                }
+                if self.consume_keyword("DEFAULT") {
+                    return Ok(Statement::SetHistoryRetention(None));
+                }
+                return Ok(Statement::SetHistoryRetention(Some(
+                    self.parse_milliseconds("history_retention")?,
+                )));
+            }
+            if let Some(timeout) = [
+                Timeout::Statement,
+                Timeout::IdleInTransaction,
+                Timeout::Lock,
+            ]
+            .into_iter()
+            .find(|timeout| self.peek_keyword(timeout.name()))
+            {
+                self.pos += 1;
+                if !self.consume_symbol("=") {
+                    self.consume_keyword("TO");
+                }
+                return Ok(Statement::SetTimeout {
+                    timeout,
+                    milliseconds: self.parse_milliseconds(timeout.name())?,
+                });
+            }
+            let session = self.consume_keyword("SESSION");
+            if session {
+                self.expect_keyword("CHARACTERISTICS")?;
+                self.expect_keyword("AS")?;
+            }
+            self.expect_keyword("TRANSACTION")?;
+            return Ok(Statement::SetTransaction {
+                isolation_level: self.parse_isolation_level()?,
+                session,
+            });
+        }
+        if self.consume_keyword("PREPARE") {
+            if self.peek_keyword("TRANSACTION")
+                && matches!(self.peek_nth(1), Some(Token::String(_)))
+            {
+                self.pos += 1;
+                return Ok(Statement::PrepareTransaction(self.parse_gid()?));
+            }
+            return self.parse_prepare();
+        }
+        if self.consume_keyword("SHOW") {
+            if self.consume_keyword("HISTORY") {
+                return Ok(Statement::ShowHistory);
+            }
+            self.expect_keyword("PREPARED")?;
+            self.expect_keyword("TRANSACTIONS")?;
+            return Ok(Statement::ShowPreparedTransactions);
+        }
+        if self.consume_keyword("EXECUTE") {
+            let name = self.parse_identifier()?;
+            let mut arguments = Vec::new();
+            if self.consume_symbol("(") {
+                arguments = self.parse_comma_separated(Self::parse_expr)?;
+                self.expect_symbol(")")?;
+            }
+            return Ok(Statement::Execute { name, arguments });
+        }
+        if self.consume_keyword("DEALLOCATE") {
+            self.consume_keyword("PREPARE");
+            if self.consume_keyword("ALL") {
+                return Ok(Statement::Deallocate(None));
+            }
+            return Ok(Statement::Deallocate(Some(self.parse_identifier()?)));
+        }
+        if self.consume_keyword("CALL") {
+            let name = self.parse_identifier()?;
+            self.expect_symbol("(")?;
+            let mut arguments = Vec::new();
+            if !self.peek_symbol(")") {
+                arguments = self.parse_comma_separated(Self::parse_expr)?;
+            }
+            self.expect_symbol(")")?;
+            return Ok(Statement::Call { name, arguments });
+        }
+        if self.consume_keyword("REFRESH") {
+            self.expect_keyword("MATERIALIZED")?;
+            self.expect_keyword("VIEW")?;
+            return Ok(Statement::RefreshMaterializedView(self.parse_identifier()?));
+        }
+        if self.consume_keyword("INSERT") {
+            return self.parse_insert();
+        }
+        if self.consume_keyword("UPDATE") {
+            return self.parse_update();
+        }
+        if self.consume_keyword("DELETE") {
+            return self.parse_delete();
+        }
+        if self.consume_keyword("ALTER") {
+            return self.parse_alter_table();
+        }
+        if self.consume_keyword("DROP") {
+            if self.consume_keyword("SEQUENCE") {
+                return Ok(Statement::DropSequence(self.parse_identifier()?));
+            }
+            if self.consume_keyword("PROCEDURE") {
+                return Ok(Statement::DropProcedure(self.parse_identifier()?));
+            }
+            if self.consume_keyword("TRIGGER") {
+                let name = self.parse_identifier()?;
+                self.expect_keyword("ON")?;
+                return Ok(Statement::DropTrigger {
+                    name,
+                    table: self.parse_identifier()?,
+                });
+            }
+            let materialized = self.consume_keyword("MATERIALIZED");
+            if materialized || self.consume_keyword("VIEW") {
+                if materialized {
+                    self.expect_keyword("VIEW")?;
+                }
+                return Ok(Statement::DropView {
+                    name: self.parse_identifier()?,
+                    materialized,
+                });
+            }
+            self.expect_keyword("TABLE")?;
+            return Ok(Statement::DropTable(self.parse_identifier()?));
+        }
+        Err("Comando SQL não suportado.".to_string())
+    }
+
+    fn parse_create_table(&mut self) -> Result<Statement, String> {
+        let name = self.parse_identifier()?;
+        self.expect_symbol("(")?;
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+        loop {
+            if self.peek_table_constraint() {
+                constraints.push(self.parse_table_constraint()?);
+            } else {
+                columns.push(self.parse_column_definition()?);
+            }
+            if !self.consume_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(")")?;
+        Ok(Statement::CreateTable(CreateTable {
+            name,
+            columns,
+            constraints,
+        }))
+    }
+
+    fn peek_table_constraint(&self) -> bool {
+        self.peek_keyword("CONSTRAINT")
+            || (self.peek_keyword("PRIMARY") && Self::is_keyword(self.peek_nth(1), "KEY"))
+            || (self.peek_keyword("UNIQUE") && self.peek_nth(1) == Some(&Token::Symbol("(")))
+    }
+
+    fn parse_table_constraint(&mut self) -> Result<TableConstraint, String> {
+        let name = if self.consume_keyword("CONSTRAINT") {
+            Some(self.parse_identifier()?)
+        } else {
+            None
+        };
+        let kind = if self.consume_keyword("PRIMARY") {
+            self.expect_keyword("KEY")?;
+            ConstraintKind::PrimaryKey(self.parse_column_list()?)
+        } else if self.consume_keyword("UNIQUE") {
+            ConstraintKind::Unique(self.parse_column_list()?)
+        } else {
+            return self.error("'PRIMARY KEY' ou 'UNIQUE'");
+        };
+        Ok(TableConstraint { name, kind })
+    }
+
+    fn parse_column_list(&mut self) -> Result<Vec<String>, String> {
+        self.expect_symbol("(")?;
+        let columns = self.parse_comma_separated(Self::parse_identifier)?;
+        self.expect_symbol(")")?;
+        Ok(columns)
+    }
+
+    fn parse_column_definition(&mut self) -> Result<ColumnDefinition, String> {
+        let name = self.parse_identifier()?;
+        let mut column = ColumnDefinition {
+            name,
+            data_type: DataType::Text,
+            is_primary_key: false,
+            foreign_key: None,
+            default: None,
+            is_unique: false,
+            is_serial: false,
+            is_not_null: false,
+        };
+
+        let mut typed = false;
+        if let Some(Token::Identifier(type_name)) = self.peek() {
+            if ["SERIAL", "BIGSERIAL", "SMALLSERIAL"]
+                .iter()
+                .any(|t| type_name.eq_ignore_ascii_case(t))
+            {
+                self.pos += 1;
+                column.data_type = DataType::Integer;
+                column.is_serial = true;
+                typed = true;
+            } else if DataType::from_name(type_name).is_some() {
+                column.data_type = self.parse_data_type()?;
+                typed = true;
+            }
+        }
+
+        loop {
+            if self.consume_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                column.is_primary_key = true;
+            } else if self.consume_keyword("FOREIGN") {
+                self.expect_keyword("KEY")?;
+                column.foreign_key = Some(self.parse_references()?);
+            } else if self.peek_keyword("REFERENCES") {
+                column.foreign_key = Some(self.parse_references()?);
+            } else if self.consume_keyword("DEFAULT") {
+                column.default = Some(self.parse_expr()?);
+            } else if self.consume_keyword("UNIQUE") {
+                column.is_unique = true;
+            } else if self.consume_keyword("NOT") {
+                self.expect_keyword("NULL")?;
+                column.is_not_null = true;
+            } else if self.consume_keyword("NULL") {
+                column.is_not_null = false;
+            } else if self.consume_keyword("AUTOINCREMENT")
+                || self.consume_keyword("AUTO_INCREMENT")
+            {
+                if typed && column.data_type != DataType::Integer {
+                    return Err(format!(
+                        "AUTOINCREMENT exige uma coluna INTEGER, mas '{}' é {}.",
+                        column.name, column.data_type
+                    ));
+                }
+                column.data_type = DataType::Integer;
+                column.is_serial = true;
+            } else {
+                break;
+            }
+        }
+        Ok(column)
+    }
+
+    fn parse_create_view(&mut self, materialized: bool) -> Result<Statement, String> {
+        let name = self.parse_identifier()?;
+        let columns = if self.peek_symbol("(") {
+            Some(self.parse_column_list()?)
+        } else {
+            None
+        };
+        self.expect_keyword("AS")?;
+        Ok(Statement::CreateView(CreateView {
+            name,
+            columns,
+            query: Box::new(self.parse_query()?),
+            materialized,
+        }))
+    }
+
+    fn parse_create_trigger(&mut self) -> Result<Statement, String> {
+        let name = self.parse_identifier()?;
+        let timing = if self.consume_keyword("BEFORE") {
+            TriggerTiming::Before
+        } else if self.consume_keyword("AFTER") {
+            TriggerTiming::After
+        } else {
+            return self.error("'BEFORE' ou 'AFTER'");
+        };
+        let mut events = Vec::new();
+        loop {
+            let event = if self.consume_keyword("INSERT") {
+                TriggerEvent::Insert
+            } else if self.consume_keyword("UPDATE") {
+                TriggerEvent::Update
+            } else if self.consume_keyword("DELETE") {
+                TriggerEvent::Delete
+            } else {
+                return self.error("'INSERT', 'UPDATE' ou 'DELETE'");
+            };
+            if !events.contains(&event) {
+                events.push(event);
+            }
+            if !self.consume_keyword("OR") {
+                break;
+            }
+        }
+        self.expect_keyword("ON")?;
+        let table = self.parse_identifier()?;
+        self.expect_keyword("FOR")?;
+        self.expect_keyword("EACH")?;
+        self.expect_keyword("ROW")?;
+        let condition = if self.consume_keyword("WHEN") {
+            self.expect_symbol("(")?;
+            let condition = self.parse_expr()?;
+            self.expect_symbol(")")?;
+            Some(condition)
+        } else {
+            None
+        };
+
+        // Corpo: BEGIN <comando>; <comando>; ... END
+        self.expect_keyword("BEGIN")?;
+        let mut body = Vec::new();
+        while !self.consume_keyword("END") {
+            let statement = self.parse_statement()?;
+            if !matches!(
+                statement,
+                Statement::Insert(_)
+                    | Statement::Update(_)
+                    | Statement::Delete(_)
+                    | Statement::Call { .. }
+            ) {
+                return Err(
+                    "O corpo de um gatilho aceita apenas INSERT, UPDATE, DELETE e CALL."
+                        .to_string(),
+                );
+            }
+            body.push(statement);
+            self.expect_symbol(";")?;
+        }
+        if body.is_empty() {
+            return Err("O corpo do gatilho está vazio.".to_string());
+        }
+        Ok(Statement::CreateTrigger(CreateTrigger {
+            name,
+            timing,
+            events,
+            table,
+            condition,
+            body,
+        }))
+    }
+
+    fn parse_gid(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::String(gid)) if !gid.is_empty() => Ok(gid),
+            _ => {
+                self.pos -= 1;
+                self.error("o identificador da transação entre aspas simples")
+            }
+        }
+    }
+
+    // Um número de milissegundos ou um texto com unidade ('500ms', '5s', '2min', '1h');
+    // zero ou DEFAULT desativam o limite.
+    fn parse_milliseconds(&mut self, setting: &str) -> Result<u64, String> {
+        if self.consume_keyword("DEFAULT") {
+            return Ok(0);
+        }
+        let text = match self.next() {
+            Some(Token::Number(text)) | Some(Token::String(text)) => text,
+            _ => {
+                self.pos -= 1;
+                return self.error("um tempo em milissegundos");
+            }
+        };
+        let invalid = || format!("Valor inválido para {}: '{}'.", setting, text);
+        let trimmed = text.trim();
+        let (number, unit) = trimmed.split_at(
+            trimmed
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(trimmed.len()),
+        );
+        let factor = match unit.trim().to_lowercase().as_str() {
+            "" | "ms" => 1,
+            "s" => 1000,
+            "min" => 60_000,
+            "h" => 3_600_000,
+            _ => return Err(invalid()),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(factor))
+            .ok_or_else(invalid)
+    }
+
+    // LOCK [TABLE] <tabela> [IN <modo> MODE] [NOWAIT]
+    fn parse_lock_table(&mut self) -> Result<Statement, String> {
+        self.consume_keyword("TABLE");
+        let table = self.parse_identifier()?;
+        let mut mode = TableLockMode::Exclusive;
+        if self.consume_keyword("IN") {
+            mode = if self.consume_keyword("ROW") {
+                if self.consume_keyword("SHARE") {
+                    TableLockMode::RowShare
+                } else {
+                    self.expect_keyword("EXCLUSIVE")?;
+                    TableLockMode::RowExclusive
+                }
+            } else if self.consume_keyword("SHARE") {
+                TableLockMode::Share
+            } else if self.consume_keyword("EXCLUSIVE") {
+                TableLockMode::Exclusive
+            } else {
+                return self.error("'ROW SHARE', 'ROW EXCLUSIVE', 'SHARE' ou 'EXCLUSIVE'");
+            };
+            self.expect_keyword("MODE")?;
+        }
+        Ok(Statement::LockTable {
+            table,
+            mode,
+            nowait: self.consume_keyword("NOWAIT"),
+        })
+    }
+
+    fn parse_begin(&mut self) -> Result<Statement, String> {
+        if !self.peek_keyword("ISOLATION") {
+            return Ok(Statement::Begin(None));
+        }
+        Ok(Statement::Begin(Some(self.parse_isolation_level()?)))
+    }
+
+    // ISOLATION LEVEL { READ COMMITTED | REPEATABLE READ | SERIALIZABLE }
+    fn parse_isolation_level(&mut self) -> Result<IsolationLevel, String> {
+        self.expect_keyword("ISOLATION")?;
+        self.expect_keyword("LEVEL")?;
+        if self.consume_keyword("SERIALIZABLE") {
+            return Ok(IsolationLevel::Serializable);
+        }
+        if self.consume_keyword("REPEATABLE") {
+            self.expect_keyword("READ")?;
+            return Ok(IsolationLevel::RepeatableRead);
+        }
+        if self.consume_keyword("READ") {
+            self.expect_keyword("COMMITTED")?;
+            return Ok(IsolationLevel::ReadCommitted);
+        }
+        self.error("'READ COMMITTED', 'REPEATABLE READ' ou 'SERIALIZABLE'")
+    }
+
+    fn parse_prepare(&mut self) -> Result<Statement, String> {
+        let name = self.parse_identifier()?;
+        let mut parameter_types = Vec::new();
+        if self.consume_symbol("(") {
+            parameter_types = self.parse_comma_separated(Self::parse_data_type)?;
+            self.expect_symbol(")")?;
+        }
+        self.expect_keyword("AS")?;
+        let statement = self.parse_statement()?;
+        Ok(Statement::Prepare(Prepare {
+            name,
+            parameter_types,
+            statement: Box::new(statement),
+            parameter_count: self.parameter_count,
+        }))
+    }
+
+    fn parse_create_procedure(&mut self) -> Result<Statement, String> {
+        let name = self.parse_identifier()?;
+        self.expect_symbol("(")?;
+        let mut parameters = Vec::new();
+        if !self.peek_symbol(")") {
+            parameters =
+                self.parse_comma_separated(|p| Ok((p.parse_identifier()?, p.parse_data_type()?)))?;
+        }
+        self.expect_symbol(")")?;
+        self.consume_keyword("AS");
+        self.expect_keyword("BEGIN")?;
+        let body = self.parse_procedure_block(&["END"])?;
+        self.expect_keyword("END")?;
+        Ok(Statement::CreateProcedure(CreateProcedure {
+            name,
+            parameters,
+            body,
+        }))
+    }
+
+    // Lê comandos terminados por ';' até uma das palavras que encerram o bloco.
+    fn parse_procedure_block(
+        &mut self,
+        terminators: &[&str],
+    ) -> Result<Vec<ProcedureStatement>, String> {
+        let mut body = Vec::new();
+        while !terminators.iter().any(|t| self.peek_keyword(t)) {
+            if self.peek().is_none() {
+                return self.error(&format!("'{}'", terminators[terminators.len() - 1]));
+            }
+            body.push(self.parse_procedure_statement()?);
+            self.expect_symbol(";")?;
+        }
+        Ok(body)
+    }
+
+    fn parse_procedure_statement(&mut self) -> Result<ProcedureStatement, String> {
+        if self.consume_keyword("DECLARE") {
+            let name = self.parse_identifier()?;
+            let data_type = self.parse_data_type()?;
+            let default = if self.consume_symbol(":=") || self.consume_keyword("DEFAULT") {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            return Ok(ProcedureStatement::Declare {
+                name,
+                data_type,
+                default,
+            });
+        }
+        if self.consume_keyword("IF") {
+            let mut branches = Vec::new();
+            loop {
+                let condition = self.parse_expr()?;
+                self.expect_keyword("THEN")?;
+                let body = self.parse_procedure_block(&["ELSIF", "ELSE", "END"])?;
+                branches.push((condition, body));
+                if !self.consume_keyword("ELSIF") {
+                    break;
+                }
+            }
+            let else_branch = if self.consume_keyword("ELSE") {
+                self.parse_procedure_block(&["END"])?
+            } else {
+                Vec::new()
+            };
+            self.expect_keyword("END")?;
+            self.expect_keyword("IF")?;
+            return Ok(ProcedureStatement::If {
+                branches,
+                else_branch,
+            });
+        }
+        if self.consume_keyword("WHILE") {
+            let condition = self.parse_expr()?;
+            let body = self.parse_loop_body()?;
+            return Ok(ProcedureStatement::While { condition, body });
+        }
+        if self.consume_keyword("FOR") {
+            let record = self.parse_identifier()?;
+            self.expect_keyword("IN")?;
+            let query = Box::new(self.parse_query()?);
+            let body = self.parse_loop_body()?;
+            return Ok(ProcedureStatement::For {
+                record,
+                query,
+                body,
+            });
+        }
+        if self.consume_keyword("RAISE") {
+            let level = if self.consume_keyword("NOTICE") {
+                RaiseLevel::Notice
+            } else {
+                self.consume_keyword("EXCEPTION");
+                RaiseLevel::Exception
+            };
+            let message = self.parse_expr()?;
+            let mut arguments = Vec::new();
+            while self.consume_symbol(",") {
+                arguments.push(self.parse_expr()?);
+            }
+            return Ok(ProcedureStatement::Raise {
+                level,
+                message,
+                arguments,
+            });
+        }
+        if self.consume_keyword("RETURN") {
+            return Ok(ProcedureStatement::Return);
+        }
+        if let (
+            Some(Token::Identifier(_) | Token::QuotedIdentifier(_)),
+            Some(Token::Symbol(":=")),
+        ) = (self.peek(), self.peek_nth(1))
+        {
+            let name = self.parse_identifier()?;
+            self.pos += 1;
+            return Ok(ProcedureStatement::Assign {
+                name,
+                value: self.parse_expr()?,
+            });
+        }
+
+        let statement = self.parse_statement()?;
+        let into = match statement {
+            Statement::Select(_) if self.consume_keyword("INTO") => {
+                self.parse_comma_separated(Self::parse_identifier)?
+            }
+            Statement::Select(_)
+            | Statement::Insert(_)
+            | Statement::Update(_)
+            | Statement::Delete(_)
+            | Statement::Call { .. } => Vec::new(),
+            _ => {
+                return Err(
+                    "Um procedimento aceita apenas SELECT, INSERT, UPDATE, DELETE e CALL."
+                        .to_string(),
+                )
+            }
+        };
+        Ok(ProcedureStatement::Execute { statement, into })
+    }
+
+    fn parse_loop_body(&mut self) -> Result<Vec<ProcedureStatement>, String> {
+        self.expect_keyword("LOOP")?;
+        let body = self.parse_procedure_block(&["END"])?;
+        self.expect_keyword("END")?;
+        self.expect_keyword("LOOP")?;
+        Ok(body)
+    }
+
+    fn parse_create_sequence(&mut self) -> Result<Statement, String> {
+        let mut sequence = CreateSequence {
+            name: self.parse_identifier()?,
+            ..Default::default()
+        };
+        loop {
+            if self.consume_keyword("INCREMENT") {
+                self.consume_keyword("BY");
+                sequence.increment = Some(self.parse_signed_integer()?);
+            } else if self.consume_keyword("MINVALUE") {
+                sequence.min_value = Some(self.parse_signed_integer()?);
+            } else if self.consume_keyword("MAXVALUE") {
+                sequence.max_value = Some(self.parse_signed_integer()?);
+            } else if self.consume_keyword("START") {
+                self.consume_keyword("WITH");
+                sequence.start = Some(self.parse_signed_integer()?);
+            } else if self.consume_keyword("CYCLE") {
+                sequence.cycle = true;
+            } else if self.consume_keyword("NO") {
+                if self.consume_keyword("CYCLE") {
+                    sequence.cycle = false;
+                } else if self.consume_keyword("MINVALUE") {
+                    sequence.min_value = None;
+                } else {
+                    self.expect_keyword("MAXVALUE")?;
+                    sequence.max_value = None;
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(Statement::CreateSequence(sequence))
+    }
+
+    fn parse_signed_integer(&mut self) -> Result<i64, String> {
+        let negative = self.consume_symbol("-");
+        match self.next() {
+            Some(Token::Number(text)) => {
+                let text = if negative { format!("-{}", text) } else { text };
+                text.parse()
+                    .map_err(|_| format!("Número inteiro inválido '{}'.", text))
+            }
+            _ => {
+                self.pos -= 1;
+                self.error("um número inteiro")
+            }
+        }
+    }
+
+    fn parse_references(&mut self) -> Result<(String, String), String> {
+        self.expect_keyword("REFERENCES")?;
+        let table = self.parse_identifier()?;
+        self.expect_symbol("(")?;
+        let column = self.parse_identifier()?;
+        self.expect_symbol(")")?;
+        Ok((table, column))
+    }
+
+    fn parse_insert(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("INTO")?;
+        let table = self.parse_identifier()?;
+        let alias = if self.consume_keyword("AS") {
+            Some(self.parse_identifier()?)
+        } else {
+            None
+        };
+        let mut columns = None;
+        if self.peek_symbol("(") && !Self::is_keyword(self.peek_nth(1), "SELECT") {
+            columns = Some(self.parse_column_list()?);
+        }
+        let source = if self.consume_keyword("VALUES") {
+            InsertSource::Values(self.parse_comma_separated(|parser| {
+                parser.expect_symbol("(")?;
+                let values = parser.parse_comma_separated(Self::parse_expr)?;
+                parser.expect_symbol(")")?;
+                Ok(values)
+            })?)
+        } else if self.peek_keyword("SELECT") || self.peek_symbol("(") {
+            InsertSource::Query(Box::new(self.parse_query()?))
+        } else {
+            return self.error("'VALUES' ou 'SELECT'");
+        };
+        let on_conflict = if self.consume_keyword("ON") {
+            self.expect_keyword("CONFLICT")?;
+            Some(self.parse_on_conflict()?)
+        } else {
+            None
+        };
+        Ok(Statement::Insert(Insert {
+            table,
+            alias,
+            columns,
+            source,
+            on_conflict,
+            returning: self.parse_returning()?,
+        }))
+    }
+
+    fn parse_update(&mut self) -> Result<Statement, String> {
+        let table = self.parse_identifier()?;
+        let alias = self.parse_alias()?;
+        self.expect_keyword("SET")?;
+        let assignments = self.parse_comma_separated(Self::parse_assignment)?;
+        let selection = self.parse_where()?;
+        Ok(Statement::Update(Update {
+            table,
+            alias,
+            assignments,
+            selection,
+            returning: self.parse_returning()?,
+        }))
+    }
+
+    fn parse_delete(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("FROM")?;
+        let table = self.parse_identifier()?;
+        let alias = self.parse_alias()?;
+        let selection = self.parse_where()?;
+        Ok(Statement::Delete(Delete {
+            table,
+            alias,
+            selection,
+            returning: self.parse_returning()?,
+        }))
+    }
+
+    fn parse_where(&mut self) -> Result<Option<Expr>, String> {
+        if self.consume_keyword("WHERE") {
+            return self.parse_expr().map(Some);
+        }
+        Ok(None)
+    }
+
+    fn parse_returning(&mut self) -> Result<Vec<SelectItem>, String> {
+        if self.consume_keyword("RETURNING") {
+            return self.parse_comma_separated(Self::parse_select_item);
+        }
+        Ok(Vec::new())
+    }
+
+    fn parse_on_conflict(&mut self) -> Result<OnConflict, String> {
+        let target = if self.peek_symbol("(") {
+            Some(self.parse_column_list()?)
+        } else {
+            None
+        };
+        self.expect_keyword("DO")?;
+        if self.consume_keyword("NOTHING") {
+            return Ok(OnConflict {
+                target,
+                action: ConflictAction::DoNothing,
+            });
+        }
+        self.expect_keyword("UPDATE")?;
+        if target.is_none() {
+            return Err("ON CONFLICT DO UPDATE exige a lista de colunas do conflito.".to_string());
+        }
+        self.expect_keyword("SET")?;
+        let assignments = self.parse_comma_separated(Self::parse_assignment)?;
+        let selection = self.parse_where()?;
+        Ok(OnConflict {
+            target,
+            action: ConflictAction::DoUpdate {
+                assignments,
+                selection,
+            },
+        })
+    }
+
+    fn parse_assignment(&mut self) -> Result<Assignment, String> {
+        let column = self.parse_identifier()?;
+        self.expect_symbol("=")?;
+        let value = self.parse_expr()?;
+        Ok(Assignment { column, value })
+    }
+
+    fn parse_alter_table(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("TABLE")?;
+        let table = self.parse_identifier()?;
+        let action = if self.consume_keyword("ADD") {
+            if self.peek_table_constraint() {
+                AlterTableAction::AddConstraint(self.parse_table_constraint()?)
+            } else {
+                self.consume_keyword("COLUMN");
+                AlterTableAction::AddColumn(self.parse_column_definition()?)
+            }
+        } else if self.consume_keyword("DROP") {
+            if self.consume_keyword("CONSTRAINT") {
+                AlterTableAction::DropConstraint(self.parse_identifier()?)
+            } else {
+                self.consume_keyword("COLUMN");
+                AlterTableAction::DropColumn(self.parse_identifier()?)
+            }
+        } else if self.consume_keyword("RENAME") {
+            if self.consume_keyword("TO") {
+                AlterTableAction::RenameTable(self.parse_identifier()?)
+            } else {
+                self.consume_keyword("COLUMN");
+                let old_name = self.parse_identifier()?;
+                self.expect_keyword("TO")?;
+                AlterTableAction::RenameColumn {
+                    old_name,
+                    new_name: self.parse_identifier()?,
+                }
+            }
+        } else if self.consume_keyword("ALTER") {
+            self.consume_keyword("COLUMN");
+            let column = self.parse_identifier()?;
+            AlterTableAction::AlterColumn {
+                column,
+                change: self.parse_column_change()?,
+            }
+        } else if self.consume_keyword("SET") {
+            self.expect_symbol("(")?;
+            let parameter = self.parse_identifier()?;
+            if !parameter.eq_ignore_ascii_case("autoanalyze_fraction") {
+                return Err(format!("Parâmetro de tabela '{}' desconhecido.", parameter));
+            }
+            self.expect_symbol("=")?;
+            let fraction = match self.next() {
+                Some(Token::Number(text)) => text
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))
+                    .ok_or_else(|| {
+                        format!(
+                            "autoanalyze_fraction deve estar entre 0 e 1, não '{}'.",
+                            text
+                        )
+                    })?,
+                _ => {
+                    self.pos -= 1;
+                    return self.error("um número");
+                }
+            };
+            self.expect_symbol(")")?;
+            AlterTableAction::SetAutoanalyzeFraction(fraction)
+        } else {
+            return Err("Ação inválida. Use ADD, DROP, RENAME, ALTER COLUMN ou SET.".to_string());
+        };
+        Ok(Statement::AlterTable(AlterTable { table, action }))
+    }
+
+    fn parse_column_change(&mut self) -> Result<ColumnChange, String> {
+        if self.consume_keyword("TYPE") {
+            return Ok(ColumnChange::SetType(self.parse_data_type()?));
+        }
+        if self.consume_keyword("SET") {
+            if self.consume_keyword("DATA") {
+                self.expect_keyword("TYPE")?;
+                return Ok(ColumnChange::SetType(self.parse_data_type()?));
+            }
+            if self.consume_keyword("DEFAULT") {
+                return Ok(ColumnChange::SetDefault(self.parse_expr()?));
+            }
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("NULL")?;
+            return Ok(ColumnChange::SetNotNull);
+        }
+        if self.consume_keyword("DROP") {
+            if self.consume_keyword("DEFAULT") {
+                return Ok(ColumnChange::DropDefault);
+            }
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("NULL")?;
+            return Ok(ColumnChange::DropNotNull);
+        }
+        self.error("'TYPE', 'SET' ou 'DROP'")
+    }
+
+    pub fn parse_query(&mut self) -> Result<Query, String> {
+        let body = self.parse_set_expr()?;
+        let mut order_by = Vec::new();
+        if self.consume_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            order_by = self.parse_comma_separated(Self::parse_order_by_item)?;
+        }
+        let mut limit = None;
+        let mut offset = None;
+        if self.consume_keyword("LIMIT") {
+            limit = Some(self.parse_expr()?);
+        }
+        if self.consume_keyword("OFFSET") {
+            offset = Some(self.parse_expr()?);
+        }
+        let mut locking = None;
+        if self.consume_keyword("FOR") {
+            let mode = if self.consume_keyword("UPDATE") {
+                RowLockMode::Update
+            } else if self.consume_keyword("SHARE") {
+                RowLockMode::Share
+            } else {
+                return self.error("'UPDATE' ou 'SHARE'");
+            };
+            let wait = if self.consume_keyword("NOWAIT") {
+                LockWait::NoWait
+            } else if self.consume_keyword("SKIP") {
+                self.expect_keyword("LOCKED")?;
+                LockWait::SkipLocked
+            } else {
+                LockWait::Wait
+            };
+            locking = Some(LockClause { mode, wait });
+        }
+        Ok(Query {
+            body,
+            order_by,
+            limit,
+            offset,
+            locking,
+        })
+    }
+
+    fn parse_order_by_item(&mut self) -> Result<OrderByItem, String> {
+        let expr = self.parse_expr()?;
+        let descending = if self.consume_keyword("DESC") {
+            true
+        } else {
+            self.consume_keyword("ASC");
+            false
+        };
+        Ok(OrderByItem { expr, descending })
+    }
+
+    // UNION e EXCEPT têm a mesma precedência; INTERSECT tem precedência maior.
+    fn parse_set_expr(&mut self) -> Result<SetExpr, String> {
+        let mut left = self.parse_intersect_expr()?;
+        loop {
+            let op = if self.consume_keyword("UNION") {
+                SetOperator::Union
+            } else if self.consume_keyword("EXCEPT") {
+                SetOperator::Except
+            } else {
+                return Ok(left);
+            };
+            let all = self.parse_set_quantifier();
+            let right = self.parse_intersect_expr()?;
+            left = SetExpr::SetOperation {
+                op,
+                all,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+    }
+
+    fn parse_intersect_expr(&mut self) -> Result<SetExpr, String> {
+        let mut left = self.parse_set_operand()?;
+        while self.consume_keyword("INTERSECT") {
+            let all = self.parse_set_quantifier();
+            let right = self.parse_set_operand()?;
+            left = SetExpr::SetOperation {
+                op: SetOperator::Intersect,
+                all,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_set_quantifier(&mut self) -> bool {
+        if self.consume_keyword("ALL") {
+            return true;
+        }
+        self.consume_keyword("DISTINCT");
+        false
+    }
+
+    fn parse_set_operand(&mut self) -> Result<SetExpr, String> {
+        if self.consume_symbol("(") {
+            let inner = self.parse_set_expr()?;
+            self.expect_symbol(")")?;
+            return Ok(inner);
+        }
+        Ok(SetExpr::Select(Box::new(self.parse_select()?)))
+    }
+
+    fn parse_select(&mut self) -> Result<Select, String> {
+        self.expect_keyword("SELECT")?;
+        let projection = self.parse_comma_separated(Self::parse_select_item)?;
+
+        let mut from = None;
+        if self.consume_keyword("FROM") {
+            let name = self.parse_identifier()?;
+            let as_of = self.parse_as_of()?;
+            let alias = self.parse_alias()?;
+            from = Some(TableReference { name, alias, as_of });
+        }
+
+        let selection = self.parse_where()?;
+
+        Ok(Select {
+            projection,
+            from,
+            selection,
+        })
+    }
+
+    // AS OF TIMESTAMP <instante> | AS OF TRANSACTION <número>
+    fn parse_as_of(&mut self) -> Result<Option<AsOf>, String> {
+        if !(self.peek_keyword("AS") && Self::is_keyword(self.peek_nth(1), "OF")) {
+            return Ok(None);
+        }
+        self.pos += 2;
+        if self.consume_keyword("TRANSACTION") {
+            return Ok(Some(AsOf::Transaction(self.parse_expr()?)));
+        }
+        self.expect_keyword("TIMESTAMP")?;
+        Ok(Some(AsOf::Timestamp(self.parse_expr()?)))
+    }
+
+    fn parse_select_item(&mut self) -> Result<SelectItem, String> {
+        if self.consume_symbol("*") {
+            return Ok(SelectItem::Wildcard);
+        }
+        if let (
+            Some(Token::Identifier(table)),
+            Some(Token::Symbol(".")),
+            Some(Token::Symbol("*")),
+        ) = (self.peek().cloned(), self.peek_nth(1), self.peek_nth(2))
+        {
+            self.pos += 3;
+            return Ok(SelectItem::QualifiedWildcard(table));
+        }
+        let expr = self.parse_expr()?;
+        let alias = self.parse_alias()?;
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    pub fn parse_expr(&mut self) -> Result<Expr, String> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_and()?;
+        while self.consume_keyword("OR") {
+            let right = self.parse_and()?;
+            left = binary(left, BinaryOperator::Or, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_not()?;
+        while self.consume_keyword("AND") {
+            let right = self.parse_not()?;
+            left = binary(left, BinaryOperator::And, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.consume_keyword("NOT") {
+            let expr = self.parse_not()?;
+            return Ok(Expr::Unary {
+                op: UnaryOperator::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let left = self.parse_additive()?;
+        if self.consume_keyword("IS") {
+            let negated = self.consume_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+        let op = match self.peek() {
+            Some(Token::Symbol("=")) => BinaryOperator::Eq,
+            Some(Token::Symbol("<>")) | Some(Token::Symbol("!=")) => BinaryOperator::NotEq,
+            Some(Token::Symbol("<")) => BinaryOperator::Lt,
+            Some(Token::Symbol("<=")) => BinaryOperator::LtEq,
+            Some(Token::Symbol(">")) => BinaryOperator::Gt,
+            Some(Token::Symbol(">=")) => BinaryOperator::GtEq,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_additive()?;
+        Ok(binary(left, op, right))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => BinaryOperator::Plus,
+                Some(Token::Symbol("-")) => BinaryOperator::Minus,
+                Some(Token::Symbol("||")) => BinaryOperator::Concat,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => BinaryOperator::Multiply,
+                Some(Token::Symbol("/")) => BinaryOperator::Divide,
+                Some(Token::Symbol("%")) => BinaryOperator::Modulo,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.consume_symbol("-") {
+            // O sinal faz parte do literal, para que -9223372036854775808 seja um INTEGER.
+            if let Some(Token::Number(text)) = self.peek() {
+                if let Ok(i) = format!("-{}", text).parse::<i64>() {
+                    self.pos += 1;
+                    return Ok(Expr::Literal(Value::Integer(i)));
+                }
+            }
+            return Ok(match self.parse_unary()? {
+                Expr::Literal(Value::Integer(i)) if i != i64::MIN => {
+                    Expr::Literal(Value::Integer(-i))
+                }
+                Expr::Literal(Value::Float(f)) => Expr::Literal(Value::Float(-f)),
+                expr => Expr::Unary {
+                    op: UnaryOperator::Minus,
+                    expr: Box::new(expr),
+                },
+            });
+        }
+        if self.consume_symbol("+") {
+            return self.parse_unary();
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(text)) => {
+                self.pos += 1;
+                if let Ok(i) = text.parse::<i64>() {
+                    return Ok(Expr::Literal(Value::Integer(i)));
+                }
+                text.parse::<f64>()
+                    .map(|f| Expr::Literal(Value::Float(f)))
+                    .map_err(|_| format!("Número inválido '{}'.", text))
+            }
+            Some(Token::String(text)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Text(text)))
+            }
+            Some(Token::Parameter(n)) => {
+                self.pos += 1;
+                self.parameter_count = self.parameter_count.max(n);
+                Ok(Expr::Parameter(n))
+            }
+            Some(Token::Symbol("?")) => {
+                self.pos += 1;
+                self.placeholders += 1;
+                self.parameter_count = self.parameter_count.max(self.placeholders);
+                Ok(Expr::Parameter(self.placeholders))
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let expr = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("NULL") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Null))
+            }
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("TRUE") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Boolean(true)))
+            }
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("FALSE") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Boolean(false)))
+            }
+            Some(Token::Identifier(word))
+                if DataType::from_name(&word).is_some_and(|t| t.is_temporal())
+                    && matches!(self.peek_nth(1), Some(Token::String(_))) =>
+            {
+                let data_type = self.parse_data_type()?;
+                let text = match self.next() {
+                    Some(Token::String(text)) => text,
+                    _ => unreachable!(),
+                };
+                Ok(Expr::Cast {
+                    expr: Box::new(Expr::Literal(Value::Text(text))),
+                    data_type,
+                })
+            }
+            Some(Token::Identifier(word))
+                if ["CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP"]
+                    .iter()
+                    .any(|k| word.eq_ignore_ascii_case(k))
+                    && self.peek_nth(1) != Some(&Token::Symbol("(")) =>
+            {
+                self.pos += 1;
+                Ok(Expr::Function(FunctionCall {
+                    name: word.to_uppercase(),
+                    args: Vec::new(),
+                    wildcard: false,
+                    over: None,
+                }))
+            }
+            Some(Token::Identifier(word))
+                if word.eq_ignore_ascii_case("EXTRACT")
+                    && self.peek_nth(1) == Some(&Token::Symbol("(")) =>
+            {
+                self.pos += 2;
+                let field = self.parse_identifier()?;
+                self.expect_keyword("FROM")?;
+                let expr = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                Ok(Expr::Function(FunctionCall {
+                    name: "EXTRACT".to_string(),
+                    args: vec![Expr::Literal(Value::Text(field.to_lowercase())), expr],
+                    wildcard: false,
+                    over: None,
+                }))
+            }
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("CASE") => {
+                self.pos += 1;
+                self.parse_case()
+            }
+            Some(Token::Identifier(word))
+                if word.eq_ignore_ascii_case("CAST")
+                    && self.peek_nth(1) == Some(&Token::Symbol("(")) =>
+            {
+                self.pos += 2;
+                let expr = self.parse_expr()?;
+                self.expect_keyword("AS")?;
+                let data_type = self.parse_data_type()?;
+                self.expect_symbol(")")?;
+                Ok(Expr::Cast {
+                    expr: Box::new(expr),
+                    data_type,
+                })
+            }
+            Some(Token::Identifier(word))
+                if RESERVED.iter().any(|r| word.eq_ignore_ascii_case(r)) =>
+            {
+                self.error("uma expressão")
+            }
+            Some(Token::Identifier(_)) | Some(Token::QuotedIdentifier(_)) => {
+                let name = self.parse_identifier()?;
+                if self.consume_symbol("(") {
+                    return self.parse_function_call(name);
+                }
+                if self.consume_symbol(".") {
+                    let column = self.parse_identifier()?;
+                    return Ok(Expr::Column {
+                        table: Some(name),
+                        name: column,
+                    });
+                }
+                Ok(Expr::Column { table: None, name })
+            }
+            _ => self.error("uma expressão"),
+        }
+    }
+
+    fn parse_case(&mut self) -> Result<Expr, String> {
+        let operand = match self.peek_keyword("WHEN") {
+            true => None,
+            false => Some(Box::new(self.parse_expr()?)),
+        };
+        let mut branches = Vec::new();
+        while self.consume_keyword("WHEN") {
+            let when = self.parse_expr()?;
+            self.expect_keyword("THEN")?;
+            branches.push((when, self.parse_expr()?));
+        }
+        if branches.is_empty() {
+            return self.error("'WHEN'");
+        }
+        let else_result = match self.consume_keyword("ELSE") {
+            true => Some(Box::new(self.parse_expr()?)),
+            false => None,
+        };
+        self.expect_keyword("END")?;
+        Ok(Expr::Case {
+            operand,
+            branches,
+            else_result,
+        })
+    }
+
+    fn parse_data_type(&mut self) -> Result<DataType, String> {
+        let data_type = match self.peek() {
+            Some(Token::Identifier(name)) => DataType::from_name(name),
+            _ => None,
+        };
+        match data_type {
+            Some(data_type) => {
+                self.pos += 1;
+                if self.consume_symbol("(") {
+                    self.parse_comma_separated(|p| match p.next() {
+                        Some(Token::Number(_)) => Ok(()),
+                        _ => Err("Erro de sintaxe: tamanho de tipo inválido.".to_string()),
+                    })?;
+                    self.expect_symbol(")")?;
+                }
+                Ok(data_type)
+            }
+            None => self.error("um tipo de dado"),
+        }
+    }
+
+    fn parse_function_call(&mut self, name: String) -> Result<Expr, String> {
+        let mut call = FunctionCall {
+            name: name.to_uppercase(),
+            args: Vec::new(),
+            wildcard: false,
+            over: None,
+        };
+        if self.consume_symbol("*") {
+            call.wildcard = true;
+        } else if !self.peek_symbol(")") {
+            call.args = self.parse_comma_separated(Self::parse_expr)?;
+        }
+        self.expect_symbol(")")?;
+        if self.consume_keyword("OVER") {
+            call.over = Some(self.parse_window_spec()?);
+        }
+        Ok(Expr::Function(call))
+    }
+
+    fn parse_window_spec(&mut self) -> Result<WindowSpec, String> {
+        self.expect_symbol("(")?;
+        let mut spec = WindowSpec {
+            partition_by: Vec::new(),
+            order_by: Vec::new(),
+            frame: None,
+        };
+        if self.consume_keyword("PARTITION") {
+            self.expect_keyword("BY")?;
+            spec.partition_by = self.parse_comma_separated(Self::parse_expr)?;
+        }
+        if self.consume_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            spec.order_by = self.parse_comma_separated(Self::parse_order_by_item)?;
+        }
+        if self.peek_keyword("ROWS") || self.peek_keyword("RANGE") {
+            spec.frame = Some(self.parse_window_frame()?);
+        }
+        self.expect_symbol(")")?;
+        Ok(spec)
+    }
+
+    fn parse_window_frame(&mut self) -> Result<WindowFrame, String> {
+        let units = if self.consume_keyword("ROWS") {
+            FrameUnits::Rows
+        } else {
+            self.expect_keyword("RANGE")?;
+            FrameUnits::Range
+        };
+        let (start, end) = if self.consume_keyword("BETWEEN") {
+            let start = self.parse_frame_bound()?;
+            self.expect_keyword("AND")?;
+            (start, self.parse_frame_bound()?)
+        } else {
+            (self.parse_frame_bound()?, FrameBound::CurrentRow)
+        };
+
+        if start == FrameBound::UnboundedFollowing || end == FrameBound::UnboundedPreceding {
+            return Err("Limites inválidos para o quadro da janela.".to_string());
+        }
+        if units == FrameUnits::Range
+            && [start, end]
+                .iter()
+                .any(|b| matches!(b, FrameBound::Preceding(_) | FrameBound::Following(_)))
+        {
+            return Err(
+                "RANGE aceita apenas UNBOUNDED PRECEDING, CURRENT ROW e UNBOUNDED FOLLOWING."
+                    .to_string(),
+            );
+        }
+        Ok(WindowFrame { units, start, end })
+    }
+
+    fn parse_frame_bound(&mut self) -> Result<FrameBound, String> {
+        if self.consume_keyword("UNBOUNDED") {
+            if self.consume_keyword("PRECEDING") {
+                return Ok(FrameBound::UnboundedPreceding);
+            }
+            self.expect_keyword("FOLLOWING")?;
+            return Ok(FrameBound::UnboundedFollowing);
+        }
+        if self.consume_keyword("CURRENT") {
+            self.expect_keyword("ROW")?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        let offset = match self.next() {
+            Some(Token::Number(text)) => text
+                .parse::<usize>()
+                .map_err(|_| format!("Deslocamento inválido '{}' no quadro da janela.", text))?,
+            _ => {
+                self.pos -= 1;
+                return self.error("um limite de quadro da janela");
+            }
+        };
+        if self.consume_keyword("PRECEDING") {
+            return Ok(FrameBound::Preceding(offset));
+        }
+        self.expect_keyword("FOLLOWING")?;
+        Ok(FrameBound::Following(offset))
+    }
+}
+
+fn binary(left: Expr, op: BinaryOperator, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
@@ -0,0 +1,484 @@
+use crate::query::{self, Context, Plan};
+use crate::sql::{BinaryOperator, Expr, SetOperator, UnaryOperator};
+use crate::table::Table;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Seletividades padrão do PostgreSQL, usadas quando não há estatísticas.
+const EQUALITY_SELECTIVITY: f64 = 0.005;
+const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+
+const SAMPLE_SIZE: usize = 30000;
+const HISTOGRAM_BUCKETS: usize = 10;
+// Como no autovacuum do PostgreSQL: nova análise após 50 linhas mais a fração da tabela.
+const AUTOANALYZE_THRESHOLD: usize = 50;
+pub const DEFAULT_AUTOANALYZE_FRACTION: f64 = 0.1;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Statistics {
+    pub analysis: Option<Analysis>,
+    pub changes_since_analyze: usize,
+    pub autoanalyze_fraction: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Analysis {
+    pub row_count: usize,
+    pub columns: HashMap<String, ColumnStatistics>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ColumnStatistics {
+    pub null_fraction: f64,
+    pub distinct: f64,
+    // Limites de faixas com o mesmo número de valores, do menor ao maior.
+    pub histogram: Vec<Value>,
+}
+
+impl Statistics {
+    fn needs_analyze(&self) -> bool {
+        let analyzed_rows = self.analysis.as_ref().map_or(0, |a| a.row_count);
+        let fraction = self
+            .autoanalyze_fraction
+            .unwrap_or(DEFAULT_AUTOANALYZE_FRACTION);
+        self.changes_since_analyze as f64
+            > AUTOANALYZE_THRESHOLD as f64 + fraction * analyzed_rows as f64
+    }
+
+    pub fn rename_column(&mut self, old_name: &str, new_name: &str) {
+        if let Some(analysis) = &mut self.analysis {
+            if let Some(column) = analysis.columns.remove(old_name) {
+                analysis.columns.insert(new_name.to_string(), column);
+            }
+        }
+    }
+
+    pub fn forget_column(&mut self, name: &str) {
+        if let Some(analysis) = &mut self.analysis {
+            analysis.columns.remove(name);
+        }
+    }
+}
+
+// Registra linhas alteradas e analisa a tabela de novo se mudou o suficiente.
+pub fn record_changes(table: &mut Table, count: usize) -> bool {
+    table.statistics.changes_since_analyze += count;
+    if count == 0 || !table.statistics.needs_analyze() {
+        return false;
+    }
+    analyze(table);
+    true
+}
+
+pub fn analyze(table: &mut Table) {
+    let rows = &table.data;
+    let step = (rows.len() as f64 / SAMPLE_SIZE as f64).max(1.0);
+    let sample: Vec<&Vec<Value>> = (0..rows.len().min(SAMPLE_SIZE))
+        .map(|i| &rows[(i as f64 * step) as usize])
+        .collect();
+
+    let columns = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            let mut values: Vec<&Value> = sample
+                .iter()
+                .filter_map(|row| row.get(index))
+                .filter(|value| !value.is_null())
+                .collect();
+            values.sort_by(|a, b| a.sort_cmp(b));
+            let null_fraction = match sample.len() {
+                0 => 0.0,
+                n => (n - values.len()) as f64 / n as f64,
+            };
+            let total = rows.len() as f64 * (1.0 - null_fraction);
+            let statistics = ColumnStatistics {
+                null_fraction,
+                distinct: estimate_distinct(&values, total),
+                histogram: histogram(&values),
+            };
+            (column.name.clone(), statistics)
+        })
+        .collect();
+
+    table.statistics.analysis = Some(Analysis {
+        row_count: rows.len(),
+        columns,
+    });
+    table.statistics.changes_since_analyze = 0;
+}
+
+// Estimador Duj1 de Haas e Stokes, o mesmo do PostgreSQL: n*d / (n - f1 + f1*n/N), onde
+// f1 é o número de valores vistos uma única vez na amostra.
+fn estimate_distinct(sorted: &[&Value], total: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let (mut distinct, mut singles, mut run) = (0usize, 0usize, 1usize);
+    for i in 1..=sorted.len() {
+        if i < sorted.len() && sorted[i].sort_cmp(sorted[i - 1]).is_eq() {
+            run += 1;
+            continue;
+        }
+        distinct += 1;
+        if run == 1 {
+            singles += 1;
+        }
+        run = 1;
+    }
+    let n = sorted.len() as f64;
+    let (d, f1) = (distinct as f64, singles as f64);
+    if n >= total {
+        return d;
+    }
+    // Nenhum valor repetido na amostra: provavelmente a coluna é única.
+    if singles == sorted.len() {
+        return total;
+    }
+    (n * d / (n - f1 + f1 * n / total)).clamp(d, total)
+}
+
+fn histogram(sorted: &[&Value]) -> Vec<Value> {
+    let Some(last) = sorted.len().checked_sub(1) else {
+        return Vec::new();
+    };
+    let buckets = HISTOGRAM_BUCKETS.min(last).max(1);
+    (0..=buckets)
+        .map(|i| sorted[i * last / buckets].clone())
+        .collect()
+}
+
+pub fn estimate_rows(tables: &HashMap<String, Table>, plan: &Plan) -> f64 {
+    match plan {
+        Plan::SeqScan { table, .. } => tables.get(table).map_or(0, |t| t.data.len()) as f64,
+        Plan::SingleRow => 1.0,
+        // Um filtro sobre uma entrada não vazia nunca é estimado abaixo de uma linha.
+        Plan::Filter { input, predicate } => {
+            let analysis = match input.as_ref() {
+                Plan::SeqScan { table, .. } => tables
+                    .get(table)
+                    .and_then(|t| t.statistics.analysis.as_ref()),
+                _ => None,
+            };
+            let input = estimate_rows(tables, input);
+            (input * selectivity(predicate, analysis)).max(input.min(1.0))
+        }
+        Plan::Sort { input, .. }
+        | Plan::Window { input, .. }
+        | Plan::Project { input, .. }
+        | Plan::ViewScan { input, .. } => estimate_rows(tables, input),
+        Plan::SetOperation {
+            op, left, right, ..
+        } => {
+            let (left, right) = (estimate_rows(tables, left), estimate_rows(tables, right));
+            match op {
+                SetOperator::Union => left + right,
+                SetOperator::Intersect => left.min(right),
+                SetOperator::Except => left,
+            }
+        }
+        Plan::Limit {
+            input,
+            limit,
+            offset,
+        }
+        | Plan::LockRows {
+            input,
+            limit,
+            offset,
+            ..
+        } => {
+            let count = |expr: &Expr| constant(expr)?.as_i64().map(|n| n.max(0) as f64);
+            let offset = offset.as_ref().and_then(count).unwrap_or(0.0);
+            let rows = (estimate_rows(tables, input) - offset).max(0.0);
+            match limit.as_ref().and_then(count) {
+                Some(limit) => rows.min(limit),
+                None => rows,
+            }
+        }
+    }
+}
+
+// Condições de um AND em ordem crescente de seletividade; como o AND para no primeiro
+// falso, as mais restritivas evitam avaliar as demais na maioria das linhas. Se alguma
+// condição pode falhar (`x <> 0 AND 10 / x = 5`), a ordem escrita é mantida: trocá-la faria
+// a consulta devolver um erro que a condição anterior evitava.
+pub fn order_conjuncts(predicate: &Expr, analysis: &Analysis) -> Expr {
+    let mut conjuncts = Vec::new();
+    split_conjuncts(predicate, &mut conjuncts);
+    if conjuncts.len() < 2 || !conjuncts.iter().all(|expr| cannot_fail(expr)) {
+        return predicate.clone();
+    }
+    let mut ranked: Vec<(f64, &Expr)> = conjuncts
+        .into_iter()
+        .map(|expr| (selectivity(expr, Some(analysis)), expr))
+        .collect();
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ranked
+        .into_iter()
+        .map(|(_, expr)| expr.clone())
+        .reduce(|left, right| Expr::Binary {
+            left: Box::new(left),
+            op: BinaryOperator::And,
+            right: Box::new(right),
+        })
+        .unwrap_or_else(|| predicate.clone())
+}
+
+fn split_conjuncts<'a>(expr: &'a Expr, conjuncts: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            split_conjuncts(left, conjuncts);
+            split_conjuncts(right, conjuncts);
+        }
+        _ => conjuncts.push(expr),
+    }
+}
+
+// Comparações e testes de nulo entre colunas e constantes, que nunca devolvem erro.
+fn cannot_fail(expr: &Expr) -> bool {
+    let operand = |expr: &Expr| matches!(expr, Expr::Column { .. } | Expr::Literal(_));
+    match expr {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::And | BinaryOperator::Or,
+            right,
+        } => cannot_fail(left) && cannot_fail(right),
+        Expr::Binary {
+            left,
+            op:
+                BinaryOperator::Eq
+                | BinaryOperator::NotEq
+                | BinaryOperator::Lt
+                | BinaryOperator::LtEq
+                | BinaryOperator::Gt
+                | BinaryOperator::GtEq,
+            right,
+        } => operand(left) && operand(right),
+        Expr::Unary {
+            op: UnaryOperator::Not,
+            expr,
+        } => cannot_fail(expr),
+        Expr::IsNull { expr, .. } => operand(expr),
+        _ => false,
+    }
+}
+
+// Fração estimada das linhas que satisfazem a condição.
+fn selectivity(expr: &Expr, analysis: Option<&Analysis>) -> f64 {
+    match expr {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => selectivity(left, analysis) * selectivity(right, analysis),
+        Expr::Binary {
+            left,
+            op: BinaryOperator::Or,
+            right,
+        } => {
+            let (left, right) = (selectivity(left, analysis), selectivity(right, analysis));
+            left + right - left * right
+        }
+        Expr::Unary {
+            op: UnaryOperator::Not,
+            expr,
+        } => 1.0 - selectivity(expr, analysis),
+        Expr::Binary { left, op, right } => {
+            if let Some(estimate) = column_comparison(left, *op, right, analysis) {
+                return estimate;
+            }
+            match op {
+                BinaryOperator::Eq => EQUALITY_SELECTIVITY,
+                BinaryOperator::NotEq => 1.0 - EQUALITY_SELECTIVITY,
+                BinaryOperator::Lt
+                | BinaryOperator::LtEq
+                | BinaryOperator::Gt
+                | BinaryOperator::GtEq => RANGE_SELECTIVITY,
+                _ => DEFAULT_SELECTIVITY,
+            }
+        }
+        Expr::IsNull { expr, negated } => {
+            let null_fraction = column_statistics(expr, analysis)
+                .map_or(EQUALITY_SELECTIVITY, |stats| stats.null_fraction);
+            if *negated {
+                1.0 - null_fraction
+            } else {
+                null_fraction
+            }
+        }
+        Expr::Literal(Value::Boolean(true)) => 1.0,
+        Expr::Literal(Value::Boolean(false) | Value::Null) => 0.0,
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+// Comparação entre uma coluna analisada e uma constante, em qualquer ordem.
+fn column_comparison(
+    left: &Expr,
+    op: BinaryOperator,
+    right: &Expr,
+    analysis: Option<&Analysis>,
+) -> Option<f64> {
+    let (stats, value, op) = match (column_statistics(left, analysis), constant(right)) {
+        (Some(stats), Some(value)) => (stats, value, op),
+        _ => {
+            let op = match op {
+                BinaryOperator::Lt => BinaryOperator::Gt,
+                BinaryOperator::LtEq => BinaryOperator::GtEq,
+                BinaryOperator::Gt => BinaryOperator::Lt,
+                BinaryOperator::GtEq => BinaryOperator::LtEq,
+                op => op,
+            };
+            (column_statistics(right, analysis)?, constant(left)?, op)
+        }
+    };
+    if value.is_null() {
+        return Some(0.0);
+    }
+    let not_null = 1.0 - stats.null_fraction;
+    let equal = not_null / stats.distinct.max(1.0);
+    let below = || fraction_below(&stats.histogram, &value);
+    let estimate = match op {
+        BinaryOperator::Eq => equal,
+        BinaryOperator::NotEq => not_null - equal,
+        BinaryOperator::Lt => not_null * below()?,
+        BinaryOperator::LtEq => (not_null * below()? + equal).min(not_null),
+        BinaryOperator::Gt => (not_null * (1.0 - below()?) - equal).max(0.0),
+        BinaryOperator::GtEq => not_null * (1.0 - below()?),
+        _ => return None,
+    };
+    Some(estimate.clamp(0.0, 1.0))
+}
+
+fn column_statistics<'a>(
+    expr: &Expr,
+    analysis: Option<&'a Analysis>,
+) -> Option<&'a ColumnStatistics> {
+    match expr {
+        Expr::Column { name, .. } => analysis?.columns.get(name),
+        _ => None,
+    }
+}
+
+// Fração dos valores não nulos menores que `value`, interpolada dentro da faixa do
+// histograma quando os limites são numéricos.
+fn fraction_below(histogram: &[Value], value: &Value) -> Option<f64> {
+    let (first, last) = (histogram.first()?, histogram.last()?);
+    if value.sort_cmp(first).is_le() {
+        return Some(0.0);
+    }
+    if value.sort_cmp(last).is_gt() {
+        return Some(1.0);
+    }
+    let buckets = (histogram.len() - 1).max(1) as f64;
+    let bucket = histogram
+        .windows(2)
+        .position(|bounds| value.sort_cmp(&bounds[1]).is_le())?;
+    let (low, high) = (&histogram[bucket], &histogram[bucket + 1]);
+    let within = match (low.as_f64(), high.as_f64(), value.as_f64()) {
+        (Some(low), Some(high), Some(value)) if high > low => (value - low) / (high - low),
+        _ => 0.5,
+    };
+    Some((bucket as f64 + within) / buckets)
+}
+
+// Valor conhecido no planejamento; expressões com colunas ou parâmetros ficam sem estimativa.
+fn constant(expr: &Expr) -> Option<Value> {
+    query::evaluate(expr, &[], &[], &Context::default()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    #[test]
+    fn analyze_keeps_the_order_of_conditions_that_can_fail() {
+        let mut test = TestDatabase::new("conjunct_order");
+        test.exec("CREATE TABLE t (x INTEGER)");
+        test.exec("INSERT INTO t VALUES (0), (1), (2), (2), (2), (2), (2), (2)");
+        test.exec("ANALYZE t");
+        assert_eq!(
+            test.rows("SELECT x FROM t WHERE x <> 0 AND 10 / x = 5"),
+            ["2", "2", "2", "2", "2", "2"]
+        );
+    }
+
+    #[test]
+    fn analyze_reorders_comparisons_without_changing_the_result() {
+        let mut test = TestDatabase::new("conjunct_reorder");
+        test.exec("CREATE TABLE t (x INTEGER, y INTEGER)");
+        test.exec("INSERT INTO t VALUES (1, 1), (2, 1), (3, 1), (4, 2)");
+        test.exec("ANALYZE t");
+        assert_eq!(test.rows("SELECT x FROM t WHERE y = 1 AND x > 2"), ["3"]);
+        let plan = test.exec("EXPLAIN SELECT x FROM t WHERE y = 1 AND x > 2");
+        assert!(plan.contains("((x > 2) AND (y = 1))"), "{}", plan);
+    }
+
+    // Linha do filtro no EXPLAIN, com a estimativa de linhas.
+    fn filter_estimate(test: &mut TestDatabase, condition: &str) -> String {
+        let plan = test.rows(&format!("EXPLAIN SELECT x FROM t WHERE {}", condition));
+        plan[1].clone()
+    }
+
+    fn insert(test: &mut TestDatabase, values: std::ops::RangeInclusive<i32>) {
+        let values: Vec<String> = values.map(|i| format!("({})", i)).collect();
+        test.exec(&format!("INSERT INTO t VALUES {}", values.join(", ")));
+    }
+
+    #[test]
+    fn analyze_estimates_from_the_histogram_and_null_fraction() {
+        let mut test = TestDatabase::new("analyze_estimates");
+        test.exec("CREATE TABLE t (x INTEGER)");
+        insert(&mut test, 1..=40);
+        test.exec("INSERT INTO t VALUES (NULL), (NULL), (NULL), (NULL), (NULL)");
+        // Sem estatísticas, a estimativa usa uma seletividade fixa.
+        assert_eq!(
+            filter_estimate(&mut test, "x <= 10"),
+            "-> Filtro: (x <= 10)  (linhas estimadas: 15)"
+        );
+        test.exec("ANALYZE t");
+        assert_eq!(
+            filter_estimate(&mut test, "x <= 10"),
+            "-> Filtro: (x <= 10)  (linhas estimadas: 11)"
+        );
+        assert_eq!(
+            filter_estimate(&mut test, "x IS NULL"),
+            "-> Filtro: x IS NULL  (linhas estimadas: 5)"
+        );
+    }
+
+    #[test]
+    fn statistics_are_refreshed_after_enough_changes() {
+        let mut test = TestDatabase::new("autoanalyze");
+        test.exec("CREATE TABLE t (x INTEGER)");
+        insert(&mut test, 1..=40);
+        test.exec("ANALYZE t");
+        test.exec("ALTER TABLE t SET (autoanalyze_fraction = 1)");
+        test.fail("ALTER TABLE t SET (autoanalyze_fraction = 2)");
+
+        // O limite é 50 + 1 * 40 alterações desde a última análise.
+        insert(&mut test, 41..=130);
+        assert_eq!(
+            filter_estimate(&mut test, "x <= 10"),
+            "-> Filtro: (x <= 10)  (linhas estimadas: 36)"
+        );
+        insert(&mut test, 131..=131);
+        assert_eq!(
+            filter_estimate(&mut test, "x <= 10"),
+            "-> Filtro: (x <= 10)  (linhas estimadas: 10)"
+        );
+        test.reopen();
+        assert_eq!(
+            filter_estimate(&mut test, "x <= 10"),
+            "-> Filtro: (x <= 10)  (linhas estimadas: 10)"
+        );
+    }
+}
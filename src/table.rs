@@ -1,14 +1,134 @@
+use crate::sql::ColumnDefinition;
+use crate::statistics::Statistics;
+use crate::trigger::Trigger;
+use crate::value::{DataType, Value};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Column {
     pub name: String,
+    #[serde(default)]
+    pub data_type: DataType,
     pub is_primary_key: bool,
     pub foreign_key: Option<(String, String)>,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub is_not_null: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UniqueConstraint {
+    pub name: String,
+    pub columns: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Table {
     pub columns: Vec<Column>,
-    pub data: Vec<Vec<String>>,
+    // Compartilhado entre os snapshots das transações; só é copiado quando alterado.
+    pub data: Arc<Vec<Vec<Value>>>,
+    #[serde(default)]
+    pub constraints: Vec<UniqueConstraint>,
+    #[serde(default)]
+    pub view: Option<View>,
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    #[serde(default)]
+    pub statistics: Statistics,
+    // Transação que criou cada linha de `data`. Só é atualizado no commit, então dentro de
+    // uma transação vale apenas para as linhas do snapshot.
+    #[serde(default)]
+    pub row_xmin: Arc<Vec<u64>>,
+}
+
+// Views ficam no mesmo catálogo das tabelas; as materializadas guardam o resultado em `data`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct View {
+    pub query: String,
+    pub materialized: bool,
+    pub dependencies: Vec<String>,
+}
+
+// Conjunto de colunas cujos valores não podem se repetir na tabela.
+pub struct UniqueKey {
+    pub name: String,
+    pub columns: Vec<usize>,
+    pub is_primary_key: bool,
+}
+
+impl Table {
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    // Linhas gravadas antes do controle de versões contam como da transação 0.
+    pub fn xmin(&self, row: usize) -> u64 {
+        self.row_xmin.get(row).copied().unwrap_or(0)
+    }
+
+    pub fn unique_keys(&self, table_name: &str) -> Vec<UniqueKey> {
+        let mut keys = Vec::new();
+        let primary_key: Vec<usize> = (0..self.columns.len())
+            .filter(|&i| self.columns[i].is_primary_key)
+            .collect();
+        if !primary_key.is_empty() {
+            keys.push(UniqueKey {
+                name: format!("{}_pkey", table_name),
+                columns: primary_key,
+                is_primary_key: true,
+            });
+        }
+        for constraint in &self.constraints {
+            keys.push(UniqueKey {
+                name: constraint.name.clone(),
+                columns: constraint
+                    .columns
+                    .iter()
+                    .filter_map(|c| self.column_index(c))
+                    .collect(),
+                is_primary_key: false,
+            });
+        }
+        keys
+    }
+}
+
+impl UniqueKey {
+    // Linhas com NULL em qualquer coluna da chave nunca entram em conflito.
+    pub fn value_of(&self, row: &[Value]) -> Option<String> {
+        let values: Vec<&Value> = self
+            .columns
+            .iter()
+            .map(|&i| row.get(i).unwrap_or(&Value::Null))
+            .collect();
+        if values.iter().any(|v| v.is_null()) {
+            return None;
+        }
+        Some(format!("{:?}", values))
+    }
+
+    pub fn describe(&self, columns: &[Column], row: &[Value]) -> String {
+        let names: Vec<&str> = self
+            .columns
+            .iter()
+            .map(|&i| columns[i].name.as_str())
+            .collect();
+        let values: Vec<String> = self.columns.iter().map(|&i| row[i].to_string()).collect();
+        format!("({})=({})", names.join(", "), values.join(", "))
+    }
+}
+
+impl From<ColumnDefinition> for Column {
+    fn from(definition: ColumnDefinition) -> Self {
+        Column {
+            name: definition.name,
+            data_type: definition.data_type,
+            is_primary_key: definition.is_primary_key,
+            foreign_key: definition.foreign_key,
+            default: definition.default.map(|expr| expr.to_string()),
+            is_not_null: definition.is_not_null,
+        }
+    }
 }
@@ -0,0 +1,408 @@
+use crate::procedure::Procedure;
+use crate::query::TableRead;
+use crate::sequence::Sequence;
+use crate::sql::IsolationLevel;
+use crate::table::Table;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// Transação aberta por uma conexão. Os comandos trabalham sobre uma cópia das tabelas
+// confirmadas quando ela começou; os dados de cada tabela são compartilhados com o snapshot
+// até a primeira alteração. No commit, as alterações são aplicadas sobre o estado confirmado
+// mais recente.
+pub struct Transaction {
+    pub xid: u64,
+    pub isolation_level: IsolationLevel,
+    // Se algum comando já foi executado; depois disso o nível não pode mais mudar.
+    pub started: bool,
+    // Contadores de commits do banco quando o snapshot foi tirado.
+    pub snapshot_commit: u64,
+    pub snapshot_schema: u64,
+    pub snapshot: HashMap<String, Table>,
+    pub tables: HashMap<String, Table>,
+    pub procedures: HashMap<String, Procedure>,
+    // Definição de cada sequência antes da primeira alteração feita pela transação; `None`
+    // quando ela foi criada na transação.
+    pub sequences: HashMap<String, Option<Sequence>>,
+    pub changed_schema: bool,
+    // Um comando falhou; só ROLLBACK (ou ROLLBACK TO SAVEPOINT) é aceito.
+    pub aborted: bool,
+    // Motivo quando o servidor já reverteu a transação por exceder um tempo limite; ela fica
+    // abortada na sessão até o ROLLBACK.
+    pub cancelled: Option<String>,
+    // Em READ COMMITTED, se alterações da transação já foram combinadas com as de outras
+    // ao renovar o snapshot.
+    pub rebased: bool,
+    // Leituras feitas em SERIALIZABLE, conferidas no commit.
+    pub reads: Vec<TableRead>,
+    pub savepoints: Vec<Savepoint>,
+}
+
+// Transação preparada com PREPARE TRANSACTION, gravada no arquivo do banco até o COMMIT
+// PREPARED ou o ROLLBACK PREPARED. As alterações já foram combinadas com o estado confirmado
+// no PREPARE, e as tabelas envolvidas ficam travadas em modo SHARE para que o commit não
+// possa mais falhar.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PreparedTransaction {
+    pub xid: u64,
+    pub prepared_at: String,
+    // Versão confirmada de cada tabela alterada no momento do PREPARE e como ela fica depois
+    // do commit.
+    pub tables: HashMap<String, (Table, Table)>,
+    pub locked_tables: Vec<String>,
+}
+
+// Estado da transação quando o savepoint foi criado.
+pub struct Savepoint {
+    pub name: String,
+    snapshot_commit: u64,
+    snapshot_schema: u64,
+    snapshot: HashMap<String, Table>,
+    tables: HashMap<String, Table>,
+    procedures: HashMap<String, Procedure>,
+    sequences: HashMap<String, Option<Sequence>>,
+    changed_schema: bool,
+    // Definições das sequências naquele momento.
+    pub definitions: HashMap<String, Sequence>,
+}
+
+impl Transaction {
+    pub fn new(
+        xid: u64,
+        isolation_level: IsolationLevel,
+        commits: (u64, u64),
+        tables: &HashMap<String, Table>,
+        procedures: &HashMap<String, Procedure>,
+    ) -> Self {
+        Transaction {
+            xid,
+            isolation_level,
+            started: false,
+            snapshot_commit: commits.0,
+            snapshot_schema: commits.1,
+            snapshot: tables.clone(),
+            tables: tables.clone(),
+            procedures: procedures.clone(),
+            sequences: HashMap::new(),
+            changed_schema: false,
+            aborted: false,
+            cancelled: None,
+            rebased: false,
+            reads: Vec::new(),
+            savepoints: Vec::new(),
+        }
+    }
+
+    pub fn savepoint(&mut self, name: &str, definitions: &HashMap<String, Sequence>) {
+        self.savepoints.push(Savepoint {
+            name: name.to_string(),
+            snapshot_commit: self.snapshot_commit,
+            snapshot_schema: self.snapshot_schema,
+            snapshot: self.snapshot.clone(),
+            tables: self.tables.clone(),
+            procedures: self.procedures.clone(),
+            sequences: self.sequences.clone(),
+            changed_schema: self.changed_schema,
+            definitions: definitions.clone(),
+        });
+    }
+
+    // Savepoint mais recente com o nome, se houver.
+    pub fn find_savepoint(&self, name: &str) -> Option<usize> {
+        self.savepoints.iter().rposition(|s| s.name == name)
+    }
+
+    // Volta ao estado do savepoint, que continua existindo; os criados depois dele são
+    // descartados. As leituras já feitas continuam valendo para SERIALIZABLE.
+    pub fn rollback_to(&mut self, position: usize) {
+        self.savepoints.truncate(position + 1);
+        let savepoint = &self.savepoints[position];
+        self.snapshot_commit = savepoint.snapshot_commit;
+        self.snapshot_schema = savepoint.snapshot_schema;
+        self.snapshot = savepoint.snapshot.clone();
+        self.tables = savepoint.tables.clone();
+        self.procedures = savepoint.procedures.clone();
+        self.sequences = savepoint.sequences.clone();
+        self.changed_schema = savepoint.changed_schema;
+        self.aborted = false;
+    }
+
+    // READ COMMITTED: antes de cada comando, o snapshot passa a ser o estado confirmado atual,
+    // mantendo as alterações ainda pendentes da transação.
+    pub fn refresh(
+        &mut self,
+        commits: (u64, u64),
+        tables: &HashMap<String, Table>,
+        procedures: &HashMap<String, Procedure>,
+    ) -> Result<(), String> {
+        if commits.0 == self.snapshot_commit || self.changed_schema {
+            return Ok(());
+        }
+        let changed = self
+            .changed_tables()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        if commits.1 != self.snapshot_schema {
+            // Com o esquema alterado, só é possível recomeçar de um snapshot novo.
+            if !changed.is_empty() {
+                return Ok(());
+            }
+            self.tables = tables.clone();
+            self.procedures = procedures.clone();
+        } else {
+            let mut work = tables.clone();
+            for name in &changed {
+                let work_table = &self.tables[name];
+                let table = match (self.snapshot.get(name), tables.get(name)) {
+                    (Some(snapshot), Some(current))
+                        if !Arc::ptr_eq(&snapshot.data, &current.data) =>
+                    {
+                        self.rebased = true;
+                        merge(snapshot, work_table, current, self.xid).ok_or_else(|| {
+                            format!(
+                                "Não foi possível serializar o acesso devido a uma alteração concorrente na tabela '{}'.",
+                                name
+                            )
+                        })?
+                    }
+                    _ => work_table.clone(),
+                };
+                work.insert(name.clone(), table);
+            }
+            self.tables = work;
+        }
+        self.snapshot = tables.clone();
+        self.snapshot_commit = commits.0;
+        self.snapshot_schema = commits.1;
+        Ok(())
+    }
+
+    // Os valores das sequências não são transacionais; apenas criação, remoção e mudança de
+    // dono são registradas para serem desfeitas no rollback.
+    pub fn record_sequences(
+        &mut self,
+        before: &HashMap<String, Sequence>,
+        after: &HashMap<String, Sequence>,
+    ) {
+        for name in before.keys().chain(after.keys()) {
+            let changed = match (before.get(name), after.get(name)) {
+                (Some(old), Some(new)) => !old.same_definition(new),
+                _ => true,
+            };
+            if changed && !self.sequences.contains_key(name) {
+                self.sequences
+                    .insert(name.clone(), before.get(name).cloned());
+            }
+        }
+    }
+
+    // Tabelas cujos dados mudaram desde o snapshot.
+    pub fn changed_tables(&self) -> Vec<&String> {
+        self.tables
+            .iter()
+            .filter(|(name, table)| {
+                self.snapshot
+                    .get(*name)
+                    .is_none_or(|snapshot| !Arc::ptr_eq(&snapshot.data, &table.data))
+            })
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+// Versão da tabela alterada pela transação quando ninguém mais a alterou desde o snapshot.
+pub fn install(snapshot: Option<&Table>, work: &Table, xid: u64) -> Table {
+    let versions = match snapshot {
+        Some(snapshot) if Arc::ptr_eq(&snapshot.data, &work.data) => return work.clone(),
+        Some(snapshot) => diff(snapshot, work, xid).0,
+        None => vec![xid; work.data.len()],
+    };
+    let mut table = work.clone();
+    table.row_xmin = Arc::new(versions);
+    table
+}
+
+// Aplica sobre `current` as alterações feitas pela transação desde o snapshot. Falha quando
+// uma versão que a transação removeu ou atualizou já não é a atual (conflito de escrita).
+pub fn merge(snapshot: &Table, work: &Table, current: &Table, xid: u64) -> Option<Table> {
+    let (versions, removed) = diff(snapshot, work, xid);
+    let mut positions: HashMap<(String, u64), Vec<usize>> = HashMap::new();
+    for (i, row) in current.data.iter().enumerate() {
+        positions
+            .entry((row_key(row), current.xmin(i)))
+            .or_default()
+            .push(i);
+    }
+    let mut deleted = HashSet::new();
+    for version in removed {
+        deleted.insert(positions.get_mut(&version)?.pop()?);
+    }
+
+    let mut data = Vec::with_capacity(current.data.len());
+    let mut row_xmin = Vec::with_capacity(current.data.len());
+    for (i, row) in current.data.iter().enumerate() {
+        if !deleted.contains(&i) {
+            data.push(row.clone());
+            row_xmin.push(current.xmin(i));
+        }
+    }
+    for (row, version) in work.data.iter().zip(versions) {
+        if version == xid {
+            data.push(row.clone());
+            row_xmin.push(xid);
+        }
+    }
+    let mut table = current.clone();
+    table.data = Arc::new(data);
+    table.row_xmin = Arc::new(row_xmin);
+    Some(table)
+}
+
+// Linhas que existem em apenas uma das duas versões da tabela.
+pub fn changed_rows<'a>(before: &'a Table, after: &'a Table) -> Vec<&'a Vec<Value>> {
+    let mut remaining: HashMap<String, Vec<&Vec<Value>>> = HashMap::new();
+    for row in before.data.iter() {
+        remaining.entry(row_key(row)).or_default().push(row);
+    }
+    let mut rows = Vec::new();
+    for row in after.data.iter() {
+        match remaining.get_mut(&row_key(row)).and_then(Vec::pop) {
+            Some(_) => {}
+            None => rows.push(row),
+        }
+    }
+    rows.extend(remaining.into_values().flatten());
+    rows
+}
+
+// Linhas da transação iguais a uma do snapshot mantêm a versão dela; as demais são versões
+// novas de `xid`. Devolve também as versões do snapshot que deixaram de existir.
+fn diff(snapshot: &Table, work: &Table, xid: u64) -> (Vec<u64>, Vec<(String, u64)>) {
+    let mut remaining: HashMap<String, Vec<u64>> = HashMap::new();
+    for (i, row) in snapshot.data.iter().enumerate() {
+        remaining
+            .entry(row_key(row))
+            .or_default()
+            .push(snapshot.xmin(i));
+    }
+    let versions = work
+        .data
+        .iter()
+        .map(|row| {
+            remaining
+                .get_mut(&row_key(row))
+                .and_then(Vec::pop)
+                .unwrap_or(xid)
+        })
+        .collect();
+    let removed = remaining
+        .into_iter()
+        .flat_map(|(key, versions)| versions.into_iter().map(move |xmin| (key.clone(), xmin)))
+        .collect();
+    (versions, removed)
+}
+
+pub fn row_key(row: &[Value]) -> String {
+    format!("{:?}", row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statistics::Statistics;
+
+    fn table(rows: &[(i64, &str)], xmin: &[u64]) -> Table {
+        Table {
+            columns: Vec::new(),
+            data: Arc::new(
+                rows.iter()
+                    .map(|(id, name)| vec![Value::Integer(*id), Value::Text(name.to_string())])
+                    .collect(),
+            ),
+            constraints: Vec::new(),
+            view: None,
+            triggers: Vec::new(),
+            statistics: Statistics::default(),
+            row_xmin: Arc::new(xmin.to_vec()),
+        }
+    }
+
+    fn rows(table: &Table) -> Vec<(Vec<Value>, u64)> {
+        let mut rows = table
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, row)| (row.clone(), table.xmin(i)))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|(row, _)| row_key(row));
+        rows
+    }
+
+    #[test]
+    fn merge_keeps_changes_to_different_rows() {
+        let snapshot = table(&[(1, "a"), (2, "b")], &[1, 1]);
+        // A transação 3 alterou a linha 1; a 2 já confirmou uma alteração na linha 2.
+        let work = table(&[(1, "x"), (2, "b")], &[1, 1]);
+        let current = table(&[(1, "a"), (2, "y")], &[1, 2]);
+        let merged = merge(&snapshot, &work, &current, 3).unwrap();
+        assert_eq!(rows(&merged), rows(&table(&[(1, "x"), (2, "y")], &[3, 2])));
+    }
+
+    #[test]
+    fn merge_rejects_a_row_changed_by_both_transactions() {
+        let snapshot = table(&[(1, "a")], &[1]);
+        let work = table(&[(1, "x")], &[1]);
+        let current = table(&[(1, "y")], &[2]);
+        assert!(merge(&snapshot, &work, &current, 3).is_none());
+
+        // Uma linha removida pela outra transação também é um conflito.
+        let current = table(&[], &[]);
+        assert!(merge(&snapshot, &work, &current, 3).is_none());
+    }
+
+    #[test]
+    fn merge_tells_identical_rows_apart_by_version() {
+        // A linha foi apagada e inserida de novo com os mesmos valores pela transação 2: a
+        // versão que a transação 3 viu não existe mais.
+        let snapshot = table(&[(1, "a")], &[1]);
+        let work = table(&[], &[]);
+        let current = table(&[(1, "a")], &[2]);
+        assert!(merge(&snapshot, &work, &current, 3).is_none());
+    }
+
+    #[test]
+    fn install_assigns_new_versions_only_to_changed_rows() {
+        let snapshot = table(&[(1, "a"), (2, "b")], &[1, 2]);
+        let work = table(&[(1, "a"), (2, "x"), (3, "c")], &[1, 2]);
+        let installed = install(Some(&snapshot), &work, 5);
+        assert_eq!(installed.row_xmin.as_ref(), &vec![1, 5, 5]);
+        assert_eq!(changed_rows(&snapshot, &work).len(), 3);
+    }
+
+    #[test]
+    fn rollback_to_savepoint_restores_the_tables() {
+        let mut tables = HashMap::new();
+        tables.insert("t".to_string(), table(&[(1, "a")], &[1]));
+        let mut transaction = Transaction::new(
+            3,
+            IsolationLevel::default(),
+            (0, 0),
+            &tables,
+            &HashMap::new(),
+        );
+        transaction.savepoint("s", &HashMap::new());
+        transaction
+            .tables
+            .insert("t".to_string(), table(&[(1, "a"), (2, "b")], &[1]));
+        transaction.aborted = true;
+        assert_eq!(transaction.changed_tables().len(), 1);
+
+        transaction.rollback_to(transaction.find_savepoint("s").unwrap());
+        assert!(transaction.changed_tables().is_empty());
+        assert!(!transaction.aborted);
+        assert_eq!(transaction.savepoints.len(), 1);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::sql::{self, ConflictAction, Expr, Statement, TriggerEvent, TriggerTiming};
+use crate::table::Column;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trigger {
+    pub name: String,
+    pub timing: TriggerTiming,
+    pub events: Vec<TriggerEvent>,
+    // Comando CREATE TRIGGER original; o corpo é lido novamente a cada disparo.
+    pub definition: String,
+}
+
+impl Trigger {
+    // Diz se o gatilho, criado na tabela `owner`, usa a tabela `table` ou, com `column`, essa
+    // coluna dela: em NEW.<coluna> e OLD.<coluna> ou nos comandos do corpo. Um INSERT sem lista
+    // de colunas depende de todas elas.
+    pub fn references(
+        &self,
+        owner: &str,
+        table: &str,
+        column: Option<&str>,
+    ) -> Result<bool, String> {
+        let Statement::CreateTrigger(definition) = sql::parse(&self.definition)? else {
+            return Err(format!("Gatilho '{}' inválido.", self.name));
+        };
+        let uses_table =
+            |statement: &Statement| statement.referenced_tables().iter().any(|t| t == table);
+        let Some(column) = column else {
+            return Ok(definition.body.iter().any(uses_table));
+        };
+        let mentions = |names: &[(Option<String>, String)], uses_table: bool| {
+            names.iter().any(|(qualifier, name)| {
+                let row_reference = qualifier.as_deref().is_some_and(|q| {
+                    q.eq_ignore_ascii_case("NEW") || q.eq_ignore_ascii_case("OLD")
+                });
+                name == column
+                    && if row_reference {
+                        owner == table
+                    } else {
+                        uses_table
+                    }
+            })
+        };
+        if let Some(condition) = &definition.condition {
+            let mut names = Vec::new();
+            condition.transform(&mut |e| collect_column(e, &mut names));
+            if mentions(&names, false) {
+                return Ok(true);
+            }
+        }
+        Ok(definition.body.iter().any(|statement| {
+            let positional = matches!(statement, Statement::Insert(insert) if insert.table == table && insert.columns.is_none());
+            positional || mentions(&statement_columns(statement), uses_table(statement))
+        }))
+    }
+}
+
+// Guarda a coluna citada, com a tabela que a qualifica; usado com `transform`, que percorre
+// todas as subexpressões enquanto recebe `None`.
+fn collect_column(expr: &Expr, names: &mut Vec<(Option<String>, String)>) -> Option<Expr> {
+    if let Expr::Column { table, name } = expr {
+        names.push((table.clone(), name.clone()));
+    }
+    None
+}
+
+// Colunas citadas nas expressões do comando e as que ele grava.
+fn statement_columns(statement: &Statement) -> Vec<(Option<String>, String)> {
+    let mut names = Vec::new();
+    statement.transform(&mut |e| collect_column(e, &mut names));
+    let mut targets = Vec::new();
+    match statement {
+        Statement::Insert(insert) => {
+            targets.extend(insert.columns.iter().flatten());
+            if let Some(on_conflict) = &insert.on_conflict {
+                targets.extend(on_conflict.target.iter().flatten());
+                if let ConflictAction::DoUpdate { assignments, .. } = &on_conflict.action {
+                    targets.extend(assignments.iter().map(|a| &a.column));
+                }
+            }
+        }
+        Statement::Update(update) => targets.extend(update.assignments.iter().map(|a| &a.column)),
+        _ => {}
+    }
+    names.extend(targets.into_iter().map(|name| (None, name.clone())));
+    names
+}
+
+// Linha afetada por um comando, com os valores antes (OLD) e depois (NEW) da alteração.
+pub struct RowChange {
+    pub event: TriggerEvent,
+    pub old: Option<Vec<Value>>,
+    pub new: Option<Vec<Value>>,
+}
+
+impl RowChange {
+    pub fn inserted(row: Vec<Value>) -> Self {
+        RowChange {
+            event: TriggerEvent::Insert,
+            old: None,
+            new: Some(row),
+        }
+    }
+
+    pub fn updated(old: Vec<Value>, new: Vec<Value>) -> Self {
+        RowChange {
+            event: TriggerEvent::Update,
+            old: Some(old),
+            new: Some(new),
+        }
+    }
+
+    pub fn deleted(row: Vec<Value>) -> Self {
+        RowChange {
+            event: TriggerEvent::Delete,
+            old: Some(row),
+            new: None,
+        }
+    }
+
+    // Substitui as referências NEW.<coluna> e OLD.<coluna> pelos valores da linha.
+    pub fn bind_expr(&self, expr: &Expr, columns: &[Column]) -> Result<Expr, String> {
+        let mut error = None;
+        let bound = expr.transform(&mut |e| self.bind_reference(e, columns, &mut error));
+        error.map_or(Ok(bound), Err)
+    }
+
+    pub fn bind_statement(
+        &self,
+        statement: &Statement,
+        columns: &[Column],
+    ) -> Result<Statement, String> {
+        let mut error = None;
+        let bound = statement.transform(&mut |e| self.bind_reference(e, columns, &mut error));
+        error.map_or(Ok(bound), Err)
+    }
+
+    fn bind_reference(
+        &self,
+        expr: &Expr,
+        columns: &[Column],
+        error: &mut Option<String>,
+    ) -> Option<Expr> {
+        let Expr::Column {
+            table: Some(table),
+            name,
+        } = expr
+        else {
+            return None;
+        };
+        let (label, row) = if table.eq_ignore_ascii_case("NEW") {
+            ("NEW", &self.new)
+        } else if table.eq_ignore_ascii_case("OLD") {
+            ("OLD", &self.old)
+        } else {
+            return None;
+        };
+        let Some(row) = row else {
+            error.get_or_insert(format!(
+                "{} não está disponível em gatilhos de {}.",
+                label,
+                self.event.keyword()
+            ));
+            return None;
+        };
+        match columns.iter().position(|c| &c.name == name) {
+            Some(index) => Some(Expr::Literal(
+                row.get(index).cloned().unwrap_or(Value::Null),
+            )),
+            None => {
+                error.get_or_insert(format!("Coluna '{}' não existe em {}.", name, label));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::{row_count, TestDatabase};
+
+    fn database(name: &str) -> TestDatabase {
+        let mut test = TestDatabase::new(name);
+        test.exec("CREATE TABLE t (id INTEGER, v INTEGER, w INTEGER)");
+        test.exec("CREATE TABLE log (v INTEGER)");
+        test.exec("INSERT INTO t VALUES (1, 1, 1), (2, 2, 2)");
+        test
+    }
+
+    #[test]
+    fn columns_and_tables_used_by_a_trigger_cannot_change() {
+        let mut test = database("trigger_dependencies");
+        test.exec(
+            "CREATE TRIGGER ti AFTER INSERT ON t FOR EACH ROW BEGIN INSERT INTO log VALUES (NEW.v); END",
+        );
+        test.fail("ALTER TABLE t RENAME COLUMN v TO val");
+        test.fail("ALTER TABLE t DROP COLUMN v");
+        test.fail("ALTER TABLE log RENAME TO history");
+        test.fail("DROP TABLE log");
+        test.exec("ALTER TABLE t RENAME COLUMN w TO z");
+        test.exec("INSERT INTO t VALUES (3, 3, 3)");
+        assert_eq!(test.rows("SELECT v FROM log"), ["3"]);
+
+        test.exec("DROP TRIGGER ti ON t");
+        test.exec("ALTER TABLE t RENAME COLUMN v TO val");
+    }
+
+    #[test]
+    fn before_trigger_writes_to_its_own_table_are_kept() {
+        let mut test = database("trigger_same_table");
+        test.exec(
+            "CREATE TRIGGER tu BEFORE UPDATE OR DELETE ON t FOR EACH ROW WHEN (OLD.id < 100) \
+             BEGIN INSERT INTO t (id, v) VALUES (OLD.id + 100, OLD.v); END",
+        );
+        test.exec("UPDATE t SET v = 10 WHERE id = 1");
+        assert_eq!(
+            test.rows("SELECT id, v FROM t ORDER BY id"),
+            ["1 | 10", "2 | 2", "101 | 1"]
+        );
+        test.exec("DELETE FROM t WHERE id = 2");
+        assert_eq!(
+            test.rows("SELECT id, v FROM t ORDER BY id"),
+            ["1 | 10", "101 | 1", "102 | 2"]
+        );
+    }
+
+    #[test]
+    fn before_trigger_cannot_modify_the_row_being_updated() {
+        let mut test = database("trigger_same_row");
+        test.exec(
+            "CREATE TRIGGER tu BEFORE UPDATE ON t FOR EACH ROW WHEN (NEW.v <> 0) \
+             BEGIN UPDATE t SET v = 0 WHERE id = OLD.id; END",
+        );
+        test.fail("UPDATE t SET v = 5 WHERE id = 1");
+        assert_eq!(test.rows("SELECT v FROM t ORDER BY id"), ["1", "2"]);
+    }
+
+    #[test]
+    fn triggers_run_for_each_matching_row() {
+        let mut test = database("trigger_rows");
+        test.exec(
+            "CREATE TRIGGER ta AFTER UPDATE OR DELETE ON t FOR EACH ROW WHEN (OLD.v <> 2) \
+             BEGIN INSERT INTO log VALUES (OLD.v * 10); END",
+        );
+        test.exec("UPDATE t SET w = 0");
+        test.exec("DELETE FROM t WHERE id = 1");
+        assert_eq!(test.rows("SELECT v FROM log"), ["10", "10"]);
+
+        // Os efeitos do gatilho fazem parte da transação e do comando.
+        test.exec("BEGIN");
+        test.exec("DELETE FROM t");
+        test.exec("ROLLBACK");
+        assert_eq!(row_count(test.exec("SELECT * FROM log")), 2);
+        test.exec(
+            "CREATE TRIGGER tf AFTER INSERT ON t FOR EACH ROW WHEN (NEW.v < 0) \
+             BEGIN INSERT INTO nada VALUES (1); END",
+        );
+        test.fail("INSERT INTO t VALUES (5, 5, 5), (6, -1, 6)");
+        assert_eq!(test.rows("SELECT id FROM t"), ["2"]);
+    }
+
+    #[test]
+    fn recursive_triggers_stop_at_the_depth_limit() {
+        let mut test = database("trigger_depth");
+        test.exec(
+            "CREATE TRIGGER tr AFTER INSERT ON log FOR EACH ROW \
+             BEGIN INSERT INTO log VALUES (NEW.v + 1); END",
+        );
+        test.fail("INSERT INTO log VALUES (1)");
+        assert_eq!(row_count(test.exec("SELECT * FROM log")), 0);
+    }
+}
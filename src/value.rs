@@ -0,0 +1,256 @@
+use crate::datetime::{self, Interval};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DataType {
+    Integer,
+    Float,
+    #[default]
+    Text,
+    Boolean,
+    Date,
+    Time,
+    Timestamp,
+    Interval,
+}
+
+impl DataType {
+    pub fn from_name(name: &str) -> Option<DataType> {
+        match name.to_uppercase().as_str() {
+            "INTEGER" | "INT" | "BIGINT" | "SMALLINT" => Some(DataType::Integer),
+            "FLOAT" | "REAL" | "DOUBLE" | "NUMERIC" | "DECIMAL" => Some(DataType::Float),
+            "TEXT" | "VARCHAR" | "CHAR" | "STRING" => Some(DataType::Text),
+            "BOOLEAN" | "BOOL" => Some(DataType::Boolean),
+            "DATE" => Some(DataType::Date),
+            "TIME" => Some(DataType::Time),
+            "TIMESTAMP" | "DATETIME" => Some(DataType::Timestamp),
+            "INTERVAL" => Some(DataType::Interval),
+            _ => None,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, DataType::Integer | DataType::Float)
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        matches!(
+            self,
+            DataType::Date | DataType::Time | DataType::Timestamp | DataType::Interval
+        )
+    }
+
+    // Tipo comum entre dois tipos, usado em operações de conjunto e expressões.
+    pub fn unify(
+        left: Option<DataType>,
+        right: Option<DataType>,
+    ) -> Result<Option<DataType>, String> {
+        match (left, right) {
+            (None, other) | (other, None) => Ok(other),
+            (Some(l), Some(r)) if l == r => Ok(Some(l)),
+            (Some(l), Some(r)) if l.is_numeric() && r.is_numeric() => Ok(Some(DataType::Float)),
+            (Some(DataType::Date), Some(DataType::Timestamp))
+            | (Some(DataType::Timestamp), Some(DataType::Date)) => Ok(Some(DataType::Timestamp)),
+            (Some(l), Some(r)) => Err(format!("Tipos incompatíveis: {} e {}.", l, r)),
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DataType::Integer => "INTEGER",
+            DataType::Float => "FLOAT",
+            DataType::Text => "TEXT",
+            DataType::Boolean => "BOOLEAN",
+            DataType::Date => "DATE",
+            DataType::Time => "TIME",
+            DataType::Timestamp => "TIMESTAMP",
+            DataType::Interval => "INTERVAL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Valores simples são gravados como JSON puro, o que mantém compatíveis os backups
+// antigos; datas e intervalos são gravados com a variante explícita.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Value {
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(NaiveDateTime),
+    Interval(Interval),
+    #[serde(untagged)]
+    Null,
+    #[serde(untagged)]
+    Boolean(bool),
+    #[serde(untagged)]
+    Integer(i64),
+    #[serde(untagged)]
+    Float(f64),
+    #[serde(untagged)]
+    Text(String),
+}
+
+impl Value {
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Boolean(_) => Some(DataType::Boolean),
+            Value::Integer(_) => Some(DataType::Integer),
+            Value::Float(_) => Some(DataType::Float),
+            Value::Text(_) => Some(DataType::Text),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Interval(_) => Some(DataType::Interval),
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<NaiveDateTime> {
+        match self {
+            Value::Timestamp(ts) => Some(*ts),
+            Value::Date(date) => date.and_hms_opt(0, 0, 0),
+            Value::Text(s) => datetime::parse_timestamp(s),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Text(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Float(f) if f.fract() == 0.0 => Some(*f as i64),
+            Value::Text(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn cast(&self, data_type: DataType) -> Result<Value, String> {
+        let invalid = || format!("Valor '{}' inválido para o tipo {}.", self, data_type);
+        match (self, data_type) {
+            (Value::Null, _) => Ok(Value::Null),
+            (Value::Integer(_), DataType::Integer)
+            | (Value::Float(_), DataType::Float)
+            | (Value::Text(_), DataType::Text)
+            | (Value::Boolean(_), DataType::Boolean)
+            | (Value::Date(_), DataType::Date)
+            | (Value::Time(_), DataType::Time)
+            | (Value::Timestamp(_), DataType::Timestamp)
+            | (Value::Interval(_), DataType::Interval) => Ok(self.clone()),
+            (_, DataType::Text) => Ok(Value::Text(self.to_string())),
+            (Value::Float(f), DataType::Integer) => Ok(Value::Integer(f.round() as i64)),
+            (Value::Integer(i), DataType::Float) => Ok(Value::Float(*i as f64)),
+            (Value::Text(_), DataType::Integer) => {
+                self.as_i64().map(Value::Integer).ok_or_else(invalid)
+            }
+            (Value::Text(_), DataType::Float) => {
+                self.as_f64().map(Value::Float).ok_or_else(invalid)
+            }
+            (Value::Text(s), DataType::Boolean) => match s.trim().to_lowercase().as_str() {
+                "true" | "t" | "1" | "sim" => Ok(Value::Boolean(true)),
+                "false" | "f" | "0" | "nao" | "não" => Ok(Value::Boolean(false)),
+                _ => Err(invalid()),
+            },
+            (Value::Integer(i), DataType::Boolean) => Ok(Value::Boolean(*i != 0)),
+            (Value::Boolean(b), DataType::Integer) => Ok(Value::Integer(*b as i64)),
+            (Value::Text(s), DataType::Date) => {
+                datetime::parse_date(s).map(Value::Date).ok_or_else(invalid)
+            }
+            (Value::Text(s), DataType::Time) => {
+                datetime::parse_time(s).map(Value::Time).ok_or_else(invalid)
+            }
+            (Value::Text(s), DataType::Interval) => {
+                Interval::parse(s)?.map(Value::Interval).ok_or_else(invalid)
+            }
+            (Value::Text(_) | Value::Date(_), DataType::Timestamp) => self
+                .as_timestamp()
+                .map(Value::Timestamp)
+                .ok_or_else(invalid),
+            (Value::Timestamp(ts), DataType::Date) => Ok(Value::Date(ts.date())),
+            (Value::Timestamp(ts), DataType::Time) => Ok(Value::Time(ts.time())),
+            _ => Err(invalid()),
+        }
+    }
+
+    // Comparação SQL: NULL não é comparável e texto é convertido quando comparado a números.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+            (Value::Text(l), Value::Text(r)) => Some(l.cmp(r)),
+            (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+            (Value::Date(l), Value::Date(r)) => Some(l.cmp(r)),
+            (Value::Time(l), Value::Time(r)) => Some(l.cmp(r)),
+            (Value::Interval(l), Value::Interval(r)) => Some(l.compare(r)),
+            (Value::Timestamp(_) | Value::Date(_), _)
+            | (_, Value::Timestamp(_) | Value::Date(_)) => {
+                match (self.as_timestamp(), other.as_timestamp()) {
+                    (Some(l), Some(r)) => Some(l.cmp(&r)),
+                    _ => Some(self.to_string().cmp(&other.to_string())),
+                }
+            }
+            (Value::Time(_) | Value::Interval(_), Value::Text(_)) => other
+                .cast(self.data_type()?)
+                .ok()
+                .and_then(|o| self.compare(&o)),
+            (Value::Text(_), Value::Time(_) | Value::Interval(_)) => self
+                .cast(other.data_type()?)
+                .ok()
+                .and_then(|s| s.compare(other)),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(l), Some(r)) => l.partial_cmp(&r),
+                _ => Some(self.to_string().cmp(&other.to_string())),
+            },
+        }
+    }
+
+    // Ordem total usada em ORDER BY: NULL fica depois de qualquer valor.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        match (self.is_null(), other.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.compare(other).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Boolean(b) => *b,
+            Value::Integer(i) => *i != 0,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            Value::Time(time) => write!(f, "{}", datetime::format_time(time)),
+            Value::Timestamp(ts) => write!(f, "{}", datetime::format_timestamp(ts)),
+            Value::Interval(interval) => write!(f, "{}", interval),
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use crate::query::{evaluate, infer_type, Context, OutputColumn};
+use crate::sql::{Expr, FrameBound, FrameUnits, FunctionCall, WindowSpec};
+use crate::value::{DataType, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+pub fn is_aggregate(name: &str) -> bool {
+    matches!(name, "COUNT" | "SUM" | "AVG" | "MIN" | "MAX")
+}
+
+pub fn infer_window_type(
+    call: &FunctionCall,
+    columns: &[OutputColumn],
+) -> Result<Option<DataType>, String> {
+    let spec = call.over.as_ref().expect("função sem OVER");
+    let nested = call
+        .args
+        .iter()
+        .chain(&spec.partition_by)
+        .any(Expr::contains_window_function)
+        || spec
+            .order_by
+            .iter()
+            .any(|o| o.expr.contains_window_function());
+    if nested {
+        return Err("Funções de janela não podem ser aninhadas.".to_string());
+    }
+    for expr in spec
+        .partition_by
+        .iter()
+        .chain(spec.order_by.iter().map(|o| &o.expr))
+    {
+        infer_type(expr, columns)?;
+    }
+
+    let arg_types = call
+        .args
+        .iter()
+        .map(|a| infer_type(a, columns))
+        .collect::<Result<Vec<_>, String>>()?;
+    let arity_error = || {
+        Err(format!(
+            "Número de argumentos inválido para a função '{}'.",
+            call.name
+        ))
+    };
+
+    match call.name.as_str() {
+        "ROW_NUMBER" | "RANK" | "DENSE_RANK" => {
+            if !call.args.is_empty() || call.wildcard {
+                return arity_error();
+            }
+            Ok(Some(DataType::Integer))
+        }
+        "LAG" | "LEAD" => {
+            if call.args.is_empty() || call.args.len() > 3 {
+                return arity_error();
+            }
+            if let Some(Some(t)) = arg_types.get(1) {
+                if *t != DataType::Integer {
+                    return Err(format!(
+                        "O deslocamento de '{}' deve ser INTEGER, não {}.",
+                        call.name, t
+                    ));
+                }
+            }
+            match arg_types.get(2) {
+                Some(default) => DataType::unify(arg_types[0], *default),
+                None => Ok(arg_types[0]),
+            }
+        }
+        "FIRST_VALUE" | "LAST_VALUE" | "MIN" | "MAX" => {
+            if call.args.len() != 1 {
+                return arity_error();
+            }
+            Ok(arg_types[0])
+        }
+        "COUNT" => {
+            if call.args.len() + call.wildcard as usize != 1 {
+                return arity_error();
+            }
+            Ok(Some(DataType::Integer))
+        }
+        "SUM" | "AVG" => {
+            if call.args.len() != 1 {
+                return arity_error();
+            }
+            match arg_types[0] {
+                Some(t) if !t.is_numeric() => Err(format!(
+                    "A função '{}' não se aplica ao tipo {}.",
+                    call.name, t
+                )),
+                Some(DataType::Integer) if call.name == "SUM" => Ok(Some(DataType::Integer)),
+                _ => Ok(Some(DataType::Float)),
+            }
+        }
+        _ => Err(format!("Função de janela '{}' não existe.", call.name)),
+    }
+}
+
+struct Partition {
+    rows: Vec<usize>,
+    peer_start: Vec<usize>,
+    peer_end: Vec<usize>,
+    dense_rank: Vec<usize>,
+}
+
+pub fn compute_window(
+    call: &FunctionCall,
+    columns: &[OutputColumn],
+    rows: &[Vec<Value>],
+    context: &Context,
+) -> Result<Vec<Value>, String> {
+    let spec = call.over.as_ref().expect("função sem OVER");
+    let mut results = vec![Value::Null; rows.len()];
+
+    for partition in partition_rows(spec, columns, rows, context)? {
+        let args = partition
+            .rows
+            .iter()
+            .map(|&i| {
+                call.args
+                    .iter()
+                    .map(|a| evaluate(a, columns, &rows[i], context))
+                    .collect::<Result<Vec<_>, String>>()
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        for position in 0..partition.rows.len() {
+            let value = match call.name.as_str() {
+                "ROW_NUMBER" => Value::Integer(position as i64 + 1),
+                "RANK" => Value::Integer(partition.peer_start[position] as i64 + 1),
+                "DENSE_RANK" => Value::Integer(partition.dense_rank[position] as i64),
+                "LAG" | "LEAD" => {
+                    let offset = match args[position].get(1) {
+                        Some(Value::Integer(n)) => Some(*n),
+                        Some(Value::Null) => None,
+                        Some(other) => {
+                            return Err(format!(
+                                "Deslocamento inválido '{}' para '{}'.",
+                                other, call.name
+                            ))
+                        }
+                        None => Some(1),
+                    };
+                    // Um deslocamento que estoura cai fora da partição e devolve o valor padrão.
+                    let target = offset.map(|offset| {
+                        if call.name == "LAG" {
+                            (position as i64).checked_sub(offset)
+                        } else {
+                            (position as i64).checked_add(offset)
+                        }
+                    });
+                    match target {
+                        None => Value::Null,
+                        Some(Some(t)) if t >= 0 && (t as usize) < args.len() => {
+                            args[t as usize][0].clone()
+                        }
+                        Some(_) => args[position].get(2).cloned().unwrap_or(Value::Null),
+                    }
+                }
+                _ => {
+                    let (start, end) = frame_bounds(spec, &partition, position);
+                    let frame: Vec<&Value> = (start..end)
+                        .map(|i| args[i].first().unwrap_or(&Value::Null))
+                        .collect();
+                    match call.name.as_str() {
+                        "FIRST_VALUE" => frame.first().map(|v| (*v).clone()).unwrap_or(Value::Null),
+                        "LAST_VALUE" => frame.last().map(|v| (*v).clone()).unwrap_or(Value::Null),
+                        "COUNT" if call.wildcard => Value::Integer(frame.len() as i64),
+                        _ => aggregate(&call.name, frame.into_iter())?,
+                    }
+                }
+            };
+            results[partition.rows[position]] = value;
+        }
+    }
+    Ok(results)
+}
+
+fn partition_rows(
+    spec: &WindowSpec,
+    columns: &[OutputColumn],
+    rows: &[Vec<Value>],
+    context: &Context,
+) -> Result<Vec<Partition>, String> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    let mut order_keys = Vec::with_capacity(rows.len());
+
+    for (i, row) in rows.iter().enumerate() {
+        let key = spec
+            .partition_by
+            .iter()
+            .map(|e| evaluate(e, columns, row, context))
+            .collect::<Result<Vec<_>, String>>()?;
+        let index = *group_index.entry(format!("{:?}", key)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(i);
+        order_keys.push(
+            spec.order_by
+                .iter()
+                .map(|o| evaluate(&o.expr, columns, row, context))
+                .collect::<Result<Vec<_>, String>>()?,
+        );
+    }
+
+    let compare = |a: usize, b: usize| -> Ordering {
+        for (item, (x, y)) in spec
+            .order_by
+            .iter()
+            .zip(order_keys[a].iter().zip(&order_keys[b]))
+        {
+            let ordering = x.sort_cmp(y);
+            let ordering = if item.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    };
+
+    Ok(groups
+        .into_iter()
+        .map(|mut rows| {
+            rows.sort_by(|&a, &b| compare(a, b));
+            let n = rows.len();
+            let mut peer_start = vec![0; n];
+            let mut dense_rank = vec![1; n];
+            for position in 1..n {
+                if compare(rows[position - 1], rows[position]) == Ordering::Equal {
+                    peer_start[position] = peer_start[position - 1];
+                    dense_rank[position] = dense_rank[position - 1];
+                } else {
+                    peer_start[position] = position;
+                    dense_rank[position] = dense_rank[position - 1] + 1;
+                }
+            }
+            let mut peer_end = vec![n.saturating_sub(1); n];
+            for position in (0..n.saturating_sub(1)).rev() {
+                if peer_start[position + 1] == peer_start[position] {
+                    peer_end[position] = peer_end[position + 1];
+                } else {
+                    peer_end[position] = position;
+                }
+            }
+            Partition {
+                rows,
+                peer_start,
+                peer_end,
+                dense_rank,
+            }
+        })
+        .collect())
+}
+
+// Retorna o intervalo [início, fim) do quadro da janela para a linha na posição informada.
+fn frame_bounds(spec: &WindowSpec, partition: &Partition, position: usize) -> (usize, usize) {
+    let n = partition.rows.len();
+    let frame = match &spec.frame {
+        Some(frame) => frame,
+        None if spec.order_by.is_empty() => return (0, n),
+        None => return (0, partition.peer_end[position] + 1),
+    };
+
+    let bound = |bound: FrameBound, is_start: bool| -> usize {
+        match bound {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::UnboundedFollowing => n,
+            FrameBound::CurrentRow if frame.units == FrameUnits::Range => {
+                if is_start {
+                    partition.peer_start[position]
+                } else {
+                    partition.peer_end[position] + 1
+                }
+            }
+            FrameBound::CurrentRow => position + !is_start as usize,
+            FrameBound::Preceding(k) => (position + !is_start as usize).saturating_sub(k),
+            FrameBound::Following(k) => (position + k + !is_start as usize).min(n),
+        }
+    };
+
+    let start = bound(frame.start, true);
+    let end = bound(frame.end, false);
+    (start, end.max(start))
+}
+
+pub fn aggregate<'a>(name: &str, values: impl Iterator<Item = &'a Value>) -> Result<Value, String> {
+    let values: Vec<&Value> = values.filter(|v| !v.is_null()).collect();
+    match name {
+        "COUNT" => Ok(Value::Integer(values.len() as i64)),
+        _ if values.is_empty() => Ok(Value::Null),
+        "SUM" if values.iter().all(|v| matches!(v, Value::Integer(_))) => values
+            .iter()
+            .try_fold(0i64, |acc, v| acc.checked_add(v.as_i64().unwrap_or(0)))
+            .map(Value::Integer)
+            .ok_or_else(|| "Estouro de inteiro.".to_string()),
+        "SUM" | "AVG" => {
+            let mut total = 0.0;
+            for value in &values {
+                total += value
+                    .as_f64()
+                    .ok_or_else(|| format!("Valor '{}' inválido para '{}'.", value, name))?;
+            }
+            if name == "AVG" {
+                total /= values.len() as f64;
+            }
+            Ok(Value::Float(total))
+        }
+        "MIN" | "MAX" => {
+            let mut best = values[0];
+            for value in &values[1..] {
+                let ordering = value.compare(best).unwrap_or(Ordering::Equal);
+                if (name == "MIN" && ordering == Ordering::Less)
+                    || (name == "MAX" && ordering == Ordering::Greater)
+                {
+                    best = value;
+                }
+            }
+            Ok(best.clone())
+        }
+        _ => Err(format!("Função de agregação '{}' não existe.", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::testing::TestDatabase;
+
+    fn database(name: &str) -> TestDatabase {
+        let mut test = TestDatabase::new(name);
+        test.exec("CREATE TABLE vendas (id INTEGER, regiao TEXT, valor INTEGER)");
+        test.exec(
+            "INSERT INTO vendas VALUES (1, 'sul', 10), (2, 'sul', 30), (3, 'norte', 20), \
+             (4, 'sul', 30), (5, 'norte', 5)",
+        );
+        test
+    }
+
+    #[test]
+    fn ranking_functions() {
+        let mut test = database("window_ranking");
+        assert_eq!(
+            test.rows(
+                "SELECT id, ROW_NUMBER() OVER (PARTITION BY regiao ORDER BY valor DESC, id), \
+                 RANK() OVER (ORDER BY valor DESC), DENSE_RANK() OVER (ORDER BY valor DESC) \
+                 FROM vendas ORDER BY id"
+            ),
+            [
+                "1 | 3 | 4 | 3",
+                "2 | 1 | 1 | 1",
+                "3 | 1 | 3 | 2",
+                "4 | 2 | 1 | 1",
+                "5 | 2 | 5 | 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn offset_and_value_functions() {
+        let mut test = database("window_offsets");
+        assert_eq!(
+            test.rows(
+                "SELECT id, LAG(valor) OVER (ORDER BY id), LEAD(valor, 2, 0) OVER (ORDER BY id), \
+                 FIRST_VALUE(valor) OVER (PARTITION BY regiao ORDER BY id), \
+                 LAST_VALUE(valor) OVER (PARTITION BY regiao ORDER BY id) \
+                 FROM vendas ORDER BY id"
+            ),
+            [
+                "1 | NULL | 20 | 10 | 10",
+                "2 | 10 | 30 | 10 | 30",
+                "3 | 30 | 5 | 20 | 20",
+                "4 | 20 | 0 | 10 | 30",
+                "5 | 30 | 0 | 20 | 5"
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_over_frames() {
+        let mut test = database("window_frames");
+        assert_eq!(
+            test.rows(
+                "SELECT id, SUM(valor) OVER (ORDER BY id), \
+                 SUM(valor) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING), \
+                 COUNT(*) OVER (PARTITION BY regiao), MAX(valor) OVER () \
+                 FROM vendas ORDER BY id"
+            ),
+            [
+                "1 | 10 | 40 | 3 | 30",
+                "2 | 40 | 60 | 3 | 30",
+                "3 | 60 | 80 | 2 | 30",
+                "4 | 90 | 55 | 3 | 30",
+                "5 | 95 | 35 | 2 | 30"
+            ]
+        );
+        // Em RANGE, as linhas empatadas no ORDER BY entram juntas no quadro.
+        assert_eq!(
+            test.rows("SELECT id, SUM(valor) OVER (ORDER BY valor) FROM vendas ORDER BY id"),
+            ["1 | 15", "2 | 95", "3 | 35", "4 | 95", "5 | 5"]
+        );
+    }
+}